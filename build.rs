@@ -4,6 +4,13 @@ use embed_manifest::manifest::{
 use embed_manifest::{embed_manifest, empty_manifest};
 
 fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // Only the Windows build has a manifest; other targets build the TUI.
+    if std::env::var_os("CARGO_CFG_WINDOWS").is_none() {
+        return;
+    }
+
     let manifest = empty_manifest()
         .name(env!("CARGO_PKG_NAME"))
         .version(
@@ -20,6 +27,4 @@ fn main() {
         .active_code_page(ActiveCodePage::Utf8)
         .heap_type(HeapType::SegmentHeap);
     embed_manifest(manifest).expect("unable to embed manifest file");
-
-    println!("cargo:rerun-if-changed=build.rs");
 }
@@ -0,0 +1,45 @@
+#[cfg(test)]
+use std::cell::Cell;
+#[cfg(test)]
+use std::rc::Rc;
+#[cfg(test)]
+use std::time::Duration;
+use std::time::Instant;
+
+/// Source of the current time, so that time-dependent logic can be tested deterministically.
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock which only moves when advanced.
+///
+/// Clones share the same time, so tests can keep a handle after passing a clone to the code under test.
+#[cfg(test)]
+#[derive(Clone)]
+pub struct ManualClock(Rc<Cell<Instant>>);
+
+#[cfg(test)]
+impl ManualClock {
+    pub fn new() -> Self {
+        Self(Rc::new(Cell::new(Instant::now())))
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.0.set(self.0.get() + duration);
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.0.get()
+    }
+}
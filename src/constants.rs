@@ -1,10 +1,17 @@
-use crate::opt::MicrophoneHotkey;
-use crate::utils::Unscaled;
-use windows::Win32::Foundation::{COLORREF, WPARAM};
+use crate::opt::{
+    DiskDisplay, DiskInstance, InterfaceKind, MemoryDisplay, MemoryUsage, MicrophoneHotkey,
+    NetworkDisplay,
+};
+use crate::utils::{Rgb, Unscaled};
+#[cfg(windows)]
+use windows::Win32::Foundation::WPARAM;
+#[cfg(windows)]
 use windows::Win32::UI::Input::KeyboardAndMouse::VK_OEM_2;
+#[cfg(windows)]
 use windows::Win32::UI::WindowsAndMessaging::{self, TIMERV_DEFAULT_COALESCING};
 
 // Startup parameters
+#[cfg(windows)]
 pub const EXISTING_PROCESS_SHUTDOWN_MS: u32 = 1000;
 
 // Sizing and positioning
@@ -77,17 +84,45 @@ pub const LABEL_WIDTH: Unscaled<i32> = Unscaled::new(32);
 pub const RIGHT_COLUMN_WIDTH: Unscaled<i32> = Unscaled::new(*LABEL_WIDTH.as_inner() + 28);
 // Microphone warning will be placed in the horizontal center of the display
 pub const MICROPHONE_WARNING_WIDTH: Unscaled<i32> = Unscaled::new(78); // ~ 48 * 1.618 (golden ratio)
+// CPU frequency will be placed between the left and right columns, if shown
+pub const FREQUENCY_VALUE_WIDTH: Unscaled<i32> = Unscaled::new(24); // fits "4.8"
+// Per-core CPU strip will be placed to the left of the left column's values, and any counters
+pub const LEFT_COLUMN_VALUE_WIDTH: Unscaled<i32> = Unscaled::new(60); // fits "1000 Mb/s"
+pub const LEFT_COLUMN_SPLIT_VALUE_WIDTH: Unscaled<i32> = Unscaled::new(100); // fits "↓1000 ↑1000 Mb/s" or "R1000 W1000 MB/s"
+// Counters from the config file and sensors are placed in columns to the left of the left column, two to a column
+pub const COUNTER_COLUMN_WIDTH: Unscaled<i32> =
+    Unscaled::new(*LEFT_COLUMN_VALUE_WIDTH.as_inner() + *LABEL_WIDTH.as_inner());
+pub const CPU_CORE_BAR_WIDTH: Unscaled<i32> = Unscaled::new(3);
+pub const CPU_CORE_BAR_SPACING: Unscaled<i32> = Unscaled::new(1);
+pub const CPU_CORE_STRIP_TOP: Unscaled<i32> = Unscaled::new(9);
+pub const CPU_CORE_STRIP_BOTTOM: Unscaled<i32> = Unscaled::new(37);
+// Beyond this many bars (or however many fit), adjacent cores share a bar showing their average
+pub const CPU_CORE_MAX_BARS: usize = 32;
+
+// Terminal layout, in columns
+pub const TUI_MICROPHONE_WARNING_WIDTH: usize = 4;
+pub const TUI_LEFT_VALUE_WIDTH: usize = 9; // fits "1000 Mb/s"
+pub const TUI_LEFT_SPLIT_VALUE_WIDTH: usize = 16; // fits "↓1000 ↑1000 Mb/s" or "R1000 W1000 MB/s"
+pub const TUI_RIGHT_VALUE_WIDTH: usize = 4; // fits "100%"
+pub const TUI_FREQUENCY_VALUE_WIDTH: usize = 4; // fits "10.0"
+pub const TUI_EXTRA_VALUE_WIDTH: usize = TUI_LEFT_VALUE_WIDTH;
 
 // Colors
-pub const DEBUG_BACKGROUND_COLOR: COLORREF = COLORREF(0x00_77_77); // yellow
-pub const MICROPHONE_WARNING_COLOR: COLORREF = COLORREF(0x00_00_99); // red
+pub const DEBUG_BACKGROUND_COLOR: Rgb = Rgb(0x77, 0x77, 0x00); // yellow
+pub const MICROPHONE_WARNING_COLOR: Rgb = Rgb(0x99, 0x00, 0x00); // red
+pub const THROTTLE_WARNING_COLOR: Rgb = Rgb(0x99, 0x66, 0x00); // amber
+pub const CPU_CORE_TRACK_COLOR: Rgb = Rgb(0x44, 0x44, 0x44); // dark gray
+pub const CPU_CORE_BAR_COLOR: Rgb = Rgb(0xff, 0xff, 0xff); // white
+pub const TEXT_COLOR: Rgb = Rgb(0xff, 0xff, 0xff); // white
 
 // File names
 pub const LOG_FILE_NAME: &str = "infoband.log";
 pub const CONFIG_FILE_NAME: &str = "infoband.json";
+#[cfg(windows)]
 pub const PID_FILE_NAME: &str = "infoband.pid";
 
 // Configuration
+#[cfg(windows)]
 pub const DEFAULT_MIC_HOTKEY: Option<MicrophoneHotkey> = if cfg!(debug_assertions) {
     // Enable by default when debugging so it's easier to test
     Some(MicrophoneHotkey {
@@ -101,35 +136,83 @@ pub const DEFAULT_MIC_HOTKEY: Option<MicrophoneHotkey> = if cfg!(debug_assertion
 } else {
     None
 };
+// Hotkeys are only supported on Windows
+#[cfg(not(windows))]
+pub const DEFAULT_MIC_HOTKEY: Option<MicrophoneHotkey> = None;
 // Enable by default when debugging so it's easier to test
 pub const DEFAULT_KEEP_AWAKE_WHILE_UNLOCKED: bool = cfg!(debug_assertions);
+pub const DEFAULT_MEMORY_USAGE: MemoryUsage = MemoryUsage::Available;
+pub const DEFAULT_SHOW_CPU_CORES: bool = false;
+pub const DEFAULT_SHOW_CPU_FREQUENCY: bool = false;
+pub const DEFAULT_SHOW_BATTERY: bool = false;
+pub const DEFAULT_MEMORY_DISPLAY: MemoryDisplay = MemoryDisplay::Used;
+pub const DEFAULT_NETWORK_DISPLAY: NetworkDisplay = NetworkDisplay::Total;
+pub const DEFAULT_DISK_DISPLAY: DiskDisplay = DiskDisplay::Total;
+pub const DEFAULT_DISK_INSTANCE: DiskInstance = DiskInstance::Total;
+// Tunnel and bridge traffic is counted again on the interface it's sent over
+pub const DEFAULT_EXCLUDED_INTERFACE_KINDS: &[InterfaceKind] = &[
+    InterfaceKind::Tunnel,
+    InterfaceKind::Loopback,
+    InterfaceKind::Bridge,
+];
+// Virtual Ethernet pairs and Docker's networks carry traffic between containers and the host
+#[cfg(target_os = "linux")]
+pub const DEFAULT_EXCLUDED_INTERFACE_NAMES: &[&str] = &["veth*", "docker*"];
+#[cfg(not(target_os = "linux"))]
+pub const DEFAULT_EXCLUDED_INTERFACE_NAMES: &[&str] = &[];
+pub const DEFAULT_COUNTER_SCALE: f64 = 1.0;
+pub const DEFAULT_TOP_PROCESS_COUNT: usize = 3;
 
 // User messages
+#[cfg(windows)]
 pub const UM_ENABLE_KEEP_AWAKE: WPARAM = WPARAM(1);
+#[cfg(windows)]
 pub const UM_ENABLE_DEBUG_PAINT: WPARAM = WPARAM(2);
+#[cfg(windows)]
 pub const UM_INITIAL_METRICS: WPARAM = WPARAM(3);
+#[cfg(windows)]
 pub const UM_INITIAL_MIC_STATE: WPARAM = WPARAM(4);
+#[cfg(windows)]
 pub const UM_INITIAL_RENDER: WPARAM = WPARAM(5);
+#[cfg(windows)]
 pub const UM_QUEUE_TRAY_POSITION_CHECK: WPARAM = WPARAM(6);
+#[cfg(windows)]
 pub const UM_QUEUE_MIC_STATE_CHECK: WPARAM = WPARAM(7);
+#[cfg(windows)]
+pub const UM_START_RECORDING: WPARAM = WPARAM(8);
+#[cfg(windows)]
+pub const UM_SET_DISPLAY_OPTIONS: WPARAM = WPARAM(9);
+#[cfg(windows)]
+pub const UM_SET_METRICS_OPTIONS: WPARAM = WPARAM(10);
 
 // Timer ids
+#[cfg(windows)]
 pub const IDT_FETCH_AND_REDRAW_TIMER: WPARAM = WPARAM(1);
+#[cfg(windows)]
 pub const IDT_TRAY_POSITION_TIMER: WPARAM = WPARAM(2);
+#[cfg(windows)]
 pub const IDT_Z_ORDER_TIMER: WPARAM = WPARAM(3);
+#[cfg(windows)]
 pub const IDT_MIC_STATE_TIMER: WPARAM = WPARAM(4);
 
 // Timer intervals
 pub const FETCH_TIMER_MS: u32 = 1000;
 pub const REDRAW_EVERY_N_FETCHES: usize = 5;
+#[cfg(windows)]
 pub const TRAY_POSITION_TIMER_MS: u32 = 10;
+#[cfg(windows)]
 pub const Z_ORDER_TIMER_MS: u32 = 50;
+#[cfg(windows)]
 pub const MIC_STATE_TIMER_MS: u32 = 10;
 
 // Timer coalescing delays
+#[cfg(windows)]
 pub const FETCH_AND_REDRAW_TIMER_COALESCE: u32 = 1000;
+#[cfg(windows)]
 pub const TRAY_POSITION_TIMER_COALESCE: u32 = TIMERV_DEFAULT_COALESCING; // usually something short like 32ms
+#[cfg(windows)]
 pub const Z_ORDER_TIMER_COALESCE: u32 = TIMERV_DEFAULT_COALESCING; // usually something short like 32ms
+#[cfg(windows)]
 pub const MIC_STATE_TIMER_COALESCE: u32 = TIMERV_DEFAULT_COALESCING; // usually something short like 32ms
 
 // Metrics
@@ -137,14 +220,21 @@ pub const SAMPLE_COUNT: usize = 8;
 pub const EXPONENTIAL_DECAY_ALPHA: f64 = 0.631; // 0.631^5 = 0.1, so 90% of the weight is for the last 5 samples
 
 // Shell hook messages
+#[cfg(windows)]
 pub const HSHELL_WINDOWACTIVATED: WPARAM = WPARAM(0x4);
+#[cfg(windows)]
 pub const HSHELL_RUDEAPPACTIVATED: WPARAM = WPARAM(0x8004);
 
 // WTS session change messages
+#[cfg(windows)]
 pub const WTS_SESSION_LOGON: WPARAM = WPARAM(WindowsAndMessaging::WTS_SESSION_LOGON as _);
+#[cfg(windows)]
 pub const WTS_SESSION_LOGOFF: WPARAM = WPARAM(WindowsAndMessaging::WTS_SESSION_LOGOFF as _);
+#[cfg(windows)]
 pub const WTS_SESSION_LOCK: WPARAM = WPARAM(WindowsAndMessaging::WTS_SESSION_LOCK as _);
+#[cfg(windows)]
 pub const WTS_SESSION_UNLOCK: WPARAM = WPARAM(WindowsAndMessaging::WTS_SESSION_UNLOCK as _);
 
 // Hotkey ids
+#[cfg(windows)]
 pub const HOTKEY_MIC_MUTE: WPARAM = WPARAM(1);
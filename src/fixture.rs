@@ -0,0 +1,56 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A temporary directory tree, e.g. a fake `/sys` or `/proc`, which is deleted when dropped.
+pub struct Fixture {
+    root: PathBuf,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+
+        let root = std::env::temp_dir().join(format!("infoband-{}-{id}", std::process::id()));
+        if let Err(e) = fs::create_dir_all(&root) {
+            panic!("Failed to create fixture dir `{}`: {e}", root.display());
+        }
+
+        Self { root }
+    }
+
+    pub fn path(&self, relative: impl AsRef<Path>) -> PathBuf {
+        self.root.join(relative)
+    }
+
+    /// Create or overwrite a file, along with any missing parent directories.
+    pub fn file(&self, relative: impl AsRef<Path>, contents: &str) -> &Self {
+        let path = self.path(relative);
+        if let Some(parent) = path.parent() {
+            self.create_dir(parent);
+        }
+        if let Err(e) = fs::write(&path, contents) {
+            panic!("Failed to write fixture file `{}`: {e}", path.display());
+        }
+        self
+    }
+
+    /// Create an empty directory, along with any missing parent directories.
+    pub fn dir(&self, relative: impl AsRef<Path>) -> &Self {
+        self.create_dir(&self.path(relative));
+        self
+    }
+
+    fn create_dir(&self, path: &Path) {
+        if let Err(e) = fs::create_dir_all(path) {
+            panic!("Failed to create fixture dir `{}`: {e}", path.display());
+        }
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        _ = fs::remove_dir_all(&self.root);
+    }
+}
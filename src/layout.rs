@@ -0,0 +1,1089 @@
+use crate::constants::{
+    COUNTER_COLUMN_WIDTH, CPU_CORE_BAR_SPACING, CPU_CORE_BAR_WIDTH, CPU_CORE_MAX_BARS,
+    CPU_CORE_STRIP_BOTTOM, CPU_CORE_STRIP_TOP, FIRST_LINE_MIDPOINT_OFFSET_FROM_TOP,
+    FREQUENCY_VALUE_WIDTH, LABEL_WIDTH, LEFT_COLUMN_SPLIT_VALUE_WIDTH, LEFT_COLUMN_VALUE_WIDTH,
+    MICROPHONE_WARNING_WIDTH, RIGHT_COLUMN_WIDTH, SECOND_LINE_MIDPOINT_OFFSET_FROM_TOP,
+};
+use crate::metrics::{Battery, Metrics, PowerState, ProcessUsage, SensorKind, TopProcesses};
+use crate::opt::{CounterMetric, DiskDisplay, MemoryDisplay, NetworkDisplay, PressureSeries};
+use crate::utils::{ScaleBy, ScalingFactor};
+
+/// Metric values to display.
+#[derive(Clone)]
+pub struct Values {
+    pub cpu: f64,
+    /// Effective CPU frequency in MHz, if available.
+    pub cpu_frequency: Option<f64>,
+    pub mem: f64,
+    pub swap: f64,
+    /// Memory pressure as a percentage, if available.
+    pub mem_pressure: Option<f64>,
+    pub net: f64,
+    pub net_received: f64,
+    pub net_sent: f64,
+    pub dsk: f64,
+    pub dsk_read: f64,
+    pub dsk_written: f64,
+    /// CPU usage of each logical processor, if available.
+    pub cpu_cores: Vec<f64>,
+    /// Cells to the left of the built-in metrics, e.g. counters from the config file, in order.
+    pub extra_cells: Vec<ExtraCell>,
+    /// Battery status, if there is one.
+    pub battery: Option<Battery>,
+}
+
+/// A metric shown to the left of the built-in ones, already formatted, since each has its own unit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExtraCell {
+    pub value: String,
+    pub label: String,
+}
+
+impl Values {
+    pub fn from_metrics(metrics: &Metrics) -> Self {
+        Self {
+            cpu: metrics.avg_cpu_percent(),
+            cpu_frequency: metrics.avg_cpu_frequency_mhz(),
+            mem: metrics.avg_memory_percent(),
+            swap: metrics.avg_swap_percent(),
+            mem_pressure: metrics.memory_pressure(),
+            net: metrics.avg_network_mbit(),
+            net_received: metrics.avg_network_mbit_received(),
+            net_sent: metrics.avg_network_mbit_sent(),
+            dsk: metrics.avg_disk_mbyte(),
+            dsk_read: metrics.avg_disk_mbyte_read(),
+            dsk_written: metrics.avg_disk_mbyte_written(),
+            cpu_cores: metrics.avg_cpu_core_percents(),
+            extra_cells: metrics
+                .avg_counters()
+                .iter()
+                .map(|(metric, value)| ExtraCell {
+                    value: format_counter(metric, *value),
+                    label: metric.label.clone(),
+                })
+                .chain(
+                    metrics
+                        .avg_sensor()
+                        .map(|(kind, value)| format_sensor(kind, value)),
+                )
+                .chain(
+                    metrics
+                        .selected_pressures()
+                        .into_iter()
+                        .map(|(series, value)| format_pressure_series(series, value)),
+                )
+                .collect(),
+            battery: metrics.battery(),
+        }
+    }
+}
+
+/// How to display metrics, as set in the config file.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Options {
+    /// Whether to show the per-core CPU strip.
+    pub cpu_cores: bool,
+    /// Whether to show the battery, after any other extra cells.
+    pub battery: bool,
+    /// Whether to show the effective CPU frequency, next to CPU usage.
+    pub cpu_frequency: bool,
+    /// Highlight the CPU frequency when it's below this, e.g. when throttled.
+    pub cpu_frequency_warning_ghz: Option<f64>,
+    pub memory: MemoryDisplay,
+    pub network: NetworkDisplay,
+    pub disk: DiskDisplay,
+}
+
+impl Options {
+    /// Whether any value in the left column shows both directions, making it wider.
+    pub fn is_left_column_split(&self) -> bool {
+        self.network == NetworkDisplay::Both || self.disk == DiskDisplay::Both
+    }
+}
+
+/// Format the effective CPU frequency in GHz, e.g. `3.9`, or `None` if it isn't shown.
+pub fn format_cpu_frequency(values: &Values, options: Options) -> Option<String> {
+    let mhz = values.cpu_frequency.filter(|_| options.cpu_frequency)?;
+    Some(format!("{:.1}", mhz / 1000.0))
+}
+
+/// Whether the CPU frequency is shown, and below the warning threshold.
+pub fn is_cpu_frequency_low(values: &Values, options: Options) -> bool {
+    match (values.cpu_frequency, options.cpu_frequency_warning_ghz) {
+        (Some(mhz), Some(ghz)) => options.cpu_frequency && mhz < ghz * 1000.0,
+        _ => false,
+    }
+}
+
+/// Label of the RAM cell, which depends on what it shows.
+pub fn memory_label(options: Options) -> &'static str {
+    match options.memory {
+        MemoryDisplay::Used => "RAM",
+        MemoryDisplay::Swap => "SWP",
+        MemoryDisplay::Pressure => "PSI",
+    }
+}
+
+/// Format memory usage, e.g. `42%`, or `-` if memory pressure isn't available.
+pub fn format_memory(values: &Values, options: Options) -> String {
+    let Values {
+        mem,
+        swap,
+        mem_pressure,
+        ..
+    } = values;
+    match options.memory {
+        MemoryDisplay::Used => format!("{mem:.0}%"),
+        MemoryDisplay::Swap => format!("{swap:.0}%"),
+        MemoryDisplay::Pressure => match mem_pressure {
+            Some(pressure) => format_pressure(*pressure),
+            None => "-".to_owned(),
+        },
+    }
+}
+
+/// Format network bandwidth, e.g. `12 Mb/s` or `↓12 ↑3 Mb/s`.
+pub fn format_network(values: &Values, options: Options) -> String {
+    let Values {
+        net,
+        net_received,
+        net_sent,
+        ..
+    } = values;
+    match options.network {
+        NetworkDisplay::Total => format!("{net:.0} Mb/s"),
+        NetworkDisplay::Both => format!("↓{net_received:.0} ↑{net_sent:.0} Mb/s"),
+        NetworkDisplay::Download => format!("↓{net_received:.0} Mb/s"),
+        NetworkDisplay::Upload => format!("↑{net_sent:.0} Mb/s"),
+    }
+}
+
+/// Format disk bandwidth, e.g. `12 MB/s` or `R12 W3 MB/s`.
+pub fn format_disk(values: &Values, options: Options) -> String {
+    let Values {
+        dsk,
+        dsk_read,
+        dsk_written,
+        ..
+    } = values;
+    match options.disk {
+        DiskDisplay::Total => format!("{dsk:.0} MB/s"),
+        DiskDisplay::Both => format!("R{dsk_read:.0} W{dsk_written:.0} MB/s"),
+        DiskDisplay::Read => format!("R{dsk_read:.0} MB/s"),
+        DiskDisplay::Write => format!("W{dsk_written:.0} MB/s"),
+    }
+}
+
+/// Format a counter from the config file, e.g. `45%` or `3.2 MB/s`.
+pub fn format_counter(metric: &CounterMetric, value: f64) -> String {
+    let CounterMetric { unit, decimals, .. } = metric;
+    format!("{value:.decimals$}{unit}")
+}
+
+/// Format a temperature or fan sensor, e.g. `45°C TMP` or `2900 RPM FAN`.
+pub fn format_sensor(kind: SensorKind, value: f64) -> ExtraCell {
+    let (value, label) = match kind {
+        SensorKind::Temperature => (format!("{value:.0}°C"), "TMP"),
+        SensorKind::Fan => (format!("{value:.0} RPM"), "FAN"),
+    };
+    ExtraCell {
+        value,
+        label: label.to_owned(),
+    }
+}
+
+/// Format a pressure stall percentage, e.g. `3.2%` or `45%`, keeping a decimal for small values since any stall matters.
+pub fn format_pressure(value: f64) -> String {
+    if value < 10.0 {
+        format!("{value:.1}%")
+    } else {
+        format!("{value:.0}%")
+    }
+}
+
+/// Format a pressure stall series, e.g. `S3.2% MEM` for memory `some` or `F45% I/O` for I/O `full`.
+pub fn format_pressure_series(series: PressureSeries, value: f64) -> ExtraCell {
+    let (kind, label) = match series {
+        PressureSeries::CpuSome => ("S", "CPU"),
+        PressureSeries::CpuFull => ("F", "CPU"),
+        PressureSeries::MemorySome => ("S", "MEM"),
+        PressureSeries::MemoryFull => ("F", "MEM"),
+        PressureSeries::IoSome => ("S", "I/O"),
+        PressureSeries::IoFull => ("F", "I/O"),
+    };
+    ExtraCell {
+        value: format!("{kind}{}", format_pressure(value)),
+        label: label.to_owned(),
+    }
+}
+
+/// Format the battery, e.g. `↓42% 1:05 BAT` when discharging with an hour and 5 minutes left.
+pub fn format_battery(battery: Battery) -> ExtraCell {
+    let Battery {
+        percent,
+        state,
+        seconds_remaining,
+    } = battery;
+    let arrow = match state {
+        PowerState::Charging => "↑",
+        PowerState::Discharging => "↓",
+        PowerState::PluggedIn | PowerState::Unknown => "",
+    };
+    let remaining = match seconds_remaining {
+        Some(seconds) => {
+            let minutes = seconds / 60;
+            format!(" {}:{:02}", minutes / 60, minutes % 60)
+        }
+        None => String::new(),
+    };
+    ExtraCell {
+        value: format!("{arrow}{percent:.0}%{remaining}"),
+        label: "BAT".to_owned(),
+    }
+}
+
+/// All cells to the left of the built-in metrics, in order, including the ones that depend on `options`.
+pub fn extra_cells(values: &Values, options: Options) -> Vec<ExtraCell> {
+    let battery = values
+        .battery
+        .filter(|_| options.battery)
+        .map(format_battery);
+    values.extra_cells.iter().cloned().chain(battery).collect()
+}
+
+/// Format the busiest processes as one line per resource, e.g. `CPU  chrome 45%, code 12%`,
+/// leaving out resources which no process is using.
+pub fn format_top_processes(top: &TopProcesses) -> Vec<String> {
+    let TopProcesses { cpu, memory, io } = top;
+    let line = |label: &str, usages: &[ProcessUsage], value: fn(&ProcessUsage) -> String| {
+        let processes = usages
+            .iter()
+            .map(|usage| format!("{} {}", usage.name, value(usage)))
+            .collect::<Vec<_>>();
+        (!processes.is_empty()).then(|| format!("{label}  {}", processes.join(", ")))
+    };
+    [
+        line("CPU", cpu, |usage| format!("{:.0}%", usage.cpu_percent)),
+        line("RAM", memory, |usage| {
+            let mbyte = usage.working_set_mbyte;
+            if mbyte < 1000.0 {
+                format!("{mbyte:.0} MB")
+            } else {
+                format!("{:.1} GB", mbyte / 1000.0)
+            }
+        }),
+        line("I/O", io, |usage| format!("{:.1} MB/s", usage.io_mbyte)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Rectangle in physical pixels, relative to the top left of the window.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Fill {
+    DebugBackground,
+    MicrophoneWarning,
+    ThrottleWarning,
+    CpuCoreTrack,
+    CpuCoreBar,
+}
+
+/// Which part of a text run is placed at its anchor point.
+///
+/// Text is always vertically centered on its anchor point.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// A single drawing operation.
+///
+/// Text is positioned by an anchor point instead of a rect, since its size depends on the font,
+/// which only the backend knows.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Item {
+    Rect {
+        rect: Rect,
+        fill: Fill,
+    },
+    Text {
+        text: String,
+        x: i32,
+        y: i32,
+        align: Align,
+    },
+}
+
+/// Group the usage of each logical processor into at most `max_bars` bars, averaging adjacent cores.
+fn group_cpu_cores(cpu_cores: &[f64], max_bars: usize) -> Vec<f64> {
+    if max_bars == 0 {
+        return Vec::new();
+    }
+    let cores_per_bar = cpu_cores.len().div_ceil(max_bars);
+    cpu_cores
+        .chunks(cores_per_bar)
+        .map(|cores| cores.iter().sum::<f64>() / cores.len() as f64)
+        .collect()
+}
+
+/// Lay out the window content, in drawing order.
+///
+/// `width` and `height` are the size of the window in physical pixels.
+pub fn layout(
+    width: i32,
+    height: i32,
+    dpi: ScalingFactor,
+    values: &Values,
+    options: Options,
+    is_muted: bool,
+    debug: bool,
+) -> Vec<Item> {
+    let mut items = Vec::new();
+
+    let text = |text: &str, x, y, align| Item::Text {
+        text: text.to_owned(),
+        x,
+        y,
+        align,
+    };
+
+    // When debugging is enabled, fill in window background.
+
+    if debug {
+        items.push(Item::Rect {
+            rect: Rect {
+                left: 0,
+                top: 0,
+                right: width,
+                bottom: height,
+            },
+            fill: Fill::DebugBackground,
+        });
+    }
+
+    // Draw microphone warning if unmuted
+
+    if !is_muted {
+        let warning_width = MICROPHONE_WARNING_WIDTH.scale_by(dpi);
+
+        items.push(Item::Rect {
+            rect: Rect {
+                left: 0,
+                top: 0,
+                right: warning_width,
+                bottom: height,
+            },
+            fill: Fill::MicrophoneWarning,
+        });
+
+        items.push(text("🎤", warning_width / 2, height / 2, Align::Center));
+    }
+
+    // Draw metrics
+
+    let Values {
+        cpu, ref cpu_cores, ..
+    } = *values;
+    let extra_cells = extra_cells(values, options);
+
+    let right_column = width - LABEL_WIDTH.scale_by(dpi);
+
+    // The CPU frequency gets its own column, pushing the left column over.
+    let frequency = format_cpu_frequency(values, options);
+    let frequency_column = width - RIGHT_COLUMN_WIDTH.scale_by(dpi) - LABEL_WIDTH.scale_by(dpi);
+    let left_column = if frequency.is_some() {
+        frequency_column - FREQUENCY_VALUE_WIDTH.scale_by(dpi) - LABEL_WIDTH.scale_by(dpi)
+    } else {
+        frequency_column
+    };
+
+    if is_cpu_frequency_low(values, options) {
+        items.push(Item::Rect {
+            rect: Rect {
+                left: frequency_column - FREQUENCY_VALUE_WIDTH.scale_by(dpi),
+                top: 0,
+                right: frequency_column + LABEL_WIDTH.scale_by(dpi),
+                bottom: height,
+            },
+            fill: Fill::ThrottleWarning,
+        });
+    }
+
+    let left_column_value_width = if options.is_left_column_split() {
+        LEFT_COLUMN_SPLIT_VALUE_WIDTH
+    } else {
+        LEFT_COLUMN_VALUE_WIDTH
+    };
+    let left_column_values = left_column - left_column_value_width.scale_by(dpi);
+
+    // Extra cells fill columns leftwards, top line first
+    let counter_columns = extra_cells.len().div_ceil(2) as i32;
+    let counter_column = |i: i32| {
+        left_column_values
+            - (i * *COUNTER_COLUMN_WIDTH.as_inner() + *LABEL_WIDTH.as_inner()).scale_by(dpi)
+    };
+
+    // Draw per-core strip, with one bar per logical processor (or group of them), filled from the bottom
+
+    if options.cpu_cores && !cpu_cores.is_empty() {
+        let pitch = *CPU_CORE_BAR_WIDTH.as_inner() + *CPU_CORE_BAR_SPACING.as_inner();
+        let strip_right =
+            left_column_values - (counter_columns * *COUNTER_COLUMN_WIDTH.as_inner()).scale_by(dpi);
+        // Don't run past the left edge of the window, or the microphone warning.
+        let strip_limit = if is_muted {
+            0
+        } else {
+            MICROPHONE_WARNING_WIDTH.scale_by(dpi)
+        };
+        let fitting_bars =
+            usize::try_from((strip_right - strip_limit) / pitch.scale_by(dpi)).unwrap_or_default();
+        let bars = group_cpu_cores(cpu_cores, fitting_bars.min(CPU_CORE_MAX_BARS));
+        let count = bars.len() as i32;
+        let strip_left =
+            strip_right - (count * pitch - *CPU_CORE_BAR_SPACING.as_inner()).scale_by(dpi);
+        let top = CPU_CORE_STRIP_TOP.scale_by(dpi);
+        let bottom = CPU_CORE_STRIP_BOTTOM.scale_by(dpi);
+
+        for (i, &percent) in (0..).zip(&bars) {
+            // Scale edges rather than widths, so bars stay evenly spaced at fractional scales.
+            let left = strip_left + (i * pitch).scale_by(dpi);
+            let right = strip_left + (i * pitch + *CPU_CORE_BAR_WIDTH.as_inner()).scale_by(dpi);
+            let filled =
+                (f64::from(bottom - top) * percent.clamp(0.0, 100.0) / 100.0).round() as i32;
+
+            items.push(Item::Rect {
+                rect: Rect {
+                    left,
+                    top,
+                    right,
+                    bottom: bottom - filled,
+                },
+                fill: Fill::CpuCoreTrack,
+            });
+            items.push(Item::Rect {
+                rect: Rect {
+                    left,
+                    top: bottom - filled,
+                    right,
+                    bottom,
+                },
+                fill: Fill::CpuCoreBar,
+            });
+        }
+    }
+
+    let first_line_midpoint = FIRST_LINE_MIDPOINT_OFFSET_FROM_TOP.scale_by(dpi);
+    let second_line_midpoint = SECOND_LINE_MIDPOINT_OFFSET_FROM_TOP.scale_by(dpi);
+
+    items.extend([
+        text(" CPU", right_column, first_line_midpoint, Align::Left),
+        text(
+            &format!(" {}", memory_label(options)),
+            right_column,
+            second_line_midpoint,
+            Align::Left,
+        ),
+        text(" NET", left_column, first_line_midpoint, Align::Left),
+        text(" DSK", left_column, second_line_midpoint, Align::Left),
+    ]);
+
+    items.extend([
+        text(
+            &format!("{cpu:.0}%"),
+            right_column,
+            first_line_midpoint,
+            Align::Right,
+        ),
+        text(
+            &format_memory(values, options),
+            right_column,
+            second_line_midpoint,
+            Align::Right,
+        ),
+        text(
+            &format_network(values, options),
+            left_column,
+            first_line_midpoint,
+            Align::Right,
+        ),
+        text(
+            &format_disk(values, options),
+            left_column,
+            second_line_midpoint,
+            Align::Right,
+        ),
+    ]);
+
+    if let Some(frequency) = frequency {
+        items.extend([
+            text(" GHz", frequency_column, first_line_midpoint, Align::Left),
+            text(
+                &frequency,
+                frequency_column,
+                first_line_midpoint,
+                Align::Right,
+            ),
+        ]);
+    }
+
+    for (i, ExtraCell { value, label }) in (0..).zip(&extra_cells) {
+        let x = counter_column(i / 2);
+        let y = if i % 2 == 0 {
+            first_line_midpoint
+        } else {
+            second_line_midpoint
+        };
+        items.extend([
+            text(&format!(" {label}"), x, y, Align::Left),
+            text(value, x, y, Align::Right),
+        ]);
+    }
+
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALUES: Values = Values {
+        cpu: 6.4,
+        cpu_frequency: Some(1234.0),
+        mem: 41.5,
+        swap: 12.6,
+        mem_pressure: Some(3.2),
+        net: 12.2,
+        net_received: 9.4,
+        net_sent: 2.8,
+        dsk: 1234.0,
+        dsk_read: 1000.0,
+        dsk_written: 234.0,
+        cpu_cores: Vec::new(),
+        extra_cells: Vec::new(),
+        battery: None,
+    };
+
+    fn snapshot(scale_percent: u32, is_muted: bool, debug: bool) -> String {
+        snapshot_with(scale_percent, &VALUES, Options::default(), is_muted, debug)
+    }
+
+    fn snapshot_with(
+        scale_percent: u32,
+        values: &Values,
+        options: Options,
+        is_muted: bool,
+        debug: bool,
+    ) -> String {
+        let dpi = ScalingFactor::from_ratio(scale_percent, 100);
+        let width = 900.scale_by(dpi);
+        let height = 48.scale_by(dpi);
+
+        let mut snapshot = String::new();
+        for item in layout(width, height, dpi, values, options, is_muted, debug) {
+            snapshot += &match item {
+                Item::Rect { rect, fill } => format!(
+                    "rect {fill:?} ({}, {}) - ({}, {})\n",
+                    rect.left, rect.top, rect.right, rect.bottom
+                ),
+                Item::Text { text, x, y, align } => {
+                    format!("text {align:?} ({x}, {y}) {text:?}\n")
+                }
+            };
+        }
+        snapshot
+    }
+
+    #[test]
+    fn muted_at_100_percent() {
+        assert_eq!(
+            snapshot(100, true, false),
+            "\
+text Left (868, 15) \" CPU\"
+text Left (868, 31) \" RAM\"
+text Left (808, 15) \" NET\"
+text Left (808, 31) \" DSK\"
+text Right (868, 15) \"6%\"
+text Right (868, 31) \"42%\"
+text Right (808, 15) \"12 Mb/s\"
+text Right (808, 31) \"1234 MB/s\"
+"
+        );
+    }
+
+    #[test]
+    fn unmuted_at_150_percent() {
+        assert_eq!(
+            snapshot(150, false, false),
+            "\
+rect MicrophoneWarning (0, 0) - (117, 72)
+text Center (58, 36) \"🎤\"
+text Left (1302, 22) \" CPU\"
+text Left (1302, 46) \" RAM\"
+text Left (1212, 22) \" NET\"
+text Left (1212, 46) \" DSK\"
+text Right (1302, 22) \"6%\"
+text Right (1302, 46) \"42%\"
+text Right (1212, 22) \"12 Mb/s\"
+text Right (1212, 46) \"1234 MB/s\"
+"
+        );
+    }
+
+    #[test]
+    fn unmuted_with_debug_at_200_percent() {
+        assert_eq!(
+            snapshot(200, false, true),
+            "\
+rect DebugBackground (0, 0) - (1800, 96)
+rect MicrophoneWarning (0, 0) - (156, 96)
+text Center (78, 48) \"🎤\"
+text Left (1736, 30) \" CPU\"
+text Left (1736, 62) \" RAM\"
+text Left (1616, 30) \" NET\"
+text Left (1616, 62) \" DSK\"
+text Right (1736, 30) \"6%\"
+text Right (1736, 62) \"42%\"
+text Right (1616, 30) \"12 Mb/s\"
+text Right (1616, 62) \"1234 MB/s\"
+"
+        );
+    }
+
+    #[test]
+    fn cpu_cores_at_150_percent() {
+        let values = Values {
+            cpu_cores: vec![100.0, 0.0, 49.0],
+            ..VALUES
+        };
+        let options = Options {
+            cpu_cores: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            snapshot_with(150, &values, options, true, false),
+            "\
+rect CpuCoreTrack (1106, 13) - (1110, 13)
+rect CpuCoreBar (1106, 13) - (1110, 55)
+rect CpuCoreTrack (1112, 13) - (1116, 55)
+rect CpuCoreBar (1112, 55) - (1116, 55)
+rect CpuCoreTrack (1118, 13) - (1122, 34)
+rect CpuCoreBar (1118, 34) - (1122, 55)
+text Left (1302, 22) \" CPU\"
+text Left (1302, 46) \" RAM\"
+text Left (1212, 22) \" NET\"
+text Left (1212, 46) \" DSK\"
+text Right (1302, 22) \"6%\"
+text Right (1302, 46) \"42%\"
+text Right (1212, 22) \"12 Mb/s\"
+text Right (1212, 46) \"1234 MB/s\"
+"
+        );
+    }
+
+    #[test]
+    fn many_cpu_cores_at_100_percent() {
+        // Every fourth core is busy, so each bar averages to 25%.
+        let values = Values {
+            cpu_cores: (0..128)
+                .map(|i| if i % 4 == 0 { 100.0 } else { 0.0 })
+                .collect(),
+            ..VALUES
+        };
+        let options = Options {
+            cpu_cores: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            snapshot_with(100, &values, options, true, false),
+            "\
+rect CpuCoreTrack (621, 9) - (624, 30)
+rect CpuCoreBar (621, 30) - (624, 37)
+rect CpuCoreTrack (625, 9) - (628, 30)
+rect CpuCoreBar (625, 30) - (628, 37)
+rect CpuCoreTrack (629, 9) - (632, 30)
+rect CpuCoreBar (629, 30) - (632, 37)
+rect CpuCoreTrack (633, 9) - (636, 30)
+rect CpuCoreBar (633, 30) - (636, 37)
+rect CpuCoreTrack (637, 9) - (640, 30)
+rect CpuCoreBar (637, 30) - (640, 37)
+rect CpuCoreTrack (641, 9) - (644, 30)
+rect CpuCoreBar (641, 30) - (644, 37)
+rect CpuCoreTrack (645, 9) - (648, 30)
+rect CpuCoreBar (645, 30) - (648, 37)
+rect CpuCoreTrack (649, 9) - (652, 30)
+rect CpuCoreBar (649, 30) - (652, 37)
+rect CpuCoreTrack (653, 9) - (656, 30)
+rect CpuCoreBar (653, 30) - (656, 37)
+rect CpuCoreTrack (657, 9) - (660, 30)
+rect CpuCoreBar (657, 30) - (660, 37)
+rect CpuCoreTrack (661, 9) - (664, 30)
+rect CpuCoreBar (661, 30) - (664, 37)
+rect CpuCoreTrack (665, 9) - (668, 30)
+rect CpuCoreBar (665, 30) - (668, 37)
+rect CpuCoreTrack (669, 9) - (672, 30)
+rect CpuCoreBar (669, 30) - (672, 37)
+rect CpuCoreTrack (673, 9) - (676, 30)
+rect CpuCoreBar (673, 30) - (676, 37)
+rect CpuCoreTrack (677, 9) - (680, 30)
+rect CpuCoreBar (677, 30) - (680, 37)
+rect CpuCoreTrack (681, 9) - (684, 30)
+rect CpuCoreBar (681, 30) - (684, 37)
+rect CpuCoreTrack (685, 9) - (688, 30)
+rect CpuCoreBar (685, 30) - (688, 37)
+rect CpuCoreTrack (689, 9) - (692, 30)
+rect CpuCoreBar (689, 30) - (692, 37)
+rect CpuCoreTrack (693, 9) - (696, 30)
+rect CpuCoreBar (693, 30) - (696, 37)
+rect CpuCoreTrack (697, 9) - (700, 30)
+rect CpuCoreBar (697, 30) - (700, 37)
+rect CpuCoreTrack (701, 9) - (704, 30)
+rect CpuCoreBar (701, 30) - (704, 37)
+rect CpuCoreTrack (705, 9) - (708, 30)
+rect CpuCoreBar (705, 30) - (708, 37)
+rect CpuCoreTrack (709, 9) - (712, 30)
+rect CpuCoreBar (709, 30) - (712, 37)
+rect CpuCoreTrack (713, 9) - (716, 30)
+rect CpuCoreBar (713, 30) - (716, 37)
+rect CpuCoreTrack (717, 9) - (720, 30)
+rect CpuCoreBar (717, 30) - (720, 37)
+rect CpuCoreTrack (721, 9) - (724, 30)
+rect CpuCoreBar (721, 30) - (724, 37)
+rect CpuCoreTrack (725, 9) - (728, 30)
+rect CpuCoreBar (725, 30) - (728, 37)
+rect CpuCoreTrack (729, 9) - (732, 30)
+rect CpuCoreBar (729, 30) - (732, 37)
+rect CpuCoreTrack (733, 9) - (736, 30)
+rect CpuCoreBar (733, 30) - (736, 37)
+rect CpuCoreTrack (737, 9) - (740, 30)
+rect CpuCoreBar (737, 30) - (740, 37)
+rect CpuCoreTrack (741, 9) - (744, 30)
+rect CpuCoreBar (741, 30) - (744, 37)
+rect CpuCoreTrack (745, 9) - (748, 30)
+rect CpuCoreBar (745, 30) - (748, 37)
+text Left (868, 15) \" CPU\"
+text Left (868, 31) \" RAM\"
+text Left (808, 15) \" NET\"
+text Left (808, 31) \" DSK\"
+text Right (868, 15) \"6%\"
+text Right (868, 31) \"42%\"
+text Right (808, 15) \"12 Mb/s\"
+text Right (808, 31) \"1234 MB/s\"
+"
+        );
+    }
+
+    fn cell(value: &str, label: &str) -> ExtraCell {
+        ExtraCell {
+            value: value.to_owned(),
+            label: label.to_owned(),
+        }
+    }
+
+    #[test]
+    fn extra_cells_at_100_percent() {
+        let values = Values {
+            cpu_cores: vec![100.0, 0.0],
+            extra_cells: vec![
+                cell("37%", "GPU"),
+                cell("2.3 GB", "VRM"),
+                cell("45°C", "TMP"),
+            ],
+            ..VALUES
+        };
+        let options = Options {
+            cpu_cores: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            snapshot_with(100, &values, options, true, false),
+            "\
+rect CpuCoreTrack (557, 9) - (560, 9)
+rect CpuCoreBar (557, 9) - (560, 37)
+rect CpuCoreTrack (561, 9) - (564, 37)
+rect CpuCoreBar (561, 37) - (564, 37)
+text Left (868, 15) \" CPU\"
+text Left (868, 31) \" RAM\"
+text Left (808, 15) \" NET\"
+text Left (808, 31) \" DSK\"
+text Right (868, 15) \"6%\"
+text Right (868, 31) \"42%\"
+text Right (808, 15) \"12 Mb/s\"
+text Right (808, 31) \"1234 MB/s\"
+text Left (716, 15) \" GPU\"
+text Right (716, 15) \"37%\"
+text Left (716, 31) \" VRM\"
+text Right (716, 31) \"2.3 GB\"
+text Left (624, 15) \" TMP\"
+text Right (624, 15) \"45°C\"
+"
+        );
+    }
+
+    #[test]
+    fn cpu_frequency_at_100_percent() {
+        let options = Options {
+            cpu_frequency: true,
+            cpu_frequency_warning_ghz: Some(2.0),
+            ..Default::default()
+        };
+        assert_eq!(
+            snapshot_with(100, &VALUES, options, true, false),
+            "\
+rect ThrottleWarning (784, 0) - (840, 48)
+text Left (868, 15) \" CPU\"
+text Left (868, 31) \" RAM\"
+text Left (752, 15) \" NET\"
+text Left (752, 31) \" DSK\"
+text Right (868, 15) \"6%\"
+text Right (868, 31) \"42%\"
+text Right (752, 15) \"12 Mb/s\"
+text Right (752, 31) \"1234 MB/s\"
+text Left (808, 15) \" GHz\"
+text Right (808, 15) \"1.2\"
+"
+        );
+    }
+
+    #[test]
+    fn cpu_frequency_warning() {
+        let options = Options {
+            cpu_frequency: true,
+            cpu_frequency_warning_ghz: Some(1.0),
+            ..Default::default()
+        };
+        assert!(!is_cpu_frequency_low(&VALUES, options));
+        let options = Options {
+            cpu_frequency_warning_ghz: Some(1.5),
+            ..options
+        };
+        assert!(is_cpu_frequency_low(&VALUES, options));
+        // Only when shown
+        let options = Options {
+            cpu_frequency: false,
+            ..options
+        };
+        assert!(!is_cpu_frequency_low(&VALUES, options));
+        assert_eq!(format_cpu_frequency(&VALUES, options), None);
+    }
+
+    #[test]
+    fn battery_formats() {
+        let battery = |percent, state, seconds_remaining| {
+            format_battery(Battery {
+                percent,
+                state,
+                seconds_remaining,
+            })
+        };
+        assert_eq!(
+            battery(42.4, PowerState::Discharging, Some(65 * 60 + 59)),
+            cell("↓42% 1:05", "BAT")
+        );
+        assert_eq!(
+            battery(80.0, PowerState::Charging, Some(9 * 60)),
+            cell("↑80% 0:09", "BAT")
+        );
+        assert_eq!(
+            battery(100.0, PowerState::PluggedIn, None),
+            cell("100%", "BAT")
+        );
+    }
+
+    #[test]
+    fn battery_is_the_last_extra_cell() {
+        let values = Values {
+            extra_cells: vec![cell("37%", "GPU")],
+            battery: Some(Battery {
+                percent: 100.0,
+                state: PowerState::PluggedIn,
+                seconds_remaining: None,
+            }),
+            ..VALUES
+        };
+        assert_eq!(
+            extra_cells(&values, Options::default()),
+            [cell("37%", "GPU")]
+        );
+        let options = Options {
+            battery: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            extra_cells(&values, options),
+            [cell("37%", "GPU"), cell("100%", "BAT")]
+        );
+
+        // No battery, e.g. on a desktop
+        let values = Values {
+            battery: None,
+            ..values
+        };
+        assert_eq!(extra_cells(&values, options), [cell("37%", "GPU")]);
+    }
+
+    #[test]
+    fn top_processes_format() {
+        let usage = |name: &str, cpu_percent, working_set_mbyte, io_mbyte| ProcessUsage {
+            name: name.to_owned(),
+            cpu_percent,
+            working_set_mbyte,
+            io_mbyte,
+        };
+        let top = TopProcesses {
+            cpu: vec![
+                usage("rustc", 187.6, 900.0, 0.0),
+                usage("chrome", 12.2, 2140.0, 0.0),
+            ],
+            memory: vec![
+                usage("chrome", 12.2, 2140.0, 0.0),
+                usage("rustc", 187.6, 900.0, 0.0),
+            ],
+            io: Vec::new(),
+        };
+        assert_eq!(
+            format_top_processes(&top),
+            [
+                "CPU  rustc 188%, chrome 12%",
+                "RAM  chrome 2.1 GB, rustc 900 MB"
+            ]
+        );
+
+        // Only memory is known until processes have been read twice
+        let top = TopProcesses {
+            cpu: Vec::new(),
+            memory: vec![usage("chrome", 0.0, 2140.0, 0.0)],
+            io: vec![usage("cp", 0.0, 3.0, 120.25)],
+        };
+        assert_eq!(
+            format_top_processes(&top),
+            ["RAM  chrome 2.1 GB", "I/O  cp 120.2 MB/s"]
+        );
+        assert_eq!(format_top_processes(&TopProcesses::default()), [""; 0]);
+    }
+
+    #[test]
+    fn counter_formats() {
+        let metric = CounterMetric {
+            unit: "%".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(format_counter(&metric, 37.6), "38%");
+        let metric = CounterMetric {
+            unit: " GB".to_owned(),
+            decimals: 2,
+            ..Default::default()
+        };
+        assert_eq!(format_counter(&metric, 2.0), "2.00 GB");
+    }
+
+    #[test]
+    fn sensor_formats() {
+        assert_eq!(
+            format_sensor(SensorKind::Temperature, 45.4),
+            cell("45°C", "TMP")
+        );
+        assert_eq!(
+            format_sensor(SensorKind::Fan, 2900.0),
+            cell("2900 RPM", "FAN")
+        );
+    }
+
+    #[test]
+    fn cpu_cores_hidden_by_default() {
+        let values = Values {
+            cpu_cores: vec![100.0, 0.0, 49.0],
+            ..VALUES
+        };
+        assert_eq!(
+            snapshot_with(100, &values, Options::default(), true, false),
+            snapshot(100, true, false)
+        );
+    }
+
+    #[test]
+    fn memory_swap() {
+        let options = Options {
+            memory: MemoryDisplay::Swap,
+            ..Default::default()
+        };
+        assert_eq!(memory_label(options), "SWP");
+        assert_eq!(format_memory(&VALUES, options), "13%");
+        assert_eq!(memory_label(Options::default()), "RAM");
+        assert_eq!(format_memory(&VALUES, Options::default()), "42%");
+    }
+
+    #[test]
+    fn memory_pressure() {
+        let options = Options {
+            memory: MemoryDisplay::Pressure,
+            ..Default::default()
+        };
+        assert_eq!(memory_label(options), "PSI");
+        assert_eq!(format_memory(&VALUES, options), "3.2%");
+        let values = Values {
+            mem_pressure: None,
+            ..VALUES
+        };
+        assert_eq!(format_memory(&values, options), "-");
+    }
+
+    #[test]
+    fn pressure_formats() {
+        assert_eq!(format_pressure(0.0), "0.0%");
+        assert_eq!(format_pressure(9.94), "9.9%");
+        assert_eq!(format_pressure(45.4), "45%");
+        assert_eq!(
+            format_pressure_series(PressureSeries::MemorySome, 3.25),
+            cell("S3.2%", "MEM")
+        );
+        assert_eq!(
+            format_pressure_series(PressureSeries::IoFull, 12.0),
+            cell("F12%", "I/O")
+        );
+    }
+
+    #[test]
+    fn network_directions() {
+        let network = |network| {
+            format_network(
+                &VALUES,
+                Options {
+                    network,
+                    ..Default::default()
+                },
+            )
+        };
+        assert_eq!(network(NetworkDisplay::Total), "12 Mb/s");
+        assert_eq!(network(NetworkDisplay::Both), "↓9 ↑3 Mb/s");
+        assert_eq!(network(NetworkDisplay::Download), "↓9 Mb/s");
+        assert_eq!(network(NetworkDisplay::Upload), "↑3 Mb/s");
+    }
+
+    #[test]
+    fn disk_directions() {
+        let disk = |disk| {
+            format_disk(
+                &VALUES,
+                Options {
+                    disk,
+                    ..Default::default()
+                },
+            )
+        };
+        assert_eq!(disk(DiskDisplay::Total), "1234 MB/s");
+        assert_eq!(disk(DiskDisplay::Both), "R1000 W234 MB/s");
+        assert_eq!(disk(DiskDisplay::Read), "R1000 MB/s");
+        assert_eq!(disk(DiskDisplay::Write), "W234 MB/s");
+    }
+}
@@ -11,8 +11,12 @@
 // Prevent the automatic console window you get on startup.
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use crate::constants::{CONFIG_FILE_NAME, LOG_FILE_NAME, PID_FILE_NAME};
-use constants::EXISTING_PROCESS_SHUTDOWN_MS;
+use crate::constants::{CONFIG_FILE_NAME, LOG_FILE_NAME};
+use crate::metrics::Metrics;
+use crate::metrics::record::{Recorder, Recording};
+use crate::utils::ScalingFactor;
+#[cfg(windows)]
+use constants::{EXISTING_PROCESS_SHUTDOWN_MS, PID_FILE_NAME};
 use log::LevelFilter;
 use log4rs::Config;
 use log4rs::append::console::{ConsoleAppender, Target};
@@ -20,76 +24,326 @@ use log4rs::append::file::FileAppender;
 use log4rs::config::{Appender, Root};
 use log4rs::encode::pattern::PatternEncoder;
 use std::env;
+use std::error::Error;
 use std::fs::{self, File};
 use std::io;
 use std::path::{Path, PathBuf};
+#[cfg(windows)]
 use windows::Win32::Foundation::{CloseHandle, WAIT_OBJECT_0, WAIT_TIMEOUT};
+#[cfg(windows)]
 use windows::Win32::System::ProcessStatus::GetModuleFileNameExW;
+#[cfg(windows)]
 use windows::Win32::System::Threading::{
     GetCurrentProcessId, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_SYNCHRONIZE,
     PROCESS_TERMINATE, TerminateProcess, WaitForSingleObject,
 };
-use windows::core::{Error, Result, w};
+#[cfg(windows)]
+use windows::core::w;
 
+// Only used by the Windows UI.
+#[cfg(windows)]
 mod macros;
 
+mod clock;
 mod constants;
+#[cfg(test)]
+mod fixture;
+mod layout;
 mod metrics;
 mod opt;
 mod perf;
+mod render;
+mod schedule;
 mod stats;
+mod tui;
 mod utils;
+#[cfg(windows)]
 mod window;
 
-fn main() -> Result<()> {
+fn main() -> Result<(), Box<dyn Error>> {
     let opt::Cli {
         verbose,
         debug_paint,
+        tui,
+        record,
+        replay,
+        list_interfaces,
+        list_sensors,
+        command,
     } = argh::from_env();
 
+    // Commands are one-off tools, so they don't need logging, config, or to replace a running instance.
+    if let Some(command) = command {
+        return run_command(command);
+    }
+
     // Init logging as early as possible.
     let config = if cfg!(debug_assertions) {
         // In debug builds, don't create log/config files
         init_logging(None, verbose);
         Default::default()
     } else {
-        // In release (installed) builds, create log/config files in local appdata.
-        let path = make_local_appdata_folder();
+        // In release (installed) builds, create log/config files in the app data folder.
+        let path = make_app_data_folder();
         init_logging(Some(&path.join(LOG_FILE_NAME)), verbose);
-        kill_and_write_pid_file(&path.join(PID_FILE_NAME));
+        // The terminal frontend and diagnostics can run alongside the band, so don't replace it.
+        #[cfg(windows)]
+        if !tui && !list_interfaces && !list_sensors {
+            kill_and_write_pid_file(&path.join(PID_FILE_NAME));
+        }
         load_config_file(&path.join(CONFIG_FILE_NAME))
     };
 
     let opt::ConfigFile {
         mic_hotkey,
         keep_awake_while_unlocked,
+        keep_awake_min_battery_percent,
+        memory_usage,
+        show_cpu_cores,
+        show_cpu_frequency,
+        cpu_frequency_warning_ghz,
+        show_battery,
+        memory_display,
+        network_display,
+        disk_display,
+        network_interfaces,
+        disk_instance,
+        counters,
+        sensor,
+        top_processes,
+        pressure,
     } = config;
 
+    let display_options = layout::Options {
+        cpu_cores: show_cpu_cores,
+        battery: show_battery,
+        cpu_frequency: show_cpu_frequency,
+        cpu_frequency_warning_ghz,
+        memory: memory_display,
+        network: network_display,
+        disk: disk_display,
+    };
+
+    let metrics_options = metrics::Options {
+        cpu_frequency: show_cpu_frequency,
+        network_interfaces,
+        disk_instance,
+        counters,
+        sensor,
+        top_processes,
+        pressure,
+    };
+
     log::info!("Started up infoband {}", env!("CARGO_PKG_VERSION"));
 
-    if let Err(e) =
-        window::create_and_run_message_loop(mic_hotkey, keep_awake_while_unlocked, debug_paint)
-    {
+    #[cfg(windows)]
+    if memory_usage != opt::MemoryUsage::Available {
+        log::warn!(
+            "Memory usage `{memory_usage:?}` is not supported on Windows, using `Available`"
+        );
+    }
+
+    #[cfg(windows)]
+    if memory_display == opt::MemoryDisplay::Pressure || !metrics_options.pressure.is_empty() {
+        log::warn!("Memory pressure is not available on Windows");
+    }
+
+    if list_interfaces {
+        for line in metrics::describe_network_interfaces(metrics_options.network_interfaces)? {
+            println!("{line}");
+        }
+        return Ok(());
+    }
+
+    if list_sensors {
+        for line in metrics::describe_sensors()? {
+            println!("{line}");
+        }
+        return Ok(());
+    }
+
+    if let Some(path) = replay {
+        if let Err(e) = replay_recording(&path, memory_usage, metrics_options) {
+            log::error!("Failed to replay recording `{}`: {e}", path.display());
+            return Err(e);
+        }
+        return Ok(());
+    }
+
+    let recorder = match record {
+        Some(path) => match Recorder::create(&path) {
+            Ok(recorder) => {
+                log::info!("Recording raw counters to `{}`", path.display());
+                Some(recorder)
+            }
+            Err(e) => {
+                log::error!("Failed to create recording `{}`: {e}", path.display());
+                return Err(e.into());
+            }
+        },
+        None => None,
+    };
+
+    if tui {
+        let metrics = Metrics::new(memory_usage)?;
+        metrics.set_options(metrics_options);
+        if let Some(recorder) = recorder {
+            metrics.start_recording(recorder);
+        }
+        if let Err(e) = tui::run(&metrics, display_options) {
+            log::error!("Failed to render to terminal: {e}");
+            return Err(e.into());
+        }
+        return Ok(());
+    }
+
+    #[cfg(windows)]
+    if let Err(e) = window::create_and_run_message_loop(
+        mic_hotkey,
+        keep_awake_while_unlocked,
+        keep_awake_min_battery_percent,
+        display_options,
+        metrics_options,
+        debug_paint,
+        recorder,
+    ) {
         log::error!("Failed to create and run message loop: {e}");
-        return Err(e);
+        return Err(e.into());
+    }
+
+    #[cfg(not(windows))]
+    let _ = (
+        mic_hotkey,
+        keep_awake_while_unlocked,
+        keep_awake_min_battery_percent,
+        display_options,
+        metrics_options,
+        debug_paint,
+        recorder,
+    );
+
+    if cfg!(not(windows)) {
+        log::error!("The taskbar frontend is not available on this platform, use --tui instead");
+        return Err("unsupported platform".into());
     }
 
     Ok(())
 }
 
-fn make_local_appdata_folder() -> PathBuf {
-    let Some(local_appdata) = env::var_os("LOCALAPPDATA") else {
+fn run_command(command: opt::Command) -> Result<(), Box<dyn Error>> {
+    match command {
+        opt::Command::Render(opt::Render {
+            out,
+            dpi,
+            muted,
+            debug_paint,
+            cpu_cores,
+            cpu_frequency,
+            battery,
+            cpu_frequency_warning_ghz,
+            memory,
+            network,
+            disk,
+        }) => {
+            if dpi == 0 {
+                return Err("Scaling must be at least 1%".into());
+            }
+            let dpi = ScalingFactor::from_ratio(dpi, 100);
+            let image = render::render(
+                dpi,
+                layout::Options {
+                    cpu_cores,
+                    battery,
+                    cpu_frequency,
+                    cpu_frequency_warning_ghz,
+                    memory,
+                    network,
+                    disk,
+                },
+                muted,
+                debug_paint,
+            );
+            if let Err(e) = fs::write(&out, image.to_png()) {
+                return Err(format!("Failed to write `{}`: {e}", out.display()).into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn replay_recording(
+    path: &Path,
+    memory_usage: opt::MemoryUsage,
+    metrics_options: metrics::Options,
+) -> Result<(), Box<dyn Error>> {
+    let recording = Recording::load(path)?;
+    let metrics = Metrics::replay(memory_usage, &recording)?;
+    metrics.set_options(metrics_options);
+
+    for _ in 0..recording.fetch_count() {
+        let fetch_count = metrics.fetch();
+        let counters = metrics
+            .avg_counters()
+            .into_iter()
+            .map(|(metric, value)| format!("\t{} {value}", metric.label))
+            .chain(
+                metrics
+                    .avg_cpu_frequency_mhz()
+                    .map(|mhz| format!("\tMHz {mhz}")),
+            )
+            .chain(
+                metrics
+                    .avg_sensor()
+                    .map(|(kind, value)| format!("\t{kind:?} {value}")),
+            )
+            .chain(
+                metrics
+                    .battery()
+                    .map(|battery| format!("\tBAT {} {:?}", battery.percent, battery.state)),
+            )
+            .chain(
+                metrics
+                    .selected_pressures()
+                    .into_iter()
+                    .map(|(series, value)| format!("\t{series:?} {value}")),
+            )
+            .chain(
+                layout::format_top_processes(&metrics.top_processes())
+                    .into_iter()
+                    .map(|line| format!("\t{line}")),
+            )
+            .collect::<String>();
+        println!(
+            "{fetch_count}\tCPU {}\tRAM {}\tSWP {}\tNET {}\tDSK {}{counters}",
+            metrics.avg_cpu_percent(),
+            metrics.avg_memory_percent(),
+            metrics.avg_swap_percent(),
+            metrics.avg_network_mbit(),
+            metrics.avg_disk_mbyte(),
+        );
+    }
+
+    Ok(())
+}
+
+fn make_app_data_folder() -> PathBuf {
+    #[cfg(windows)]
+    let Some(mut path) = env::var_os("LOCALAPPDATA").map(PathBuf::from) else {
         panic!("Failed to get LOCALAPPDATA environment variable.");
     };
+    #[cfg(not(windows))]
+    let Some(mut path) = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+    else {
+        panic!("Failed to get XDG_DATA_HOME or HOME environment variable.");
+    };
 
-    let mut path = PathBuf::from(local_appdata);
     path.push("infoband");
 
     if let Err(e) = fs::create_dir_all(&path) {
-        panic!(
-            "Failed to create local appdata folder `{}`: {e}",
-            path.display()
-        );
+        panic!("Failed to create app data folder `{}`: {e}", path.display());
     }
 
     path
@@ -121,6 +375,7 @@ fn init_logging(path: Option<&Path>, verbose: u8) {
     .unwrap();
 }
 
+#[cfg(windows)]
 fn kill_and_write_pid_file(path: &Path) {
     fn kill_existing_process(path: &Path) {
         let pid = match fs::read_to_string(path) {
@@ -156,7 +411,7 @@ fn kill_and_write_pid_file(path: &Path) {
             0 => {
                 return log::warn!(
                     "Failed to get process name for pid {pid}: {}",
-                    Error::from_thread()
+                    windows::core::Error::from_thread()
                 );
             }
             len => len,
@@ -185,7 +440,7 @@ fn kill_and_write_pid_file(path: &Path) {
             ),
             _ => log::warn!(
                 "Failed to wait for existing instance with pid {pid} to exit: {}",
-                Error::from_thread()
+                windows::core::Error::from_thread()
             ),
         }
     }
@@ -1,115 +1,979 @@
+use crate::clock::{Clock, SystemClock};
 use crate::constants::{EXPONENTIAL_DECAY_ALPHA, SAMPLE_COUNT};
+pub use crate::metrics::battery::{Battery, PowerState};
+pub use crate::metrics::pressure::Pressures;
+pub use crate::metrics::processes::{ProcessUsage, TopProcesses};
+use crate::metrics::record::{Recorder, Recording, Replay};
+pub use crate::metrics::sensors::SensorKind;
+use crate::opt::{
+    CounterMetric, DiskInstance, InterfaceRules, MemoryUsage, PressureSeries, ProcessSampling,
+};
 use crate::stats::CircularBuffer;
-use std::cell::Cell;
-use std::time::Instant;
-use windows::core::Result;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::cell::{Cell, RefCell};
+use std::error::Error;
+#[cfg(target_os = "linux")]
+use std::io::Result as SysResult;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+#[cfg(windows)]
+use windows::core::Result as SysResult;
 
+mod battery;
+mod counter;
 mod cpu;
 mod disk;
+#[cfg(test)]
+mod fake;
 mod memory;
 mod network;
+mod pressure;
+mod processes;
+pub mod record;
+mod sensors;
+
+/// A source of samples for a single metric, e.g. CPU usage or network bandwidth.
+///
+/// Fetching is split into reading raw counters and converting them into a sample,
+/// so that the raw counters can be recorded and replayed through the same conversion later.
+pub trait MetricSource {
+    /// Raw counters read from the system, e.g. cumulative CPU times or byte counts.
+    type Raw: Serialize + DeserializeOwned;
+    /// What raw counters are converted into: a number to average, or the latest status for metrics which aren't
+    /// averaged, like the battery.
+    type Sample;
+    type Error: Error + 'static;
+
+    /// Name of the metric, used for logging and to identify it in recordings.
+    fn name(&self) -> &str;
+
+    /// Unit of the samples returned by `convert`, if they're numbers.
+    fn unit(&self) -> &str {
+        ""
+    }
+
+    /// Read raw counters.
+    fn read(&self) -> Result<Self::Raw, Self::Error>;
+
+    /// Convert raw counters into a new sample.
+    ///
+    /// `time_delta` is the time since the previous fetch, or `None` if this is the first fetch.
+    fn convert(&self, raw: Self::Raw, time_delta: Option<Duration>) -> Self::Sample;
+
+    /// Read raw counters and convert them into a new sample.
+    fn fetch(&self, time_delta: Option<Duration>) -> Result<Self::Sample, Self::Error> {
+        let raw = self.read()?;
+        Ok(self.convert(raw, time_delta))
+    }
+}
+
+/// Object-safe version of `MetricSource`, with the raw and error types erased.
+trait DynMetricSource {
+    type Sample;
+    fn name(&self) -> &str;
+    fn unit(&self) -> &str;
+    fn fetch(
+        &self,
+        time_delta: Option<Duration>,
+        recorder: Option<&Recorder>,
+    ) -> Result<Self::Sample, Box<dyn Error>>;
+}
+
+impl<S: MetricSource> DynMetricSource for S {
+    type Sample = S::Sample;
+
+    fn name(&self) -> &str {
+        MetricSource::name(self)
+    }
+
+    fn unit(&self) -> &str {
+        MetricSource::unit(self)
+    }
+
+    fn fetch(
+        &self,
+        time_delta: Option<Duration>,
+        recorder: Option<&Recorder>,
+    ) -> Result<S::Sample, Box<dyn Error>> {
+        let Some(recorder) = recorder else {
+            return Ok(MetricSource::fetch(self, time_delta)?);
+        };
+
+        let raw = self.read();
+        recorder.record(MetricSource::name(self), time_delta, &raw);
+        Ok(self.convert(raw?, time_delta))
+    }
+}
+
+/// Metric source whose samples may be missing, e.g. a rate when no time has passed since the previous fetch.
+struct Optional<S>(S);
+
+impl<S: MetricSource<Sample: Into<Option<f64>>>> MetricSource for Optional<S> {
+    type Raw = S::Raw;
+    type Sample = Option<f64>;
+    type Error = S::Error;
+
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn unit(&self) -> &str {
+        self.0.unit()
+    }
+
+    fn read(&self) -> Result<S::Raw, S::Error> {
+        self.0.read()
+    }
+
+    fn convert(&self, raw: S::Raw, time_delta: Option<Duration>) -> Option<f64> {
+        self.0.convert(raw, time_delta).into()
+    }
+}
+
+/// A metric source, along with the samples it has produced.
+struct Series {
+    source: Box<dyn DynMetricSource<Sample = Option<f64>>>,
+    samples: CircularBuffer<f64, SAMPLE_COUNT>,
+}
+
+impl Series {
+    fn new(source: impl MetricSource<Sample: Into<Option<f64>>> + 'static) -> Self {
+        Self {
+            source: Box::new(Optional(source)),
+            samples: Default::default(),
+        }
+    }
+
+    fn fetch(&self, time_delta: Option<Duration>, recorder: Option<&Recorder>) {
+        let name = self.source.name();
+        match self.source.fetch(time_delta, recorder) {
+            Ok(Some(sample)) => {
+                log::trace!("Fetched {name}: {sample:.3} {}", self.source.unit());
+                self.samples.push(sample);
+            }
+            Ok(None) => log::trace!("Fetched {name}: no sample"),
+            Err(e) => log::error!("Failed to fetch {name}: {e}"),
+        }
+    }
+
+    fn average(&self) -> f64 {
+        self.samples
+            .exponential_moving_average(EXPONENTIAL_DECAY_ALPHA)
+    }
+}
+
+/// A metric source which isn't averaged, along with its latest sample, e.g. the battery status.
+struct Snapshot<T> {
+    source: Box<dyn DynMetricSource<Sample = T>>,
+    latest: RefCell<T>,
+}
+
+impl<T: Clone + Default> Snapshot<T> {
+    fn new(source: impl MetricSource<Sample = T> + 'static) -> Self {
+        Self {
+            source: Box::new(source),
+            latest: Default::default(),
+        }
+    }
+
+    /// Fetch a new sample, keeping the previous one if that fails.
+    fn fetch(&self, time_delta: Option<Duration>, recorder: Option<&Recorder>) {
+        let name = self.source.name();
+        match self.source.fetch(time_delta, recorder) {
+            Ok(sample) => {
+                log::trace!("Fetched {name}");
+                *self.latest.borrow_mut() = sample;
+            }
+            Err(e) => log::error!("Failed to fetch {name}: {e}"),
+        }
+    }
+
+    fn latest(&self) -> T {
+        self.latest.borrow().clone()
+    }
+}
+
+/// Samples of a throughput metric in each direction, e.g. download and upload, or disk reads and writes.
+///
+/// Updated by the metric's source, alongside the samples of the total in its `Series`.
+#[derive(Default)]
+pub struct Directions {
+    incoming: CircularBuffer<f64, SAMPLE_COUNT>,
+    outgoing: CircularBuffer<f64, SAMPLE_COUNT>,
+}
+
+impl Directions {
+    fn push(&self, incoming: f64, outgoing: f64) {
+        self.incoming.push(incoming);
+        self.outgoing.push(outgoing);
+    }
+
+    fn average_incoming(&self) -> f64 {
+        self.incoming
+            .exponential_moving_average(EXPONENTIAL_DECAY_ALPHA)
+    }
+
+    fn average_outgoing(&self) -> f64 {
+        self.outgoing
+            .exponential_moving_average(EXPONENTIAL_DECAY_ALPHA)
+    }
+}
+
+/// Increase in a cumulative counter which wraps after `max`, e.g. `u32::MAX as u64` for a 32-bit counter.
+///
+/// A counter going backwards either wrapped, or was reset, e.g. because the interface or disk was re-created.
+/// A wrap is assumed only if the resulting delta is less than half of the counter's range;
+/// otherwise it's a reset, and since the counts before it are unknown, the delta is zero.
+/// 64-bit counters effectively never wrap, so for them, going backwards is a reset unless the counter was within
+/// half of its range of `u64::MAX`, which only happens with corrupted counters, e.g. in a hand-edited recording.
+fn counter_delta(prev: u64, current: u64, max: u64) -> u64 {
+    if current >= prev {
+        return current - prev;
+    }
+
+    // Can't overflow: `max - prev + current < max`, since `current < prev`.
+    let wrapped = max - prev + current + 1;
+    if wrapped <= max / 2 { wrapped } else { 0 }
+}
+
+/// Samples which sources update alongside their totals, e.g. per-core CPU usage, and settings they read.
+#[derive(Default)]
+struct Details {
+    /// Samples of CPU usage for each logical processor, updated along with `cpu`.
+    cpu_cores: Rc<cpu::Cores>,
+
+    /// Samples of the effective processor frequency, updated along with `cpu` if enabled.
+    cpu_frequency: Rc<cpu::Frequencies>,
+
+    /// Samples of swap usage (or commit charge on Windows) as a percentage, updated along with `memory`.
+    memory_swap: Rc<CircularBuffer<f64, SAMPLE_COUNT>>,
+
+    /// Samples of disk bandwidth read and written, updated along with `disk`.
+    disk_directions: Rc<Directions>,
+
+    /// Samples of network bandwidth received and sent, updated along with `network`.
+    network_directions: Rc<Directions>,
+
+    /// Which disk or volume `disk` measures, read whenever it's fetched.
+    disk_instance: Rc<RefCell<DiskInstance>>,
+
+    /// Which interfaces count toward `network`, applied whenever it's fetched.
+    network_rules: Rc<RefCell<InterfaceRules>>,
+
+    /// Which sensor `sensor` shows, read whenever it's fetched.
+    sensor_selection: Rc<RefCell<Option<String>>>,
+
+    /// Kind of the sensor shown by `sensor`, updated along with it.
+    sensor_kind: Rc<Cell<Option<SensorKind>>>,
+
+    /// Whether `processes` reads processes, set before each fetch.
+    processes_enabled: Rc<Cell<bool>>,
+
+    /// How many processes `processes` lists for each resource, read whenever it's fetched.
+    process_count: Rc<Cell<usize>>,
+}
+
+impl Details {
+    fn of(
+        cpu: &cpu::State,
+        memory: &memory::State,
+        disk: &disk::State,
+        network: &network::State,
+        sensors: &sensors::State,
+        processes: &processes::State,
+    ) -> Self {
+        Self {
+            cpu_cores: cpu.cores(),
+            cpu_frequency: cpu.frequency(),
+            memory_swap: memory.swap(),
+            disk_directions: disk.directions(),
+            network_directions: network.directions(),
+            disk_instance: disk.instance(),
+            network_rules: network.rules(),
+            sensor_selection: sensors.selection(),
+            sensor_kind: sensors.kind(),
+            processes_enabled: processes.enabled(),
+            process_count: processes.count(),
+        }
+    }
+}
+
+/// Settings for how metrics are collected, which can be changed after creating `Metrics`.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Whether to read the processor frequency, which is only needed if it's shown.
+    pub cpu_frequency: bool,
+    pub network_interfaces: InterfaceRules,
+    pub disk_instance: DiskInstance,
+    pub counters: Vec<CounterMetric>,
+    /// Glob matching the temperature or fan sensor to show, if any.
+    pub sensor: Option<String>,
+    pub top_processes: ProcessSampling,
+    /// Pressure stall series to show, in order.
+    pub pressure: Vec<PressureSeries>,
+}
+
+/// A metric read from a performance counter, as declared in the config file, along with its samples.
+struct Counter {
+    metric: CounterMetric,
+    series: Series,
+}
 
 pub struct Metrics {
+    clock: Box<dyn Clock>,
+
     /// Timestamp of the last time metrics were fetched.
     prev_time: Cell<Option<Instant>>,
 
-    cpu: cpu::State,
     /// Samples of CPU usage as a percentage of total CPU time.
-    cpu_percent: CircularBuffer<f64, SAMPLE_COUNT>,
+    cpu: Series,
 
-    memory: memory::State,
     /// Samples of memory usage as a percentage of total memory.
-    memory_percent: CircularBuffer<f64, SAMPLE_COUNT>,
+    memory: Series,
 
-    disk: disk::State,
     /// Samples of disk bandwidth in megabytes per second.
-    disk_mbyte: CircularBuffer<f64, SAMPLE_COUNT>,
+    disk: Series,
 
-    /// Count of network bytes transferred at the time of the previous fetch.
-    network: network::State,
     /// Samples of network bandwidth in megabits per second.
-    network_mbit: CircularBuffer<f64, SAMPLE_COUNT>,
+    network: Series,
+
+    /// Samples of the selected sensor, in degrees Celsius or RPM.
+    sensor: Series,
+
+    /// Latest battery status, or `None` if there's no battery.
+    battery: Snapshot<Option<Battery>>,
+
+    /// Latest pressure stall information, or `None` if it isn't available.
+    ///
+    /// This isn't averaged again, since the kernel already averages it over 10 seconds.
+    pressure: Snapshot<Option<Pressures>>,
+
+    /// Pressure stall series to show, in order.
+    pressure_selection: RefCell<Vec<PressureSeries>>,
+
+    /// Processes using the most CPU, memory, and I/O as of the latest fetch, or empty while processes aren't read.
+    processes: Snapshot<TopProcesses>,
+
+    /// Whether processes are wanted regardless of thresholds, e.g. while the tooltip listing them is open.
+    processes_wanted: Cell<bool>,
+
+    /// Thresholds above which processes are read.
+    process_sampling: Cell<ProcessSampling>,
+
+    details: Details,
+
+    /// Samples of each counter from the config file, in units set by the config file.
+    counters: RefCell<Vec<Counter>>,
+
+    /// If set, counters are replayed from here instead of being read from the system.
+    recording: Option<Recording>,
 
     /// Number of times that metrics have been fetched (wrapping).
     fetch_count: Cell<usize>,
+
+    /// If set, raw counters are written here on every fetch.
+    recorder: RefCell<Option<Recorder>>,
 }
 
 impl Metrics {
-    pub fn new() -> Result<Self> {
+    pub fn new(memory_usage: MemoryUsage) -> SysResult<Self> {
+        let cpu = cpu::State::default();
+        let memory = memory::State::new(memory_usage);
+        let disk = disk::State::new()?;
+        let network = network::State::new()?;
+        let sensors = sensors::State::new();
+        let battery = battery::State::new();
+        let pressure = pressure::State::new();
+        let processes = processes::State::new();
+        let details = Details::of(&cpu, &memory, &disk, &network, &sensors, &processes);
+        Ok(Self::from_sources(
+            SystemClock,
+            cpu,
+            memory,
+            disk,
+            network,
+            sensors,
+            battery,
+            pressure,
+            processes,
+            details,
+        ))
+    }
+
+    /// Replay a recording through the same conversions as live metrics.
+    ///
+    /// Each fetch consumes one recorded sample of each metric, using the recorded time deltas.
+    pub fn replay(memory_usage: MemoryUsage, recording: &Recording) -> SysResult<Self> {
+        let cpu = cpu::State::default();
+        let memory = memory::State::new(memory_usage);
+        let disk = disk::State::new()?;
+        let network = network::State::new()?;
+        let sensors = sensors::State::new();
+        let battery = battery::State::new();
+        let pressure = pressure::State::new();
+        let processes = processes::State::new();
+        let details = Details::of(&cpu, &memory, &disk, &network, &sensors, &processes);
         Ok(Self {
+            recording: Some(recording.clone()),
+            ..Self::from_sources(
+                SystemClock,
+                Replay::new(cpu, recording),
+                Replay::new(memory, recording),
+                Replay::new(disk, recording),
+                Replay::new(network, recording),
+                Replay::new(sensors, recording),
+                Replay::new(battery, recording),
+                Replay::new(pressure, recording),
+                Replay::new(processes, recording),
+                details,
+            )
+        })
+    }
+
+    // One parameter per metric, so that tests can replace any of them.
+    #[allow(clippy::too_many_arguments)]
+    fn from_sources(
+        clock: impl Clock + 'static,
+        cpu: impl MetricSource<Sample: Into<Option<f64>>> + 'static,
+        memory: impl MetricSource<Sample: Into<Option<f64>>> + 'static,
+        disk: impl MetricSource<Sample: Into<Option<f64>>> + 'static,
+        network: impl MetricSource<Sample: Into<Option<f64>>> + 'static,
+        sensor: impl MetricSource<Sample: Into<Option<f64>>> + 'static,
+        battery: impl MetricSource<Sample = Option<Battery>> + 'static,
+        pressure: impl MetricSource<Sample = Option<Pressures>> + 'static,
+        processes: impl MetricSource<Sample = TopProcesses> + 'static,
+        details: Details,
+    ) -> Self {
+        Self {
+            clock: Box::new(clock),
             prev_time: Default::default(),
-            cpu: Default::default(),
-            cpu_percent: Default::default(),
-            memory: Default::default(),
-            memory_percent: Default::default(),
-            disk: disk::State::new()?,
-            disk_mbyte: Default::default(),
-            network: Default::default(),
-            network_mbit: Default::default(),
+            cpu: Series::new(cpu),
+            memory: Series::new(memory),
+            disk: Series::new(disk),
+            network: Series::new(network),
+            sensor: Series::new(sensor),
+            battery: Snapshot::new(battery),
+            pressure: Snapshot::new(pressure),
+            pressure_selection: Default::default(),
+            processes: Snapshot::new(processes),
+            processes_wanted: Default::default(),
+            process_sampling: Default::default(),
+            details,
+            counters: Default::default(),
+            recording: None,
             fetch_count: Default::default(),
-        })
+            recorder: Default::default(),
+        }
+    }
+
+    pub fn start_recording(&self, recorder: Recorder) {
+        *self.recorder.borrow_mut() = Some(recorder);
+    }
+
+    pub fn set_options(&self, options: Options) {
+        let Options {
+            cpu_frequency,
+            network_interfaces,
+            disk_instance,
+            counters,
+            sensor,
+            top_processes,
+            pressure,
+        } = options;
+        self.details.cpu_frequency.set_enabled(cpu_frequency);
+        *self.details.network_rules.borrow_mut() = network_interfaces;
+        *self.details.disk_instance.borrow_mut() = disk_instance;
+        *self.details.sensor_selection.borrow_mut() = sensor;
+        self.details.process_count.set(top_processes.count);
+        self.process_sampling.set(top_processes);
+        *self.pressure_selection.borrow_mut() = pressure;
+        *self.counters.borrow_mut() = counters
+            .into_iter()
+            .filter_map(|metric| self.open_counter(metric))
+            .collect();
+    }
+
+    /// Open a counter from the config file, or log why it can't be shown.
+    fn open_counter(&self, metric: CounterMetric) -> Option<Counter> {
+        let state = match counter::State::new(metric.clone()) {
+            Ok(state) => state,
+            Err(e) => {
+                log::error!("Failed to open counter `{}`: {e}", metric.path);
+                return None;
+            }
+        };
+        let series = match &self.recording {
+            Some(recording) => Series::new(Replay::new(state, recording)),
+            None => Series::new(state),
+        };
+        Some(Counter { metric, series })
     }
 
     #[inline(never)]
     pub fn fetch(&self) -> usize {
-        let time = Instant::now();
+        let time = self.clock.now();
         let prev_time = self.prev_time.replace(Some(time));
         let time_delta = prev_time.map(|prev_time| time - prev_time);
 
-        match self.cpu.fetch_percent() {
-            Ok(cpu) => {
-                log::trace!("Fetched CPU: {cpu:.3}");
-                self.cpu_percent.push(cpu);
-            }
-            Err(e) => log::error!("Failed to fetch CPU: {e}"),
+        let recorder = self.recorder.borrow();
+        for series in [
+            &self.cpu,
+            &self.memory,
+            &self.disk,
+            &self.network,
+            &self.sensor,
+        ] {
+            series.fetch(time_delta, recorder.as_ref());
         }
-
-        match self.memory.fetch_percent() {
-            Ok(memory) => {
-                log::trace!("Fetched memory: {memory:.3}");
-                self.memory_percent.push(memory);
-            }
-            Err(e) => log::error!("Failed to fetch memory: {e}"),
-        }
-
-        match self.disk.fetch_mbyte(time_delta) {
-            Ok(disk) => {
-                log::trace!("Fetched disk: {disk:.3}");
-                self.disk_mbyte.push(disk);
-            }
-            Err(e) => log::error!("Failed to fetch disk: {e}"),
-        }
-
-        match self.network.fetch_mbit(time_delta) {
-            Ok(network) => {
-                log::trace!("Fetched network: {network:.3}");
-                self.network_mbit.push(network);
-            }
-            Err(e) => log::error!("Failed to fetch network: {e}"),
+        self.battery.fetch(time_delta, recorder.as_ref());
+        self.pressure.fetch(time_delta, recorder.as_ref());
+        for counter in &*self.counters.borrow() {
+            counter.series.fetch(time_delta, recorder.as_ref());
         }
+        // Processes are fetched last, so that thresholds are checked against the latest samples.
+        self.details
+            .processes_enabled
+            .set(self.processes_wanted.get() || self.is_process_threshold_crossed());
+        self.processes.fetch(time_delta, recorder.as_ref());
 
         let new_fetch_count = self.fetch_count.get().wrapping_add(1);
         self.fetch_count.set(new_fetch_count);
         new_fetch_count
     }
 
+    /// Read processes on every fetch, not just while a threshold is crossed, e.g. while the tooltip listing them is open.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub fn set_processes_wanted(&self, wanted: bool) {
+        self.processes_wanted.set(wanted);
+    }
+
+    /// Whether any of the thresholds for reading processes is crossed.
+    fn is_process_threshold_crossed(&self) -> bool {
+        let ProcessSampling {
+            count: _,
+            cpu_percent,
+            memory_percent,
+            disk_mbyte,
+            network_mbit,
+        } = self.process_sampling.get();
+        let crossed = |threshold: Option<f64>, value: f64| threshold.is_some_and(|t| value >= t);
+        crossed(cpu_percent, self.avg_cpu_percent())
+            || crossed(memory_percent, self.avg_memory_percent())
+            || crossed(disk_mbyte, self.avg_disk_mbyte())
+            || crossed(network_mbit, self.avg_network_mbit())
+    }
+
     pub fn avg_cpu_percent(&self) -> f64 {
-        self.cpu_percent
-            .exponential_moving_average(EXPONENTIAL_DECAY_ALPHA)
+        self.cpu.average()
+    }
+
+    /// Average CPU usage of each logical processor, or empty if not available.
+    pub fn avg_cpu_core_percents(&self) -> Vec<f64> {
+        self.details.cpu_cores.averages()
+    }
+
+    /// Average effective processor frequency in MHz, or `None` if it isn't enabled or available.
+    pub fn avg_cpu_frequency_mhz(&self) -> Option<f64> {
+        self.details.cpu_frequency.average()
     }
 
     pub fn avg_memory_percent(&self) -> f64 {
-        self.memory_percent
+        self.memory.average()
+    }
+
+    /// Average swap usage, or commit charge as a percentage of the commit limit on Windows.
+    pub fn avg_swap_percent(&self) -> f64 {
+        self.details
+            .memory_swap
             .exponential_moving_average(EXPONENTIAL_DECAY_ALPHA)
     }
 
     pub fn avg_disk_mbyte(&self) -> f64 {
-        self.disk_mbyte
-            .exponential_moving_average(EXPONENTIAL_DECAY_ALPHA)
+        self.disk.average()
+    }
+
+    pub fn avg_disk_mbyte_read(&self) -> f64 {
+        self.details.disk_directions.average_incoming()
+    }
+
+    pub fn avg_disk_mbyte_written(&self) -> f64 {
+        self.details.disk_directions.average_outgoing()
     }
 
     pub fn avg_network_mbit(&self) -> f64 {
-        self.network_mbit
-            .exponential_moving_average(EXPONENTIAL_DECAY_ALPHA)
+        self.network.average()
+    }
+
+    pub fn avg_network_mbit_received(&self) -> f64 {
+        self.details.network_directions.average_incoming()
+    }
+
+    pub fn avg_network_mbit_sent(&self) -> f64 {
+        self.details.network_directions.average_outgoing()
+    }
+
+    /// Average reading of the selected sensor, or `None` if no sensor is selected or it wasn't found.
+    pub fn avg_sensor(&self) -> Option<(SensorKind, f64)> {
+        let kind = self.details.sensor_kind.get()?;
+        Some((kind, self.sensor.average()))
+    }
+
+    /// Latest battery status, or `None` if there's no battery.
+    ///
+    /// This isn't averaged, since the charge changes slowly, and the power state shouldn't lag behind plugging in.
+    pub fn battery(&self) -> Option<Battery> {
+        self.battery.latest()
+    }
+
+    /// Latest memory pressure (`some`), or `None` if it isn't available, e.g. on Windows.
+    pub fn memory_pressure(&self) -> Option<f64> {
+        Some(self.pressure.latest()?.memory.some)
+    }
+
+    /// Latest value of each pressure stall series from the config file, or empty if pressure isn't available.
+    pub fn selected_pressures(&self) -> Vec<(PressureSeries, f64)> {
+        let Some(pressures) = self.pressure.latest() else {
+            return Vec::new();
+        };
+        self.pressure_selection
+            .borrow()
+            .iter()
+            .map(|&series| (series, pressures.get(series)))
+            .collect()
+    }
+
+    /// Processes using the most CPU, memory, and I/O as of the latest fetch, or empty if processes weren't read.
+    ///
+    /// Like the battery, these aren't averaged, since the busiest processes change from one fetch to the next.
+    pub fn top_processes(&self) -> TopProcesses {
+        self.processes.latest()
+    }
+
+    /// Average of each counter from the config file which could be opened, along with how to display it.
+    pub fn avg_counters(&self) -> Vec<(CounterMetric, f64)> {
+        self.counters
+            .borrow()
+            .iter()
+            .map(|counter| (counter.metric.clone(), counter.series.average()))
+            .collect()
+    }
+}
+
+/// Describe each temperature and fan sensor, and its current reading.
+pub fn describe_sensors() -> SysResult<Vec<String>> {
+    sensors::State::new().describe_sensors()
+}
+
+/// Describe each network interface, and whether it counts toward network bandwidth under `rules`.
+pub fn describe_network_interfaces(rules: InterfaceRules) -> SysResult<Vec<String>> {
+    let network = network::State::new()?;
+    *network.rules().borrow_mut() = rules;
+    network.describe_interfaces()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::metrics::fake::{Constant, FakeError, Scripted};
+
+    fn metrics_from(
+        cpu: &Scripted,
+        memory: &Scripted,
+        disk: &Scripted,
+        network: &Scripted,
+    ) -> Metrics {
+        Metrics::from_sources(
+            SystemClock,
+            cpu.clone(),
+            memory.clone(),
+            disk.clone(),
+            network.clone(),
+            Scripted::zeros(),
+            Constant(None),
+            Constant(None),
+            Constant(TopProcesses::default()),
+            Default::default(),
+        )
+    }
+
+    #[test]
+    fn counter_deltas() {
+        const U32_MAX: u64 = u32::MAX as u64;
+
+        assert_eq!(counter_delta(100, 250, U32_MAX), 150);
+        assert_eq!(counter_delta(100, 100, U32_MAX), 0);
+        assert_eq!(counter_delta(0, U32_MAX, U32_MAX), U32_MAX);
+
+        // Wrapped
+        assert_eq!(counter_delta(U32_MAX - 9, 5, U32_MAX), 15);
+        assert_eq!(counter_delta(U32_MAX, 0, U32_MAX), 1);
+        assert_eq!(counter_delta(u64::MAX - 9, 5, u64::MAX), 15);
+
+        // Reset
+        assert_eq!(counter_delta(1_000_000, 5, U32_MAX), 0);
+        assert_eq!(counter_delta(1 << 40, 5, u64::MAX), 0);
+        assert_eq!(counter_delta(u64::MAX / 2, 0, u64::MAX), 0);
+    }
+
+    #[test]
+    fn first_fetch_has_no_time_delta() {
+        let source = Scripted::new([Ok(1.0), Ok(2.0), Ok(3.0)]);
+        let metrics = metrics_from(
+            &source,
+            &Scripted::zeros(),
+            &Scripted::zeros(),
+            &Scripted::zeros(),
+        );
+
+        metrics.fetch();
+        metrics.fetch();
+        metrics.fetch();
+
+        let time_deltas = source.time_deltas();
+        assert_eq!(time_deltas.len(), 3);
+        assert_eq!(time_deltas[0], None);
+        assert!(time_deltas[1].is_some());
+        assert!(time_deltas[2].is_some());
+    }
+
+    #[test]
+    fn missing_samples_are_not_averaged() {
+        let series = Series::new(Constant(None::<f64>));
+        series.samples.push(10.0);
+
+        series.fetch(Some(Duration::ZERO), None);
+        assert_eq!(series.average(), 10.0);
+    }
+
+    #[test]
+    fn averages_samples() {
+        let cpu = Scripted::new([Ok(10.0), Ok(20.0)]);
+        let memory = Scripted::new([Ok(50.0), Ok(50.0)]);
+        let disk = Scripted::new([Ok(0.0), Ok(100.0)]);
+        let network = Scripted::new([Ok(8.0), Ok(0.0)]);
+        let metrics = metrics_from(&cpu, &memory, &disk, &network);
+
+        assert_eq!(metrics.avg_cpu_percent(), 0.0);
+
+        metrics.fetch();
+        assert_eq!(metrics.avg_cpu_percent(), 10.0);
+        assert_eq!(metrics.avg_memory_percent(), 50.0);
+        assert_eq!(metrics.avg_disk_mbyte(), 0.0);
+        assert_eq!(metrics.avg_network_mbit(), 8.0);
+
+        metrics.fetch();
+        let expected = |prev: f64, latest: f64| latest + EXPONENTIAL_DECAY_ALPHA * (prev - latest);
+        assert_eq!(metrics.avg_cpu_percent(), expected(10.0, 20.0));
+        assert_eq!(metrics.avg_memory_percent(), 50.0);
+        assert_eq!(metrics.avg_disk_mbyte(), expected(0.0, 100.0));
+        assert_eq!(metrics.avg_network_mbit(), expected(8.0, 0.0));
+    }
+
+    #[test]
+    fn errors_are_not_recorded_as_samples() {
+        let cpu = Scripted::new([Ok(10.0), Err(FakeError), Ok(10.0)]);
+        let memory = Scripted::new([Err(FakeError), Ok(40.0), Ok(40.0)]);
+        let metrics = metrics_from(&cpu, &memory, &Scripted::zeros(), &Scripted::zeros());
+
+        metrics.fetch();
+        assert_eq!(metrics.avg_cpu_percent(), 10.0);
+        assert_eq!(metrics.avg_memory_percent(), 0.0);
+
+        metrics.fetch();
+        assert_eq!(metrics.avg_cpu_percent(), 10.0);
+        assert_eq!(metrics.avg_memory_percent(), 40.0);
+
+        metrics.fetch();
+        assert_eq!(metrics.avg_cpu_percent(), 10.0);
+        assert_eq!(metrics.avg_memory_percent(), 40.0);
+    }
+
+    #[test]
+    fn errors_still_advance_time() {
+        let source = Scripted::new([Err(FakeError), Ok(1.0)]);
+        let metrics = metrics_from(
+            &source,
+            &Scripted::zeros(),
+            &Scripted::zeros(),
+            &Scripted::zeros(),
+        );
+
+        metrics.fetch();
+        metrics.fetch();
+
+        // Only the successful fetch is converted, but it still sees the time since the failed one.
+        let time_deltas = source.time_deltas();
+        assert_eq!(time_deltas.len(), 1);
+        assert!(time_deltas[0].is_some());
+    }
+
+    #[test]
+    fn fetch_count() {
+        let metrics = metrics_from(
+            &Scripted::zeros(),
+            &Scripted::zeros(),
+            &Scripted::zeros(),
+            &Scripted::zeros(),
+        );
+
+        assert_eq!(metrics.fetch(), 1);
+        assert_eq!(metrics.fetch(), 2);
+        assert_eq!(metrics.fetch(), 3);
+    }
+
+    #[test]
+    fn counters_are_fetched_with_builtins() {
+        let metrics = metrics_from(
+            &Scripted::zeros(),
+            &Scripted::zeros(),
+            &Scripted::zeros(),
+            &Scripted::zeros(),
+        );
+        let metric = CounterMetric {
+            label: "GPU".to_owned(),
+            ..Default::default()
+        };
+        metrics.counters.borrow_mut().push(Counter {
+            metric: metric.clone(),
+            series: Series::new(Scripted::new([Ok(30.0), Ok(30.0)])),
+        });
+
+        metrics.fetch();
+        metrics.fetch();
+        assert_eq!(metrics.avg_counters(), [(metric, 30.0)]);
+    }
+
+    #[test]
+    fn counters_which_cant_be_opened_are_skipped() {
+        let metrics = metrics_from(
+            &Scripted::zeros(),
+            &Scripted::zeros(),
+            &Scripted::zeros(),
+            &Scripted::zeros(),
+        );
+        metrics.set_options(Options {
+            counters: vec![CounterMetric {
+                path: "not a path".to_owned(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+
+        metrics.fetch();
+        assert_eq!(metrics.avg_counters(), []);
+    }
+
+    #[test]
+    fn processes_are_read_while_wanted_or_over_a_threshold() {
+        let cpu = Scripted::new([Ok(10.0), Ok(10.0), Ok(95.0), Ok(95.0), Ok(95.0)]);
+        let metrics = metrics_from(
+            &cpu,
+            &Scripted::zeros(),
+            &Scripted::zeros(),
+            &Scripted::zeros(),
+        );
+        metrics.set_options(Options {
+            top_processes: ProcessSampling {
+                cpu_percent: Some(60.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        metrics.fetch();
+        assert!(!metrics.details.processes_enabled.get());
+
+        metrics.set_processes_wanted(true);
+        metrics.fetch();
+        assert!(metrics.details.processes_enabled.get());
+
+        metrics.set_processes_wanted(false);
+        // The average takes a few samples to catch up with a spike
+        metrics.fetch();
+        assert!(!metrics.details.processes_enabled.get());
+        metrics.fetch();
+        assert!(!metrics.details.processes_enabled.get());
+        metrics.fetch();
+        assert!(metrics.details.processes_enabled.get());
+    }
+
+    #[test]
+    fn pressures_are_shown_as_selected() {
+        let pressures = Pressures {
+            memory: pressure::Stall {
+                some: 4.5,
+                full: 1.5,
+            },
+            ..Default::default()
+        };
+        let metrics = Metrics::from_sources(
+            SystemClock,
+            Scripted::zeros(),
+            Scripted::zeros(),
+            Scripted::zeros(),
+            Scripted::zeros(),
+            Scripted::zeros(),
+            Constant(None),
+            Constant(Some(pressures)),
+            Constant(TopProcesses::default()),
+            Default::default(),
+        );
+        metrics.set_options(Options {
+            pressure: vec![PressureSeries::MemoryFull, PressureSeries::IoSome],
+            ..Default::default()
+        });
+        assert_eq!(metrics.memory_pressure(), None);
+        assert_eq!(metrics.selected_pressures(), []);
+
+        metrics.fetch();
+        assert_eq!(metrics.memory_pressure(), Some(4.5));
+        assert_eq!(
+            metrics.selected_pressures(),
+            [
+                (PressureSeries::MemoryFull, 1.5),
+                (PressureSeries::IoSome, 0.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn time_deltas_follow_clock() {
+        let clock = ManualClock::new();
+        let source = Scripted::zeros();
+        let metrics = Metrics::from_sources(
+            clock.clone(),
+            source.clone(),
+            Scripted::zeros(),
+            Scripted::zeros(),
+            Scripted::zeros(),
+            Scripted::zeros(),
+            Constant(None),
+            Constant(None),
+            Constant(TopProcesses::default()),
+            Default::default(),
+        );
+
+        metrics.fetch();
+        clock.advance(Duration::from_secs(1));
+        metrics.fetch();
+        // e.g. the fetch timer being stopped while the session is locked
+        clock.advance(Duration::from_secs(60 * 60));
+        metrics.fetch();
+        clock.advance(Duration::from_micros(100));
+        metrics.fetch();
+        metrics.fetch();
+
+        assert_eq!(
+            source.time_deltas(),
+            [
+                None,
+                Some(Duration::from_secs(1)),
+                Some(Duration::from_secs(60 * 60)),
+                Some(Duration::from_micros(100)),
+                Some(Duration::ZERO),
+            ]
+        );
     }
 }
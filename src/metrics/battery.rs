@@ -0,0 +1,89 @@
+use crate::metrics::MetricSource;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[cfg_attr(windows, path = "battery/windows.rs")]
+#[cfg_attr(target_os = "linux", path = "battery/linux.rs")]
+mod sys;
+
+/// Whether the battery is being charged or drained.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerState {
+    Charging,
+    /// Running on battery.
+    Discharging,
+    /// Plugged in, but not charging, e.g. because it's full, or held below a charge limit.
+    PluggedIn,
+    Unknown,
+}
+
+/// Status of the system battery, or of all of them combined.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Battery {
+    /// Remaining charge, as a percentage of full.
+    pub percent: f64,
+    pub state: PowerState,
+    /// Estimated time until empty when discharging, or until full when charging, if known.
+    pub seconds_remaining: Option<u64>,
+}
+
+impl Battery {
+    /// Whether it's fine to keep the machine awake, i.e. unless running on battery below `min_percent`.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub fn allows_keep_awake(&self, min_percent: f64) -> bool {
+        self.state != PowerState::Discharging || self.percent >= min_percent
+    }
+}
+
+pub struct State {
+    reader: sys::Reader,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self {
+            reader: sys::Reader::new(),
+        }
+    }
+}
+
+impl MetricSource for State {
+    type Raw = Option<Battery>;
+    type Sample = Option<Battery>;
+    type Error = sys::Error;
+
+    fn name(&self) -> &str {
+        "battery"
+    }
+
+    fn read(&self) -> Result<Option<Battery>, sys::Error> {
+        self.reader.read_battery()
+    }
+
+    fn convert(&self, battery: Option<Battery>, _time_delta: Option<Duration>) -> Option<Battery> {
+        battery
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keep_awake_on_battery() {
+        let battery = |percent, state| Battery {
+            percent,
+            state,
+            seconds_remaining: None,
+        };
+
+        assert!(battery(50.0, PowerState::Discharging).allows_keep_awake(20.0));
+        assert!(battery(20.0, PowerState::Discharging).allows_keep_awake(20.0));
+        assert!(!battery(19.0, PowerState::Discharging).allows_keep_awake(20.0));
+        // Plugged in, so it won't run out
+        assert!(battery(5.0, PowerState::Charging).allows_keep_awake(20.0));
+        assert!(battery(5.0, PowerState::PluggedIn).allows_keep_awake(20.0));
+        assert!(battery(5.0, PowerState::Unknown).allows_keep_awake(20.0));
+    }
+}
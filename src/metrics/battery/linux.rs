@@ -0,0 +1,442 @@
+use crate::metrics::battery::{Battery, PowerState};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub use std::io::Error;
+
+const SYS_CLASS_POWER_SUPPLY_PATH: &str = "/sys/class/power_supply";
+
+const SECONDS_PER_HOUR: f64 = 60.0 * 60.0;
+
+pub struct Reader {
+    sys_class_power_supply: PathBuf,
+}
+
+impl Reader {
+    pub fn new() -> Self {
+        Self::with_path(SYS_CLASS_POWER_SUPPLY_PATH)
+    }
+
+    pub fn with_path(sys_class_power_supply: impl Into<PathBuf>) -> Self {
+        Self {
+            sys_class_power_supply: sys_class_power_supply.into(),
+        }
+    }
+
+    /// Read the system batteries from `/sys/class/power_supply/BAT*`, combined as if they were one,
+    /// or `None` if there aren't any.
+    ///
+    /// Batteries in peripherals, e.g. wireless mice, are also listed there, but with a `scope` of `Device`, so they're skipped.
+    ///
+    /// https://www.kernel.org/doc/Documentation/ABI/testing/sysfs-class-power
+    pub fn read_battery(&self) -> io::Result<Option<Battery>> {
+        let mut supplies = match fs::read_dir(&self.sys_class_power_supply) {
+            Ok(entries) => entries
+                .map(|entry| Ok(entry?.path()))
+                .collect::<io::Result<Vec<_>>>()?,
+            // Desktops, containers, and VMs often don't have any.
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        supplies.sort();
+
+        let batteries = supplies
+            .iter()
+            .filter(|dir| is_system_battery(dir))
+            .map(|dir| read_supply(dir))
+            .collect::<Vec<_>>();
+        Ok(combine(&batteries))
+    }
+}
+
+/// Whether a power supply is a battery which powers the system.
+fn is_system_battery(dir: &Path) -> bool {
+    let is_battery = match read_attribute(dir, "type") {
+        Some(kind) => kind == "Battery",
+        None => dir
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with("BAT")),
+    };
+    let is_device = read_attribute(dir, "scope").is_some_and(|scope| scope == "Device");
+    is_battery && !is_device
+}
+
+/// Which attributes a driver reports a battery's level and rate in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Units {
+    /// `energy_*` in µWh, and `power_now` in µW.
+    Energy,
+    /// `charge_*` in µAh, and `current_now` in µA.
+    Charge,
+}
+
+/// Attributes of a single battery.
+///
+/// `now`, `full`, and `rate` are all in `units`, so `now / rate` is in hours.
+#[derive(Debug, Default, PartialEq)]
+struct Supply {
+    status: String,
+    capacity: Option<f64>,
+    units: Option<Units>,
+    now: Option<u64>,
+    full: Option<u64>,
+    rate: Option<u64>,
+}
+
+impl Supply {
+    /// Charge as a percentage, preferring the precise level over the rounded `capacity`.
+    fn percent(&self) -> Option<f64> {
+        match (self.now, self.full) {
+            (Some(now), Some(full)) if full > 0 => Some(now as f64 * 100.0 / full as f64),
+            _ => self.capacity,
+        }
+    }
+}
+
+fn read_supply(dir: &Path) -> Supply {
+    let number = |name| {
+        let value = read_attribute(dir, name)?;
+        match value.parse::<u64>() {
+            Ok(value) => Some(value),
+            Err(e) => {
+                log::debug!("Invalid `{name}` `{value}` in `{}`: {e}", dir.display());
+                None
+            }
+        }
+    };
+    let has = |name: &str| dir.join(name).exists();
+
+    // Pick one family of attributes, since e.g. µWh divided by µA isn't a time.
+    let units = if has("energy_now") || has("energy_full") {
+        Some(Units::Energy)
+    } else if has("charge_now") || has("charge_full") {
+        Some(Units::Charge)
+    } else {
+        None
+    };
+    let [now, full, rate] = match units {
+        Some(Units::Energy) => ["energy_now", "energy_full", "power_now"].map(number),
+        Some(Units::Charge) => ["charge_now", "charge_full", "current_now"].map(number),
+        None => [None; 3],
+    };
+
+    Supply {
+        status: read_attribute(dir, "status").unwrap_or_default(),
+        capacity: number("capacity").map(|capacity| capacity as f64),
+        units,
+        now,
+        full,
+        rate,
+    }
+}
+
+/// Read a sysfs attribute, or `None` if the driver doesn't provide it.
+///
+/// Some drivers list attributes which fail to read, e.g. while the battery is being removed, so those are also `None`.
+fn read_attribute(dir: &Path, name: &str) -> Option<String> {
+    match fs::read_to_string(dir.join(name)) {
+        Ok(value) => Some(value.trim().to_owned()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => {
+            log::debug!("Failed to read `{name}` in `{}`: {e}", dir.display());
+            None
+        }
+    }
+}
+
+/// Combine batteries as if they were one, e.g. the internal and external batteries of some ThinkPads.
+fn combine(supplies: &[Supply]) -> Option<Battery> {
+    if supplies.is_empty() {
+        return None;
+    }
+
+    let statuses = supplies.iter().map(|supply| supply.status.as_str());
+    let state = if statuses.clone().any(|status| status == "Discharging") {
+        PowerState::Discharging
+    } else if statuses.clone().any(|status| status == "Charging") {
+        PowerState::Charging
+    } else if statuses
+        .clone()
+        .all(|status| matches!(status, "Full" | "Not charging"))
+    {
+        PowerState::PluggedIn
+    } else {
+        PowerState::Unknown
+    };
+
+    // Energy and charge can't be added together, so batteries using different units are only averaged, without a
+    // time estimate.
+    let same_units = supplies
+        .iter()
+        .all(|supply| supply.units == supplies[0].units);
+    let sum = |field: fn(&Supply) -> Option<u64>| {
+        if same_units {
+            supplies.iter().filter_map(field).sum::<u64>()
+        } else {
+            0
+        }
+    };
+    let now = sum(|supply| supply.now);
+    let full = sum(|supply| supply.full);
+    let rate = sum(|supply| supply.rate);
+
+    // Prefer the precise charge, since `capacity` is rounded, but not every driver reports it.
+    let percent = if full > 0 {
+        now as f64 * 100.0 / full as f64
+    } else {
+        let percents = supplies
+            .iter()
+            .filter_map(Supply::percent)
+            .collect::<Vec<_>>();
+        if percents.is_empty() {
+            return None;
+        }
+        percents.iter().sum::<f64>() / percents.len() as f64
+    };
+
+    let hours = match state {
+        PowerState::Discharging => Some(now as f64 / rate as f64),
+        PowerState::Charging => Some(full.saturating_sub(now) as f64 / rate as f64),
+        PowerState::PluggedIn | PowerState::Unknown => None,
+    };
+
+    Some(Battery {
+        percent: percent.clamp(0.0, 100.0),
+        state,
+        // Drivers report a rate of zero while it's settling, e.g. just after unplugging.
+        seconds_remaining: hours
+            .filter(|hours| hours.is_finite() && full > 0)
+            .map(|hours| (hours * SECONDS_PER_HOUR).round() as u64),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::Fixture;
+
+    fn battery(fixture: &Fixture, name: &str, attributes: &[(&str, &str)]) {
+        for (attribute, value) in attributes {
+            fixture.file(format!("{name}/{attribute}"), &format!("{value}\n"));
+        }
+    }
+
+    #[test]
+    fn discharging() {
+        let fixture = Fixture::new();
+        battery(
+            &fixture,
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("status", "Discharging"),
+                ("capacity", "50"),
+                ("energy_now", "25000000"),
+                ("energy_full", "50000000"),
+                ("power_now", "10000000"),
+            ],
+        );
+        battery(&fixture, "AC", &[("type", "Mains"), ("online", "0")]);
+
+        assert_eq!(
+            Reader::with_path(fixture.path("")).read_battery().unwrap(),
+            Some(Battery {
+                percent: 50.0,
+                state: PowerState::Discharging,
+                seconds_remaining: Some(2 * 60 * 60 + 30 * 60),
+            })
+        );
+    }
+
+    #[test]
+    fn charging_with_charge_attributes() {
+        let fixture = Fixture::new();
+        battery(
+            &fixture,
+            "BAT1",
+            &[
+                ("type", "Battery"),
+                ("status", "Charging"),
+                ("charge_now", "3000000"),
+                ("charge_full", "4000000"),
+                ("current_now", "2000000"),
+            ],
+        );
+
+        assert_eq!(
+            Reader::with_path(fixture.path("")).read_battery().unwrap(),
+            Some(Battery {
+                percent: 75.0,
+                state: PowerState::Charging,
+                seconds_remaining: Some(30 * 60),
+            })
+        );
+    }
+
+    #[test]
+    fn multiple_batteries_are_combined() {
+        let fixture = Fixture::new();
+        // Internal battery is drained first
+        battery(
+            &fixture,
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("status", "Unknown"),
+                ("energy_now", "20000000"),
+                ("energy_full", "20000000"),
+                ("power_now", "0"),
+            ],
+        );
+        battery(
+            &fixture,
+            "BAT1",
+            &[
+                ("type", "Battery"),
+                ("status", "Discharging"),
+                ("energy_now", "10000000"),
+                ("energy_full", "60000000"),
+                ("power_now", "15000000"),
+            ],
+        );
+        // A wireless mouse doesn't count
+        battery(
+            &fixture,
+            "hidpp_battery_0",
+            &[
+                ("type", "Battery"),
+                ("scope", "Device"),
+                ("status", "Discharging"),
+                ("capacity", "5"),
+            ],
+        );
+
+        assert_eq!(
+            Reader::with_path(fixture.path("")).read_battery().unwrap(),
+            Some(Battery {
+                percent: 37.5,
+                state: PowerState::Discharging,
+                seconds_remaining: Some(2 * 60 * 60),
+            })
+        );
+    }
+
+    #[test]
+    fn rate_in_other_units() {
+        let fixture = Fixture::new();
+        // Energy, but the rate is a current, so there's no time estimate.
+        battery(
+            &fixture,
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("status", "Discharging"),
+                ("energy_now", "25000000"),
+                ("energy_full", "50000000"),
+                ("current_now", "2000000"),
+            ],
+        );
+
+        assert_eq!(
+            Reader::with_path(fixture.path("")).read_battery().unwrap(),
+            Some(Battery {
+                percent: 50.0,
+                state: PowerState::Discharging,
+                seconds_remaining: None,
+            })
+        );
+    }
+
+    #[test]
+    fn batteries_in_different_units_are_averaged() {
+        let fixture = Fixture::new();
+        battery(
+            &fixture,
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("status", "Discharging"),
+                ("energy_now", "20000000"),
+                ("energy_full", "80000000"),
+                ("power_now", "10000000"),
+            ],
+        );
+        battery(
+            &fixture,
+            "BAT1",
+            &[
+                ("type", "Battery"),
+                ("status", "Discharging"),
+                ("charge_now", "3000000"),
+                ("charge_full", "4000000"),
+                ("current_now", "2000000"),
+            ],
+        );
+
+        assert_eq!(
+            Reader::with_path(fixture.path("")).read_battery().unwrap(),
+            Some(Battery {
+                percent: 50.0,
+                state: PowerState::Discharging,
+                seconds_remaining: None,
+            })
+        );
+    }
+
+    #[test]
+    fn full_with_only_capacity() {
+        let fixture = Fixture::new();
+        // No `type`, as on some very old kernels
+        battery(&fixture, "BAT0", &[("status", "Full"), ("capacity", "100")]);
+
+        assert_eq!(
+            Reader::with_path(fixture.path("")).read_battery().unwrap(),
+            Some(Battery {
+                percent: 100.0,
+                state: PowerState::PluggedIn,
+                seconds_remaining: None,
+            })
+        );
+    }
+
+    #[test]
+    fn rate_settling_after_unplugging() {
+        let fixture = Fixture::new();
+        battery(
+            &fixture,
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("status", "Discharging"),
+                ("energy_now", "25000000"),
+                ("energy_full", "50000000"),
+                ("power_now", "0"),
+            ],
+        );
+
+        let battery = Reader::with_path(fixture.path("")).read_battery().unwrap();
+        assert_eq!(battery.unwrap().seconds_remaining, None);
+    }
+
+    #[test]
+    fn no_batteries() {
+        let fixture = Fixture::new();
+        battery(&fixture, "AC", &[("type", "Mains"), ("online", "1")]);
+        assert_eq!(
+            Reader::with_path(fixture.path("")).read_battery().unwrap(),
+            None
+        );
+        assert_eq!(
+            Reader::with_path(fixture.path("missing"))
+                .read_battery()
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn read_live() {
+        Reader::new().read_battery().unwrap();
+    }
+}
@@ -0,0 +1,56 @@
+use crate::metrics::battery::{Battery, PowerState};
+use windows::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+use windows::core::Result;
+
+pub use windows::core::Error;
+
+// https://learn.microsoft.com/en-us/windows/win32/api/winbase/ns-winbase-system_power_status
+const AC_LINE_OFFLINE: u8 = 0;
+const AC_LINE_ONLINE: u8 = 1;
+const BATTERY_FLAG_CHARGING: u8 = 8;
+const BATTERY_FLAG_NO_BATTERY: u8 = 128;
+const BATTERY_FLAG_UNKNOWN: u8 = 255;
+const BATTERY_PERCENT_UNKNOWN: u8 = 255;
+const BATTERY_LIFE_UNKNOWN: u32 = u32::MAX;
+
+pub struct Reader;
+
+impl Reader {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Read the system battery status, or `None` if there's no battery.
+    ///
+    /// Windows combines multiple batteries itself.
+    pub fn read_battery(&self) -> Result<Option<Battery>> {
+        let mut status = SYSTEM_POWER_STATUS::default();
+        // SAFETY: `status` is valid for writes
+        unsafe { GetSystemPowerStatus(&mut status)? };
+
+        if status.BatteryFlag == BATTERY_FLAG_UNKNOWN
+            || status.BatteryFlag & BATTERY_FLAG_NO_BATTERY != 0
+            || status.BatteryLifePercent == BATTERY_PERCENT_UNKNOWN
+        {
+            return Ok(None);
+        }
+
+        let state = match status.ACLineStatus {
+            AC_LINE_OFFLINE => PowerState::Discharging,
+            AC_LINE_ONLINE if status.BatteryFlag & BATTERY_FLAG_CHARGING != 0 => {
+                PowerState::Charging
+            }
+            AC_LINE_ONLINE => PowerState::PluggedIn,
+            _ => PowerState::Unknown,
+        };
+
+        Ok(Some(Battery {
+            percent: f64::from(status.BatteryLifePercent),
+            state,
+            // Only estimated while discharging; there's no estimate of the time until full.
+            seconds_remaining: (state == PowerState::Discharging
+                && status.BatteryLifeTime != BATTERY_LIFE_UNKNOWN)
+                .then_some(u64::from(status.BatteryLifeTime)),
+        }))
+    }
+}
@@ -0,0 +1,382 @@
+use crate::metrics::{MetricSource, counter_delta};
+use crate::opt::{Aggregation, CounterKind, CounterMetric};
+use crate::utils::glob_matches;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::time::Duration;
+
+#[cfg_attr(windows, path = "counter/windows.rs")]
+#[cfg_attr(target_os = "linux", path = "counter/linux.rs")]
+mod sys;
+
+/// Raw values of each instance of a counter, by instance name.
+///
+/// Counters from countersets without instances have a single value, with an empty name.
+pub type CounterValues = BTreeMap<String, RawValue>;
+
+/// Raw value of one instance of a counter.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RawValue {
+    pub value: u64,
+    /// Value of the `<counter> Base` companion counter, which is only read for ratios.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base: Option<u64>,
+}
+
+/// Parts of a counter path, e.g. `\GPU Engine(*engtype_3D)\Utilization Percentage`.
+#[derive(Debug, PartialEq)]
+pub struct CounterPath {
+    pub counterset: String,
+    /// Glob matching the instances to combine. All instances if the path doesn't have one.
+    pub instance: String,
+    pub counter: String,
+}
+
+impl CounterPath {
+    /// Parse a path in the same format as perfmon and typeperf, i.e. `\Counterset(Instance)\Counter`.
+    pub fn parse(path: &str) -> Result<Self, String> {
+        let invalid =
+            || format!("invalid counter path `{path}`, expected `\\Counterset(Instance)\\Counter`");
+
+        let (counterset, counter) = path
+            .strip_prefix('\\')
+            .and_then(|path| path.split_once('\\'))
+            .ok_or_else(invalid)?;
+
+        // Instance names can contain parentheses, e.g. `pid_1234 (chrome.exe)`, so only the outermost ones count.
+        let (counterset, instance) = match counterset.split_once('(') {
+            Some((counterset, instance)) => {
+                (counterset, instance.strip_suffix(')').ok_or_else(invalid)?)
+            }
+            None => (counterset, "*"),
+        };
+
+        if counterset.is_empty() || counter.is_empty() || counter.contains('\\') {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            counterset: counterset.to_owned(),
+            instance: instance.to_owned(),
+            counter: counter.to_owned(),
+        })
+    }
+}
+
+pub struct State {
+    reader: sys::Reader,
+    metric: CounterMetric,
+    combiner: Combiner,
+}
+
+impl State {
+    pub fn new(metric: CounterMetric) -> Result<Self, Box<dyn Error>> {
+        let path = CounterPath::parse(&metric.path)?;
+        Ok(Self {
+            reader: sys::Reader::new(&path, metric.kind == CounterKind::Ratio)?,
+            metric,
+            combiner: Combiner::new(path.instance),
+        })
+    }
+}
+
+/// Converts raw values into samples, keeping the previous values around for rates.
+struct Combiner {
+    instance: String,
+    prev_values: RefCell<Option<CounterValues>>,
+}
+
+impl Combiner {
+    fn new(instance: String) -> Self {
+        Self {
+            instance,
+            prev_values: Default::default(),
+        }
+    }
+
+    fn convert(
+        &self,
+        metric: &CounterMetric,
+        values: CounterValues,
+        time_delta: Option<Duration>,
+    ) -> f64 {
+        let prev_values = self.prev_values.replace(None);
+        let sample = combine(
+            metric,
+            &self.instance,
+            &values,
+            prev_values.as_ref(),
+            time_delta,
+        );
+        *self.prev_values.borrow_mut() = Some(values);
+        sample
+    }
+}
+
+/// Combine the values of each instance matching `instance` into a sample, as configured by `metric`.
+///
+/// Rates and ratios need a previous value, so they only include instances which were also present in `prev_values`.
+fn combine(
+    metric: &CounterMetric,
+    instance: &str,
+    values: &CounterValues,
+    prev_values: Option<&CounterValues>,
+    time_delta: Option<Duration>,
+) -> f64 {
+    let secs = time_delta
+        .filter(|time_delta| !time_delta.is_zero())
+        .map(|time_delta| time_delta.as_secs_f64());
+
+    let samples = values
+        .iter()
+        .filter(|(name, _)| glob_matches(instance, name))
+        .filter_map(|(name, &RawValue { value, base })| match metric.kind {
+            CounterKind::Value => Some(value as f64),
+            CounterKind::Rate => {
+                let prev = prev_values?.get(name)?;
+                Some(counter_delta(prev.value, value, u64::MAX) as f64 / secs?)
+            }
+            CounterKind::Ratio => {
+                let prev = prev_values?.get(name)?;
+                let base_delta = counter_delta(prev.base?, base?, u64::MAX);
+                // The base doesn't advance if e.g. no time passed, so there's nothing to divide.
+                (base_delta > 0)
+                    .then(|| counter_delta(prev.value, value, u64::MAX) as f64 / base_delta as f64)
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let combined = match (metric.aggregation, samples.len()) {
+        (_, 0) => 0.0,
+        (Aggregation::Sum, _) => samples.iter().sum(),
+        (Aggregation::Average, len) => samples.iter().sum::<f64>() / len as f64,
+        (Aggregation::Min, _) => samples.iter().copied().fold(f64::INFINITY, f64::min),
+        (Aggregation::Max, _) => samples.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+    };
+
+    combined * metric.scale
+}
+
+impl MetricSource for State {
+    type Raw = CounterValues;
+    type Sample = f64;
+    type Error = sys::Error;
+
+    fn name(&self) -> &str {
+        &self.metric.path
+    }
+
+    fn unit(&self) -> &str {
+        &self.metric.unit
+    }
+
+    fn read(&self) -> Result<CounterValues, sys::Error> {
+        self.reader.read_values()
+    }
+
+    fn convert(&self, values: CounterValues, time_delta: Option<Duration>) -> f64 {
+        self.combiner.convert(&self.metric, values, time_delta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(values: &[(&str, u64)]) -> CounterValues {
+        values
+            .iter()
+            .map(|&(name, value)| (name.to_owned(), RawValue { value, base: None }))
+            .collect()
+    }
+
+    fn ratios(values: &[(&str, u64, u64)]) -> CounterValues {
+        values
+            .iter()
+            .map(|&(name, value, base)| {
+                (
+                    name.to_owned(),
+                    RawValue {
+                        value,
+                        base: Some(base),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(
+            CounterPath::parse(r"\GPU Engine(*engtype_3D)\Utilization Percentage"),
+            Ok(CounterPath {
+                counterset: "GPU Engine".to_owned(),
+                instance: "*engtype_3D".to_owned(),
+                counter: "Utilization Percentage".to_owned(),
+            })
+        );
+        assert_eq!(
+            CounterPath::parse(r"\Process(pid_1234 (chrome.exe))\IO Read Bytes/sec"),
+            Ok(CounterPath {
+                counterset: "Process".to_owned(),
+                instance: "pid_1234 (chrome.exe)".to_owned(),
+                counter: "IO Read Bytes/sec".to_owned(),
+            })
+        );
+        assert_eq!(
+            CounterPath::parse(r"\Memory\Pages/sec"),
+            Ok(CounterPath {
+                counterset: "Memory".to_owned(),
+                instance: "*".to_owned(),
+                counter: "Pages/sec".to_owned(),
+            })
+        );
+
+        for invalid in [
+            "",
+            r"\Memory",
+            r"Memory\Pages/sec",
+            r"\Memory\",
+            r"\(_Total)\Pages/sec",
+            r"\Processor(_Total\% Processor Time",
+            r"\Memory\Pages\sec",
+        ] {
+            assert!(CounterPath::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn config() {
+        let metric: CounterMetric = serde_json::from_str(
+            r#"{
+                "path": "\\GPU Engine(*engtype_3D)\\Utilization Percentage",
+                "label": "GPU",
+                "unit": "%",
+                "kind": "rate",
+                "aggregation": "max",
+                "scale": 0.00001
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            metric,
+            CounterMetric {
+                path: r"\GPU Engine(*engtype_3D)\Utilization Percentage".to_owned(),
+                label: "GPU".to_owned(),
+                unit: "%".to_owned(),
+                kind: CounterKind::Rate,
+                aggregation: Aggregation::Max,
+                scale: 0.00001,
+                decimals: 0,
+            }
+        );
+
+        let metric: CounterMetric =
+            serde_json::from_str(r#"{ "path": "\\Memory\\Pages/sec" }"#).unwrap();
+        assert_eq!(metric.kind, CounterKind::Value);
+        assert_eq!(metric.aggregation, Aggregation::Sum);
+        assert_eq!(metric.scale, 1.0);
+    }
+
+    #[test]
+    fn values_are_aggregated_then_scaled() {
+        let values = values(&[("_Total", 100), ("0", 10), ("1", 30), ("2", 20)]);
+        let combine = |aggregation, instance| {
+            let metric = CounterMetric {
+                aggregation,
+                scale: 0.5,
+                ..Default::default()
+            };
+            combine(&metric, instance, &values, None, None)
+        };
+
+        assert_eq!(combine(Aggregation::Sum, "?"), 30.0);
+        assert_eq!(combine(Aggregation::Average, "?"), 10.0);
+        assert_eq!(combine(Aggregation::Min, "?"), 5.0);
+        assert_eq!(combine(Aggregation::Max, "?"), 15.0);
+        assert_eq!(combine(Aggregation::Sum, "_total"), 50.0);
+        // `*` includes the total, like in perfmon.
+        assert_eq!(combine(Aggregation::Sum, "*"), 80.0);
+        assert_eq!(combine(Aggregation::Max, "missing"), 0.0);
+    }
+
+    #[test]
+    fn rates() {
+        let metric = CounterMetric {
+            kind: CounterKind::Rate,
+            // 100ns timer to percent
+            scale: 0.00001,
+            ..Default::default()
+        };
+        let prev = values(&[("pid_1", 1_000_000), ("pid_2", 5_000_000)]);
+        // Half of one engine over two seconds, and an engine which wasn't present before.
+        let current = values(&[("pid_1", 11_000_000), ("pid_2", 5_000_000), ("pid_3", 1)]);
+        let two_seconds = Some(Duration::from_secs(2));
+
+        let percent = combine(&metric, "*", &current, Some(&prev), two_seconds);
+        assert!((percent - 50.0).abs() < 1e-9, "{percent}");
+
+        // First fetch, or no time passed
+        assert_eq!(combine(&metric, "*", &current, None, two_seconds), 0.0);
+        assert_eq!(
+            combine(&metric, "*", &current, Some(&prev), Some(Duration::ZERO)),
+            0.0
+        );
+
+        // Instance re-created with a lower count
+        assert_eq!(
+            combine(&metric, "pid_2", &prev, Some(&current), two_seconds),
+            0.0
+        );
+    }
+
+    #[test]
+    fn ratios_divide_by_the_base() {
+        let metric = CounterMetric {
+            kind: CounterKind::Ratio,
+            aggregation: Aggregation::Average,
+            scale: 100.0,
+            ..Default::default()
+        };
+        let prev = ratios(&[("0,0", 1_000, 10_000), ("0,1", 5_000, 10_000)]);
+        // The first processor did 80% of its base, the second 120%, e.g. while boosting.
+        let current = ratios(&[("0,0", 9_000, 20_000), ("0,1", 17_000, 20_000)]);
+        let second = Some(Duration::from_secs(1));
+
+        let percent = combine(&metric, "*", &current, Some(&prev), second);
+        assert!((percent - 100.0).abs() < 1e-9, "{percent}");
+        let percent = combine(&metric, "0,0", &current, Some(&prev), second);
+        assert!((percent - 80.0).abs() < 1e-9, "{percent}");
+
+        // Doesn't depend on the time passed, only on the base.
+        let percent = combine(&metric, "0,0", &current, Some(&prev), None);
+        assert!((percent - 80.0).abs() < 1e-9, "{percent}");
+
+        // First fetch, or the base didn't advance
+        assert_eq!(combine(&metric, "*", &current, None, second), 0.0);
+        assert_eq!(combine(&metric, "*", &current, Some(&current), second), 0.0);
+
+        // No base was read
+        let without_base = values(&[("0,0", 9_000)]);
+        assert_eq!(
+            combine(&metric, "*", &without_base, Some(&prev), second),
+            0.0
+        );
+    }
+
+    #[test]
+    fn old_samples_are_kept_for_rates() {
+        let metric = CounterMetric {
+            kind: CounterKind::Rate,
+            ..Default::default()
+        };
+        let combiner = Combiner::new("*".to_owned());
+        let second = Some(Duration::from_secs(1));
+
+        assert_eq!(combiner.convert(&metric, values(&[("", 10)]), None), 0.0);
+        assert_eq!(combiner.convert(&metric, values(&[("", 15)]), second), 5.0);
+        assert_eq!(combiner.convert(&metric, values(&[("", 35)]), second), 20.0);
+    }
+}
@@ -0,0 +1,56 @@
+use crate::metrics::counter::{CounterPath, CounterValues, RawValue};
+use crate::perf::{MultiCounter, PerfQueries, find_counters};
+use windows::core::Result;
+
+pub use windows::core::Error;
+
+pub enum Reader {
+    Value(PerfQueries<MultiCounter, 1, u64>),
+    /// The counter and its `<counter> Base` companion, which it's a fraction of.
+    Ratio(PerfQueries<MultiCounter, 2, u64>),
+}
+
+impl Reader {
+    pub fn new(path: &CounterPath, with_base: bool) -> Result<Self> {
+        // Instances are matched afterwards, so globs behave the same as in the rest of the config.
+        if with_base {
+            let base = format!("{} Base", path.counter);
+            let (counterset, counter_ids) =
+                find_counters(&path.counterset, [path.counter.as_str(), base.as_str()])?;
+            Ok(Self::Ratio(PerfQueries::new_all_instances(
+                counterset,
+                &counter_ids,
+            )?))
+        } else {
+            let (counterset, counter_ids) =
+                find_counters(&path.counterset, [path.counter.as_str()])?;
+            Ok(Self::Value(PerfQueries::new_all_instances(
+                counterset,
+                &counter_ids,
+            )?))
+        }
+    }
+
+    pub fn read_values(&self) -> Result<CounterValues> {
+        Ok(match self {
+            Self::Value(queries) => queries
+                .query_data()?
+                .into_iter()
+                .map(|(name, [value])| (name, RawValue { value, base: None }))
+                .collect(),
+            Self::Ratio(queries) => queries
+                .query_data()?
+                .into_iter()
+                .map(|(name, [value, base])| {
+                    (
+                        name,
+                        RawValue {
+                            value,
+                            base: Some(base),
+                        },
+                    )
+                })
+                .collect(),
+        })
+    }
+}
@@ -1,52 +1,370 @@
-use std::cell::Cell;
-use windows::Win32::Foundation::FILETIME;
-use windows::Win32::System::Threading::GetSystemTimes;
-use windows::core::Result;
+use crate::constants::{EXPONENTIAL_DECAY_ALPHA, SAMPLE_COUNT};
+use crate::metrics::MetricSource;
+use crate::stats::CircularBuffer;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Duration;
+
+#[cfg_attr(windows, path = "cpu/windows.rs")]
+#[cfg_attr(target_os = "linux", path = "cpu/linux.rs")]
+mod sys;
+
+/// Cumulative CPU times since boot, in platform-specific units.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CpuTimes {
+    /// Time spent idle.
+    idle: u64,
+    /// Time spent doing anything, including idling.
+    total: u64,
+}
+
+/// Cumulative CPU times for the whole system and for each logical processor.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SystemCpuTimes {
+    #[serde(flatten)]
+    total: CpuTimes,
+    /// Times for each logical processor, if available.
+    #[serde(default)]
+    cores: Vec<CpuTimes>,
+    /// Processor speed, if it's shown and available.
+    #[serde(default)]
+    frequency: Option<Frequency>,
+}
+
+/// How fast the processors are running, in a platform-specific form.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Frequency {
+    /// Average current frequency of the logical processors, in MHz.
+    Current { mhz: f64 },
+    /// Cumulative counters from `% Processor Performance`, whose rate is the percentage of `nominal_mhz` that
+    /// the processors ran at, which can be more than 100% with turbo boost.
+    Performance {
+        performance: u64,
+        time: u64,
+        nominal_mhz: u64,
+    },
+}
 
 #[derive(Default)]
 pub struct State {
-    prev_times: Cell<Option<(u64, u64, u64)>>,
+    prev_times: Cell<Option<CpuTimes>>,
+    cores: Rc<Cores>,
+    frequency: Rc<Frequencies>,
+    frequency_reader: sys::FrequencyReader,
 }
 
 impl State {
-    pub fn fetch_percent(&self) -> Result<f64> {
-        let mut idle = FILETIME::default();
-        let mut kernel_plus_idle = FILETIME::default();
-        let mut user = FILETIME::default();
-        // SAFETY: all pointers are to valid `FILETIME`s
-        unsafe {
-            GetSystemTimes(
-                Some(&mut idle),
-                Some(&mut kernel_plus_idle),
-                Some(&mut user),
-            )?
-        };
+    /// Handle to the per-core usage, which is updated whenever this source is fetched.
+    pub fn cores(&self) -> Rc<Cores> {
+        Rc::clone(&self.cores)
+    }
 
-        let to_100ns_intervals = |filetime: FILETIME| {
-            (u64::from(filetime.dwHighDateTime) << 32) | u64::from(filetime.dwLowDateTime)
-        };
+    /// Handle to the processor frequency, which is updated whenever this source is fetched, if enabled.
+    pub fn frequency(&self) -> Rc<Frequencies> {
+        Rc::clone(&self.frequency)
+    }
 
-        let idle = to_100ns_intervals(idle);
-        let kernel_plus_idle = to_100ns_intervals(kernel_plus_idle);
-        let user = to_100ns_intervals(user);
+    fn update(&self, times: SystemCpuTimes) -> f64 {
+        self.cores.update(times.cores);
+        self.frequency.update(times.frequency);
 
         // On first sample, just store the current times and return zero.
-        let percent = match self.prev_times.get() {
-            Some((prev_idle, prev_kernel_plus_idle, prev_user)) => {
-                let idle_delta = idle.wrapping_sub(prev_idle);
-                let kernel_plus_idle_delta = kernel_plus_idle.wrapping_sub(prev_kernel_plus_idle);
-                let user_delta = user.wrapping_sub(prev_user);
+        match self.prev_times.replace(Some(times.total)) {
+            Some(prev_times) => busy_percent(prev_times, times.total),
+            None => 0.0,
+        }
+    }
+}
+
+fn busy_percent(prev_times: CpuTimes, times: CpuTimes) -> f64 {
+    let idle_delta = times.idle.wrapping_sub(prev_times.idle);
+    let time_delta = times.total.wrapping_sub(prev_times.total);
+
+    if time_delta == 0 {
+        // Both samples were taken within the same clock tick.
+        return 0.0;
+    }
 
-                let time_delta = kernel_plus_idle_delta + user_delta;
-                let active_delta = time_delta - idle_delta;
+    let active_delta = time_delta.saturating_sub(idle_delta);
+
+    active_delta as f64 * 100.0 / time_delta as f64
+}
+
+/// Samples of CPU usage for each logical processor, as a percentage of that processor's time.
+#[derive(Default)]
+pub struct Cores {
+    prev_times: RefCell<Vec<CpuTimes>>,
+    samples: RefCell<Vec<CircularBuffer<f64, SAMPLE_COUNT>>>,
+}
 
-                (active_delta * 100) as f64 / (time_delta as f64)
+impl Cores {
+    fn update(&self, times: Vec<CpuTimes>) {
+        let mut prev_times = self.prev_times.borrow_mut();
+        let mut samples = self.samples.borrow_mut();
+
+        if times.len() != prev_times.len() {
+            // First sample, or processors were added or removed, so the indices may no longer line up.
+            // Start over, treating this like the first sample.
+            samples.clear();
+            samples.resize_with(times.len(), Default::default);
+            for samples in samples.iter() {
+                samples.push(0.0);
             }
-            None => 0.0,
+        } else {
+            for ((prev, &times), samples) in prev_times.iter().zip(&times).zip(samples.iter()) {
+                samples.push(busy_percent(*prev, times));
+            }
+        }
+
+        *prev_times = times;
+    }
+
+    /// Average usage of each logical processor, or empty if per-core times aren't available.
+    pub fn averages(&self) -> Vec<f64> {
+        self.samples
+            .borrow()
+            .iter()
+            .map(|samples| samples.exponential_moving_average(EXPONENTIAL_DECAY_ALPHA))
+            .collect()
+    }
+}
+
+/// Samples of the effective processor frequency, in MHz.
+#[derive(Default)]
+pub struct Frequencies {
+    /// Whether to read the frequency, since it's only shown if enabled in the config file.
+    enabled: Cell<bool>,
+    /// Whether the latest fetch had a frequency, since e.g. VMs often don't report it.
+    available: Cell<bool>,
+    prev_performance: Cell<Option<(u64, u64)>>,
+    samples: CircularBuffer<f64, SAMPLE_COUNT>,
+}
+
+impl Frequencies {
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.set(enabled);
+    }
+
+    fn update(&self, frequency: Option<Frequency>) {
+        match frequency {
+            Some(Frequency::Current { mhz }) => self.push(mhz),
+            Some(Frequency::Performance {
+                performance,
+                time,
+                nominal_mhz,
+            }) => {
+                // On first sample, just store the current counters; there's nothing to show until the next.
+                let Some((prev_performance, prev_time)) =
+                    self.prev_performance.replace(Some((performance, time)))
+                else {
+                    return;
+                };
+                let time_delta = time.wrapping_sub(prev_time);
+                if time_delta == 0 {
+                    // Both samples were taken within the same update of the counters.
+                    return;
+                }
+                let percent = performance.wrapping_sub(prev_performance) as f64 / time_delta as f64;
+                self.push(nominal_mhz as f64 * percent / 100.0);
+            }
+            None => {
+                self.available.set(false);
+                self.prev_performance.set(None);
+            }
+        }
+    }
+
+    fn push(&self, mhz: f64) {
+        self.available.set(true);
+        self.samples.push(mhz);
+    }
+
+    /// Average effective frequency, or `None` if it isn't enabled or available.
+    pub fn average(&self) -> Option<f64> {
+        self.available.get().then(|| {
+            self.samples
+                .exponential_moving_average(EXPONENTIAL_DECAY_ALPHA)
+        })
+    }
+}
+
+impl MetricSource for State {
+    type Raw = SystemCpuTimes;
+    type Sample = f64;
+    type Error = sys::Error;
+
+    fn name(&self) -> &str {
+        "CPU"
+    }
+
+    fn unit(&self) -> &str {
+        "%"
+    }
+
+    fn read(&self) -> Result<SystemCpuTimes, sys::Error> {
+        let mut times = sys::read_times()?;
+        if self.frequency.enabled.get() {
+            times.frequency = self.frequency_reader.read();
+        }
+        Ok(times)
+    }
+
+    fn convert(&self, times: SystemCpuTimes, _time_delta: Option<Duration>) -> f64 {
+        self.update(times)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn times(idle: u64, total: u64) -> SystemCpuTimes {
+        SystemCpuTimes {
+            total: CpuTimes { idle, total },
+            cores: Vec::new(),
+            frequency: None,
+        }
+    }
+
+    fn per_core(times: &[(u64, u64)]) -> SystemCpuTimes {
+        let cores = times
+            .iter()
+            .map(|&(idle, total)| CpuTimes { idle, total })
+            .collect::<Vec<_>>();
+        SystemCpuTimes {
+            total: CpuTimes {
+                idle: cores.iter().map(|core| core.idle).sum(),
+                total: cores.iter().map(|core| core.total).sum(),
+            },
+            cores,
+            frequency: None,
+        }
+    }
+
+    fn with_frequency(frequency: Frequency) -> SystemCpuTimes {
+        SystemCpuTimes {
+            frequency: Some(frequency),
+            ..times(0, 0)
+        }
+    }
+
+    #[test]
+    fn first_sample_is_zero() {
+        let state = State::default();
+        assert_eq!(state.update(times(100, 1000)), 0.0);
+    }
+
+    #[test]
+    fn busy_percent() {
+        let state = State::default();
+        state.update(times(100, 1000));
+        assert_eq!(state.update(times(175, 1100)), 25.0);
+        assert_eq!(state.update(times(175, 1200)), 100.0);
+    }
+
+    #[test]
+    fn wrapping_deltas() {
+        let state = State::default();
+        state.update(times(u64::MAX - 10, u64::MAX - 50));
+        assert_eq!(state.update(times(39, 49)), 50.0);
+    }
+
+    #[test]
+    fn no_elapsed_time() {
+        let state = State::default();
+        state.update(times(100, 1000));
+        assert_eq!(state.update(times(100, 1000)), 0.0);
+    }
+
+    #[test]
+    fn per_core_percent() {
+        let state = State::default();
+        let cores = state.cores();
+        assert!(cores.averages().is_empty());
+
+        state.update(per_core(&[
+            (100, 1000),
+            (100, 1000),
+            (100, 1000),
+            (100, 1000),
+        ]));
+        assert_eq!(cores.averages(), [0.0, 0.0, 0.0, 0.0]);
+
+        // One pegged core is only a quarter of the total.
+        let total = state.update(per_core(&[
+            (100, 1100),
+            (200, 1100),
+            (200, 1100),
+            (200, 1100),
+        ]));
+        assert_eq!(total, 25.0);
+        let expected = 100.0 + EXPONENTIAL_DECAY_ALPHA * (0.0 - 100.0);
+        assert_eq!(cores.averages(), [expected, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn core_count_change_restarts_per_core_samples() {
+        let state = State::default();
+        let cores = state.cores();
+
+        state.update(per_core(&[(100, 1000), (100, 1000)]));
+        state.update(per_core(&[(100, 1100), (100, 1100)]));
+        assert_eq!(cores.averages().len(), 2);
+        assert_ne!(cores.averages(), [0.0, 0.0]);
+
+        // e.g. a processor being taken offline
+        state.update(per_core(&[(100, 1200)]));
+        assert_eq!(cores.averages(), [0.0]);
+
+        state.update(per_core(&[(150, 1300)]));
+        let expected = 50.0 + EXPONENTIAL_DECAY_ALPHA * (0.0 - 50.0);
+        assert_eq!(cores.averages(), [expected]);
+    }
+
+    #[test]
+    fn current_frequency() {
+        let state = State::default();
+        let frequency = state.frequency();
+        assert_eq!(frequency.average(), None);
+
+        state.update(with_frequency(Frequency::Current { mhz: 2400.0 }));
+        assert_eq!(frequency.average(), Some(2400.0));
+
+        // e.g. cpufreq went away
+        state.update(times(0, 0));
+        assert_eq!(frequency.average(), None);
+    }
+
+    #[test]
+    fn frequency_from_performance() {
+        let state = State::default();
+        let frequency = state.frequency();
+        let performance = |performance, time| {
+            with_frequency(Frequency::Performance {
+                performance,
+                time,
+                nominal_mhz: 2000,
+            })
         };
 
-        self.prev_times.set(Some((idle, kernel_plus_idle, user)));
+        // Rates need two samples.
+        state.update(performance(1000, 10));
+        assert_eq!(frequency.average(), None);
+
+        // Turbo boost: 150% of nominal
+        state.update(performance(2500, 20));
+        assert_eq!(frequency.average(), Some(3000.0));
+
+        // No time has passed, so the last sample stands.
+        state.update(performance(2500, 20));
+        assert_eq!(frequency.average(), Some(3000.0));
+    }
 
-        Ok(percent)
+    #[test]
+    fn old_recordings_without_cores() {
+        let parsed: SystemCpuTimes = serde_json::from_str("{\"idle\":1,\"total\":2}").unwrap();
+        assert_eq!(parsed, times(1, 2));
     }
 }
@@ -0,0 +1,309 @@
+use crate::metrics::cpu::{CpuTimes, Frequency, SystemCpuTimes};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+pub use std::io::Error;
+
+const PROC_STAT_PATH: &str = "/proc/stat";
+const SYS_DEVICES_SYSTEM_CPU_PATH: &str = "/sys/devices/system/cpu";
+
+pub fn read_times() -> io::Result<SystemCpuTimes> {
+    read_times_from(PROC_STAT_PATH)
+}
+
+pub fn read_times_from(path: impl AsRef<Path>) -> io::Result<SystemCpuTimes> {
+    parse_times(BufReader::new(File::open(path)?))
+}
+
+/// Parse the aggregate `cpu` line and per-processor `cpuN` lines of `/proc/stat`, which look like:
+///
+/// ```text
+/// cpu  user nice system idle iowait irq softirq steal guest guest_nice
+/// cpu0 user nice system idle iowait irq softirq steal guest guest_nice
+/// ```
+///
+/// Offline processors are omitted, so the numbers aren't necessarily contiguous.
+fn parse_times(reader: impl BufRead) -> io::Result<SystemCpuTimes> {
+    let mut total = None;
+    let mut cores = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        let Some((name, values)) = line.split_once(' ') else {
+            continue;
+        };
+        let Some(number) = name.strip_prefix("cpu") else {
+            continue;
+        };
+
+        if number.is_empty() {
+            total = Some(parse_line(values)?);
+        } else if number.bytes().all(|c| c.is_ascii_digit()) {
+            cores.push(parse_line(values)?);
+        }
+    }
+
+    match total {
+        Some(total) => Ok(SystemCpuTimes {
+            total,
+            cores,
+            frequency: None,
+        }),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "no aggregate cpu line",
+        )),
+    }
+}
+
+/// Parse the values of a single `cpu` line.
+///
+/// All values are in USER_HZ (usually 1/100ths of a second).
+/// Guest time is already included in user time, so it is not counted separately.
+fn parse_line(values: &str) -> io::Result<CpuTimes> {
+    let mut values = values.split_ascii_whitespace().map(|value| {
+        value.parse::<u64>().map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid cpu time `{value}`: {e}"),
+            )
+        })
+    });
+    let mut next = || {
+        values.next().unwrap_or_else(|| {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "too few cpu times",
+            ))
+        })
+    };
+
+    let user = next()?;
+    let nice = next()?;
+    let system = next()?;
+    let idle = next()?;
+    let iowait = next()?;
+    let irq = next()?;
+    let softirq = next()?;
+    let steal = next()?;
+
+    let idle = idle.wrapping_add(iowait);
+    let busy = [user, nice, system, irq, softirq, steal]
+        .into_iter()
+        .fold(0, u64::wrapping_add);
+
+    Ok(CpuTimes {
+        idle,
+        total: busy.wrapping_add(idle),
+    })
+}
+
+pub struct FrequencyReader {
+    sys_devices_system_cpu: PathBuf,
+}
+
+impl Default for FrequencyReader {
+    fn default() -> Self {
+        Self::with_path(SYS_DEVICES_SYSTEM_CPU_PATH)
+    }
+}
+
+impl FrequencyReader {
+    pub fn with_path(sys_devices_system_cpu: impl Into<PathBuf>) -> Self {
+        Self {
+            sys_devices_system_cpu: sys_devices_system_cpu.into(),
+        }
+    }
+
+    /// Read the average current frequency of the logical processors,
+    /// from `/sys/devices/system/cpu/cpu*/cpufreq/scaling_cur_freq`, which is in kHz.
+    ///
+    /// Processors without cpufreq (e.g. offline, or in most VMs) are skipped,
+    /// and if none have it, the frequency isn't available.
+    ///
+    /// https://www.kernel.org/doc/html/latest/admin-guide/pm/cpufreq.html
+    pub fn read(&self) -> Option<Frequency> {
+        let entries = match fs::read_dir(&self.sys_devices_system_cpu) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::debug!(
+                    "Failed to list `{}`: {e}",
+                    self.sys_devices_system_cpu.display()
+                );
+                return None;
+            }
+        };
+
+        let khz = entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let name = entry.file_name().into_string().ok()?;
+                let number = name.strip_prefix("cpu")?;
+                if number.is_empty() || !number.bytes().all(|c| c.is_ascii_digit()) {
+                    return None;
+                }
+                let path = entry.path().join("cpufreq/scaling_cur_freq");
+                let contents = fs::read_to_string(&path).ok()?;
+                match contents.trim().parse::<u64>() {
+                    Ok(khz) => Some(khz),
+                    Err(e) => {
+                        log::debug!("Invalid frequency in `{}`: {e}", path.display());
+                        None
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if khz.is_empty() {
+            return None;
+        }
+        let mhz = khz.iter().sum::<u64>() as f64 / khz.len() as f64 / 1000.0;
+        Some(Frequency::Current { mhz })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::EXPONENTIAL_DECAY_ALPHA;
+    use crate::fixture::Fixture;
+    use crate::metrics::cpu::State;
+
+    const PROC_STAT_0: &str = "\
+cpu  14028 0 2054 90487 240 0 1 597 0 0
+cpu0 7014 0 1027 45243 120 0 1 298 0 0
+cpu1 7014 0 1027 45244 120 0 0 299 0 0
+intr 88606 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 1 1 2 0 0 0 0
+ctxt 207075
+btime 1792189203
+processes 1874
+procs_running 1
+procs_blocked 0
+softirq 96017 0 20372 2 1318 6427 0 19 37123 0 30756
+";
+
+    const PROC_STAT_1: &str = "\
+cpu  14178 5 2104 90587 260 10 6 597 0 0
+cpu0 7089 5 1052 45293 130 5 3 298 0 0
+cpu1 7089 0 1052 45294 130 5 3 299 0 0
+intr 89114 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 1 1 2 0 0 0 0
+ctxt 208313
+btime 1792189203
+processes 1877
+procs_running 2
+procs_blocked 0
+softirq 96511 0 20498 2 1322 6465 0 19 37311 0 30894
+";
+
+    #[test]
+    fn parse() {
+        let times = parse_times(PROC_STAT_0.as_bytes()).unwrap();
+        assert_eq!(
+            times.total,
+            CpuTimes {
+                idle: 90487 + 240,
+                total: 14028 + 2054 + 90487 + 240 + 1 + 597,
+            }
+        );
+        assert_eq!(
+            times.cores,
+            [
+                CpuTimes {
+                    idle: 45243 + 120,
+                    total: 7014 + 1027 + 45243 + 120 + 1 + 298,
+                },
+                CpuTimes {
+                    idle: 45244 + 120,
+                    total: 7014 + 1027 + 45244 + 120 + 299,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_without_trailing_guest_columns() {
+        // Kernels before 2.6.33 don't report guest_nice, and before 2.6.24 don't report guest.
+        let times = parse_times("cpu  1 2 3 4 5 6 7 8\n".as_bytes()).unwrap();
+        assert_eq!(
+            times.total,
+            CpuTimes {
+                idle: 4 + 5,
+                total: 1 + 2 + 3 + 4 + 5 + 6 + 7 + 8,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse_times("".as_bytes()).is_err());
+        assert!(parse_times("cpu0 1 2 3 4 5 6 7 8\n".as_bytes()).is_err());
+        assert!(parse_times("cpu  1 2 3 4 5 6 7\n".as_bytes()).is_err());
+        assert!(parse_times("cpu  1 2 3 x 5 6 7 8\n".as_bytes()).is_err());
+        assert!(parse_times("cpu  1 2 3 4 5 6 7 8\ncpu0 1 2 3\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn busy_percent_between_snapshots() {
+        let state = State::default();
+        assert_eq!(
+            state.update(parse_times(PROC_STAT_0.as_bytes()).unwrap()),
+            0.0
+        );
+
+        // busy: user 150 + nice 5 + system 50 + irq 10 + softirq 5 = 220
+        // idle: idle 100 + iowait 20 = 120
+        let percent = state.update(parse_times(PROC_STAT_1.as_bytes()).unwrap());
+        assert_eq!(percent, 220.0 * 100.0 / 340.0);
+
+        // cpu0: busy 75 + 5 + 25 + 5 + 2 = 112, idle 50 + 10 = 60
+        // cpu1: busy 75 + 25 + 5 + 3 = 108, idle 50 + 10 = 60
+        let [cpu0, cpu1] = state.cores().averages()[..] else {
+            panic!("expected two cores");
+        };
+        let expected = |latest: f64| latest + EXPONENTIAL_DECAY_ALPHA * (0.0 - latest);
+        assert_eq!(cpu0, expected(112.0 * 100.0 / 172.0));
+        assert_eq!(cpu1, expected(108.0 * 100.0 / 168.0));
+    }
+
+    #[test]
+    fn read_from_path() {
+        let fixture = Fixture::new();
+        fixture.file("stat", PROC_STAT_0);
+        let times = read_times_from(fixture.path("stat"));
+        assert_eq!(times.unwrap(), parse_times(PROC_STAT_0.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn read_live() {
+        read_times().unwrap();
+    }
+
+    #[test]
+    fn frequency_from_sysfs() {
+        let fixture = Fixture::new();
+        fixture
+            .file("cpu0/cpufreq/scaling_cur_freq", "800000\n")
+            .file("cpu1/cpufreq/scaling_cur_freq", "4800000\n")
+            // Offline
+            .dir("cpu2")
+            .file("cpu3/cpufreq/scaling_cur_freq", "garbage\n")
+            // Not processors
+            .file("cpufreq/policy0/scaling_cur_freq", "1\n")
+            .file("cpuidle/current_driver", "intel_idle\n");
+
+        let reader = FrequencyReader::with_path(fixture.path(""));
+        assert_eq!(reader.read(), Some(Frequency::Current { mhz: 2800.0 }));
+    }
+
+    #[test]
+    fn frequency_unavailable() {
+        let fixture = Fixture::new();
+        fixture.dir("cpu0").dir("cpu1");
+        assert_eq!(FrequencyReader::with_path(fixture.path("")).read(), None);
+        assert_eq!(
+            FrequencyReader::with_path(fixture.path("missing")).read(),
+            None
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use crate::metrics::cpu::{CpuTimes, Frequency, SystemCpuTimes};
+use crate::perf::{MultiCounter, PerfQueries, find_counters};
+use std::cell::OnceCell;
+use std::mem;
+use windows::Wdk::System::SystemInformation::{
+    NtQuerySystemInformation, SystemProcessorPerformanceInformation,
+};
+use windows::Win32::Foundation::FILETIME;
+use windows::Win32::System::Threading::GetSystemTimes;
+use windows::Win32::System::WindowsProgramming::SYSTEM_PROCESSOR_PERFORMANCE_INFORMATION;
+use windows::core::Result;
+
+pub use windows::core::Error;
+
+/// Maximum number of logical processors in a processor group.
+///
+/// `SystemProcessorPerformanceInformation` only reports processors in the calling thread's group.
+const MAX_PROCESSORS_PER_GROUP: usize = 64;
+
+const PROCESSOR_INFORMATION_COUNTERSET: &str = "Processor Information";
+/// Performance relative to the nominal frequency, as a percentage averaged over `PERFORMANCE_BASE_COUNTER`.
+const PERFORMANCE_COUNTER: &str = "% Processor Performance";
+const PERFORMANCE_BASE_COUNTER: &str = "% Processor Performance Base";
+/// Nominal frequency, in MHz.
+const NOMINAL_FREQUENCY_COUNTER: &str = "Processor Frequency";
+/// Instance covering every processor.
+const TOTAL_INSTANCE: &str = "_Total";
+
+pub fn read_times() -> Result<SystemCpuTimes> {
+    let mut idle = FILETIME::default();
+    let mut kernel_plus_idle = FILETIME::default();
+    let mut user = FILETIME::default();
+    // SAFETY: all pointers are to valid `FILETIME`s
+    unsafe {
+        GetSystemTimes(
+            Some(&mut idle),
+            Some(&mut kernel_plus_idle),
+            Some(&mut user),
+        )?
+    };
+
+    let to_100ns_intervals = |filetime: FILETIME| {
+        (u64::from(filetime.dwHighDateTime) << 32) | u64::from(filetime.dwLowDateTime)
+    };
+
+    let idle = to_100ns_intervals(idle);
+    let kernel_plus_idle = to_100ns_intervals(kernel_plus_idle);
+    let user = to_100ns_intervals(user);
+
+    Ok(SystemCpuTimes {
+        total: CpuTimes {
+            idle,
+            total: kernel_plus_idle.wrapping_add(user),
+        },
+        // Per-core times are optional, so a failure to read them shouldn't fail the total.
+        cores: read_core_times().unwrap_or_else(|e| {
+            log::debug!("Failed to read per-core times: {e}");
+            Vec::new()
+        }),
+        frequency: None,
+    })
+}
+
+fn read_core_times() -> Result<Vec<CpuTimes>> {
+    let mut info = [SYSTEM_PROCESSOR_PERFORMANCE_INFORMATION::default(); MAX_PROCESSORS_PER_GROUP];
+    let mut len = 0;
+    // SAFETY: buffer is valid for the given size; the return length is a valid `u32`
+    unsafe {
+        NtQuerySystemInformation(
+            SystemProcessorPerformanceInformation,
+            info.as_mut_ptr().cast(),
+            mem::size_of_val(&info) as u32,
+            &mut len,
+        )
+        .ok()?
+    };
+
+    let count = len as usize / mem::size_of::<SYSTEM_PROCESSOR_PERFORMANCE_INFORMATION>();
+
+    Ok(info[..count.min(MAX_PROCESSORS_PER_GROUP)]
+        .iter()
+        .map(|info| {
+            // Kernel time includes idle time, as with `GetSystemTimes`.
+            let idle = info.IdleTime as u64;
+            let kernel_plus_idle = info.KernelTime as u64;
+            let user = info.UserTime as u64;
+            CpuTimes {
+                idle,
+                total: kernel_plus_idle.wrapping_add(user),
+            }
+        })
+        .collect())
+}
+
+#[derive(Default)]
+pub struct FrequencyReader {
+    /// Opened on first use, since the frequency is only read if it's shown.
+    queries: OnceCell<PerfQueries<MultiCounter, 3, u64>>,
+}
+
+impl FrequencyReader {
+    fn queries(&self) -> Result<&PerfQueries<MultiCounter, 3, u64>> {
+        if let Some(queries) = self.queries.get() {
+            return Ok(queries);
+        }
+        let (counterset, counter_ids) = find_counters(
+            PROCESSOR_INFORMATION_COUNTERSET,
+            [
+                PERFORMANCE_COUNTER,
+                PERFORMANCE_BASE_COUNTER,
+                NOMINAL_FREQUENCY_COUNTER,
+            ],
+        )?;
+        let queries = PerfQueries::new_all_instances(counterset, &counter_ids)?;
+        Ok(self.queries.get_or_init(|| queries))
+    }
+
+    /// Read `\Processor Information(_Total)\% Processor Performance`, along with the frequency it's relative to.
+    pub fn read(&self) -> Option<Frequency> {
+        let values = match self.queries().and_then(|queries| queries.query_data()) {
+            Ok(values) => values,
+            Err(e) => {
+                log::debug!("Failed to read processor performance: {e}");
+                return None;
+            }
+        };
+        let &[performance, time, nominal_mhz] = values.get(TOTAL_INSTANCE)?;
+        Some(Frequency::Performance {
+            performance,
+            time,
+            nominal_mhz,
+        })
+    }
+}
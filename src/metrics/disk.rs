@@ -1,66 +1,143 @@
-use crate::perf::{PerfQueries, SingleCounter};
-use std::cell::Cell;
+use crate::metrics::{Directions, MetricSource, counter_delta};
+use crate::opt::DiskInstance;
+use crate::utils::glob_matches;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
 use std::time::Duration;
-use windows::core::{GUID, Result};
 
-// Should be the counterset ID of "FileSystem Disk Activity", aka {F596750D-B109-4247-A62F-DEA47A46E505}.
-// This is a counterset of type PERF_COUNTERSET_MULTI_AGGREGATE.
-// If this gets changed at some point, we'll need to use PerfEnumerateCounterSet + PerfQueryCounterSetRegistrationInfo
-// to find the ID dynamically, as described in:
-// https://learn.microsoft.com/en-ca/windows/win32/perfctrs/using-the-perflib-functions-to-consume-counter-data
-const FILESYSTEM_DISK_ACTIVITY_COUNTERSET: GUID =
-    GUID::from_u128(0xF596750DB1094247A62FDEA47A46E505);
+#[cfg_attr(windows, path = "disk/windows.rs")]
+#[cfg_attr(target_os = "linux", path = "disk/linux.rs")]
+mod sys;
 
-// "FileSystem Bytes Read" counter ID
-const FILESYSTEM_BYTES_READ_COUNTER: u32 = 0;
-// "FileSystem Bytes Written" counter ID
-const FILESYSTEM_BYTES_WRITTEN_COUNTER: u32 = 1;
+/// Cumulative bytes transferred to and from disk.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DiskBytes {
+    read: u64,
+    written: u64,
+}
 
-// Always filter to the "default" instance.
-// From introspection, it seems that there are only two instances: "default" and "_Total", which always have the same value.
-const FILESYSTEM_INSTANCE_NAME: &[u8; 6] = b"_Total";
+impl DiskBytes {
+    /// Bytes transferred since `prev`.
+    fn since(self, prev: Self) -> Self {
+        // 64-bit byte counts never wrap, but they go backwards when a disk is removed from the total, or re-created.
+        Self {
+            read: counter_delta(prev.read, self.read, u64::MAX),
+            written: counter_delta(prev.written, self.written, u64::MAX),
+        }
+    }
+}
+
+/// Cumulative bytes transferred in total, and for each disk or volume.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DiskCounters {
+    /// Across all disks, without double-counting partitions or other volumes layered on top of disks.
+    #[serde(flatten)]
+    total: DiskBytes,
+    /// For each disk or volume, by name. Older recordings don't have these.
+    #[serde(default)]
+    instances: BTreeMap<String, DiskBytes>,
+}
 
 pub struct State {
-    queries: PerfQueries<SingleCounter, 2, u64>,
-    prev_bytes_read: Cell<u64>,
-    prev_bytes_written: Cell<u64>,
+    reader: sys::Reader,
+    prev_counters: RefCell<DiskCounters>,
+    instance: Rc<RefCell<DiskInstance>>,
+    directions: Rc<Directions>,
 }
 
 impl State {
-    pub fn new() -> Result<Self> {
-        Ok(Self {
-            queries: PerfQueries::new_filtered_to_single_counter(
-                FILESYSTEM_DISK_ACTIVITY_COUNTERSET,
-                &[
-                    FILESYSTEM_BYTES_READ_COUNTER,
-                    FILESYSTEM_BYTES_WRITTEN_COUNTER,
-                ],
-                FILESYSTEM_INSTANCE_NAME,
-            )?,
-            prev_bytes_read: Default::default(),
-            prev_bytes_written: Default::default(),
-        })
+    pub fn new() -> Result<Self, sys::Error> {
+        Ok(Self::with_reader(sys::Reader::new()?))
     }
 
-    pub fn fetch_mbyte(&self, time_delta: Option<Duration>) -> Result<f64> {
-        let [bytes_read, bytes_written] = self.queries.query_data()?;
+    fn with_reader(reader: sys::Reader) -> Self {
+        Self {
+            reader,
+            prev_counters: Default::default(),
+            instance: Default::default(),
+            directions: Default::default(),
+        }
+    }
 
-        // On first sample, just store the current byte count and return zero.
-        let mbyte = match time_delta {
-            Some(time_delta) => {
-                let bytes_read_delta = bytes_read.wrapping_sub(self.prev_bytes_read.get());
-                let bytes_written_delta = bytes_written.wrapping_sub(self.prev_bytes_written.get());
+    /// Handle to the read and write throughput, which is updated whenever this source is fetched.
+    pub fn directions(&self) -> Rc<Directions> {
+        Rc::clone(&self.directions)
+    }
+
+    /// Handle to which disk or volume to measure, which is read whenever this source is fetched.
+    pub fn instance(&self) -> Rc<RefCell<DiskInstance>> {
+        Rc::clone(&self.instance)
+    }
 
-                let total_byte_delta = bytes_read_delta + bytes_written_delta;
+    fn update(&self, counters: DiskCounters, time_delta: Option<Duration>) -> Option<f64> {
+        // If no time has passed, there's no throughput to sample,
+        // so keep the previous counters, so that these bytes are counted in the next fetch.
+        if time_delta == Some(Duration::ZERO) {
+            return None;
+        }
 
-                total_byte_delta as f64 / (1024 * 1024) as f64 / time_delta.as_secs_f64()
-            }
+        let prev = self.prev_counters.replace(counters.clone());
+
+        // Disks which weren't present in the previous fetch don't have a delta yet.
+        let instance_deltas = counters.instances.iter().filter_map(|(name, &bytes)| {
+            let &prev_bytes = prev.instances.get(name)?;
+            Some((name, bytes.since(prev_bytes)))
+        });
+
+        let delta = match &*self.instance.borrow() {
+            DiskInstance::Total => counters.total.since(prev.total),
+            DiskInstance::Busiest => instance_deltas
+                .map(|(_, delta)| delta)
+                .max_by_key(|delta| delta.read.saturating_add(delta.written))
+                .unwrap_or_default(),
+            DiskInstance::Named(pattern) => instance_deltas
+                .filter(|(name, _)| glob_matches(pattern, name))
+                .fold(DiskBytes::default(), |sum, (_, delta)| DiskBytes {
+                    read: sum.read.saturating_add(delta.read),
+                    written: sum.written.saturating_add(delta.written),
+                }),
+        };
+        let DiskBytes {
+            read: bytes_read_delta,
+            written: bytes_written_delta,
+        } = delta;
+
+        // On first sample, just store the current byte count and return zero.
+        let to_mbyte = |byte_delta: u64| match time_delta {
+            Some(time_delta) => byte_delta as f64 / (1024 * 1024) as f64 / time_delta.as_secs_f64(),
             None => 0.0,
         };
 
-        self.prev_bytes_read.set(bytes_read);
-        self.prev_bytes_written.set(bytes_written);
+        self.directions
+            .push(to_mbyte(bytes_read_delta), to_mbyte(bytes_written_delta));
+
+        Some(to_mbyte(
+            bytes_read_delta.saturating_add(bytes_written_delta),
+        ))
+    }
+}
+
+impl MetricSource for State {
+    type Raw = DiskCounters;
+    /// Throughput, or `None` if no time has passed since the previous fetch.
+    type Sample = Option<f64>;
+    type Error = sys::Error;
+
+    fn name(&self) -> &str {
+        "disk"
+    }
+
+    fn unit(&self) -> &str {
+        "MB/s"
+    }
+
+    fn read(&self) -> Result<DiskCounters, sys::Error> {
+        self.reader.read_counters()
+    }
 
-        Ok(mbyte)
+    fn convert(&self, counters: DiskCounters, time_delta: Option<Duration>) -> Option<f64> {
+        self.update(counters, time_delta)
     }
 }
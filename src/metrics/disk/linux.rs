@@ -0,0 +1,341 @@
+use crate::metrics::disk::{DiskBytes, DiskCounters};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::PathBuf;
+
+pub use std::io::Error;
+
+const PROC_DISKSTATS_PATH: &str = "/proc/diskstats";
+
+/// Sectors in `/proc/diskstats` are always 512 bytes, regardless of the device's actual sector size.
+const SECTOR_SIZE: u64 = 512;
+
+/// Devices which don't correspond to physical disk activity.
+const VIRTUAL_DEVICE_PREFIXES: &[&str] = &["loop", "ram", "zram"];
+
+/// Devices which are layered on top of other devices (LVM and RAID volumes),
+/// so counting them in the total would double count the underlying disk's traffic.
+const LAYERED_DEVICE_PREFIXES: &[&str] = &["dm-", "md"];
+
+pub struct Reader {
+    path: PathBuf,
+}
+
+impl Reader {
+    pub fn new() -> io::Result<Self> {
+        Ok(Self::with_path(PROC_DISKSTATS_PATH))
+    }
+
+    pub fn with_path(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn read_counters(&self) -> io::Result<DiskCounters> {
+        parse_counters(BufReader::new(File::open(&self.path)?))
+    }
+}
+
+/// Parse `/proc/diskstats`, summing bytes read and written across all whole physical disks,
+/// and listing those of every disk, partition, and volume.
+///
+/// Each line looks like:
+///
+/// ```text
+///  254       0 vda 6273 4873 2087306 8220 2419 8875 1091936 2373 0 3168 10761 742 0 173664 164 43 3
+/// major minor name reads reads_merged sectors_read ms_reading writes writes_merged sectors_written ...
+/// ```
+///
+/// https://www.kernel.org/doc/Documentation/ABI/testing/procfs-diskstats
+fn parse_counters(reader: impl BufRead) -> io::Result<DiskCounters> {
+    let mut devices = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        let fields = line.split_ascii_whitespace().collect::<Vec<_>>();
+        let &[
+            _major,
+            _minor,
+            name,
+            _reads,
+            _reads_merged,
+            sectors_read,
+            _ms_reading,
+            _writes,
+            _writes_merged,
+            sectors_written,
+            ..,
+        ] = fields.as_slice()
+        else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("too few fields in diskstats line `{line}`"),
+            ));
+        };
+
+        let parse = |value: &str| {
+            value.parse::<u64>().map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid sector count `{value}` for {name}: {e}"),
+                )
+            })
+        };
+
+        devices.push((
+            name.to_owned(),
+            parse(sectors_read)?,
+            parse(sectors_written)?,
+        ));
+    }
+
+    let mut counters = DiskCounters::default();
+
+    for (name, sectors_read, sectors_written) in &devices {
+        let has_prefix = |prefixes: &[&str]| prefixes.iter().any(|prefix| name.starts_with(prefix));
+
+        if has_prefix(VIRTUAL_DEVICE_PREFIXES) {
+            continue;
+        }
+
+        let bytes = DiskBytes {
+            read: sectors_read * SECTOR_SIZE,
+            written: sectors_written * SECTOR_SIZE,
+        };
+        counters.instances.insert(name.clone(), bytes);
+
+        // Volumes and partitions are also counted in their underlying disk, so leave them out of the total.
+        if has_prefix(LAYERED_DEVICE_PREFIXES)
+            || devices
+                .iter()
+                .any(|(parent, _, _)| is_partition_of(name, parent))
+        {
+            continue;
+        }
+
+        counters.total.read += bytes.read;
+        counters.total.written += bytes.written;
+    }
+
+    Ok(counters)
+}
+
+/// Whether `name` is the name of a partition of the disk `parent`, per the kernel's naming scheme:
+/// if the disk name ends in a digit, partitions are named like `nvme0n1p1`, otherwise like `sda1`.
+fn is_partition_of(name: &str, parent: &str) -> bool {
+    let Some(suffix) = name.strip_prefix(parent) else {
+        return false;
+    };
+
+    let number = if parent.ends_with(|c: char| c.is_ascii_digit()) {
+        match suffix.strip_prefix('p') {
+            Some(number) => number,
+            None => return false,
+        }
+    } else {
+        suffix
+    };
+
+    !number.is_empty() && number.bytes().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::EXPONENTIAL_DECAY_ALPHA;
+    use crate::fixture::Fixture;
+    use crate::metrics::MetricSource;
+    use crate::metrics::disk::State;
+    use crate::opt::DiskInstance;
+    use std::time::Duration;
+
+    const PROC_DISKSTATS_0: &str = "\
+   7       0 loop0 52 0 2118 13 0 0 0 0 0 44 13 0 0 0 0 0 0
+   7       1 loop1 1104 0 4346 161 0 0 0 0 0 228 161 0 0 0 0 0 0
+ 259       0 nvme0n1 149812 39113 11086910 22846 242155 196745 15391560 171540 0 178072 215218 0 0 0 0 23196 20831
+ 259       1 nvme0n1p1 408 1512 21858 85 2 0 2 0 0 100 85 0 0 0 0 0 0
+ 259       2 nvme0n1p2 149299 37601 11060940 22744 242153 196745 15391558 171540 0 177940 194284 0 0 0 0 0 0
+   8       0 sda 2087 4113 273450 1310 31 30 2368 191 0 1264 1502 0 0 0 0 2 0
+   8       1 sda1 1953 4113 267410 1280 31 30 2368 191 0 1220 1472 0 0 0 0 0 0
+ 253       0 dm-0 186707 0 11058410 46100 438900 0 15391558 1104036 0 178356 1150136 0 0 0 0 0 0
+ 252       0 zram0 304 0 2432 1 7281 0 58248 30 0 60 31 0 0 0 0 0 0
+";
+
+    const PROC_DISKSTATS_1: &str = "\
+   7       0 loop0 52 0 2118 13 0 0 0 0 0 44 13 0 0 0 0 0 0
+   7       1 loop1 1104 0 4346 161 0 0 0 0 0 228 161 0 0 0 0 0 0
+ 259       0 nvme0n1 149912 39113 11091006 22866 242355 196845 15395656 171640 0 178172 215318 0 0 0 0 23196 20831
+ 259       1 nvme0n1p1 408 1512 21858 85 2 0 2 0 0 100 85 0 0 0 0 0 0
+ 259       2 nvme0n1p2 149399 37601 11065036 22764 242353 196845 15395654 171640 0 178040 194384 0 0 0 0 0 0
+   8       0 sda 2287 4113 275498 1410 31 30 2368 191 0 1364 1602 0 0 0 0 2 0
+   8       1 sda1 2153 4113 269458 1380 31 30 2368 191 0 1320 1572 0 0 0 0 0 0
+ 253       0 dm-0 186807 0 11062506 46200 439100 0 15395654 1104136 0 178456 1150236 0 0 0 0 0 0
+ 252       0 zram0 404 0 9432 2 8281 0 158248 40 0 70 41 0 0 0 0 0 0
+";
+
+    #[test]
+    fn parse() {
+        let counters = parse_counters(PROC_DISKSTATS_0.as_bytes()).unwrap();
+        assert_eq!(
+            counters.total,
+            DiskBytes {
+                read: (11086910 + 273450) * 512,
+                written: (15391560 + 2368) * 512,
+            }
+        );
+
+        let names = counters.instances.keys().collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["dm-0", "nvme0n1", "nvme0n1p1", "nvme0n1p2", "sda", "sda1"]
+        );
+        assert_eq!(
+            counters.instances["sda1"],
+            DiskBytes {
+                read: 267410 * 512,
+                written: 2368 * 512,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_old_kernel_format() {
+        // Before Linux 4.18, there were no discard fields.
+        let counters =
+            parse_counters("   8       0 sda 1 2 3 4 5 6 7 8 9 10 11\n".as_bytes()).unwrap();
+        assert_eq!(
+            counters.total,
+            DiskBytes {
+                read: 3 * 512,
+                written: 7 * 512,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse_counters("   8       0 sda 1 2 3\n".as_bytes()).is_err());
+        assert!(parse_counters("   8       0 sda 1 2 x 4 5 6 7 8 9 10 11\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn partitions() {
+        assert!(is_partition_of("sda1", "sda"));
+        assert!(is_partition_of("sda12", "sda"));
+        assert!(is_partition_of("nvme0n1p1", "nvme0n1"));
+        assert!(is_partition_of("mmcblk0p2", "mmcblk0"));
+
+        assert!(!is_partition_of("sda", "sda"));
+        assert!(!is_partition_of("sdab", "sda"));
+        assert!(!is_partition_of("sdb1", "sda"));
+        assert!(!is_partition_of("nvme0n10", "nvme0n1"));
+        assert!(!is_partition_of("nvme0n1p", "nvme0n1"));
+    }
+
+    #[test]
+    fn throughput_between_snapshots() {
+        let fixture = Fixture::new();
+        let state = State::with_reader(Reader::with_path(fixture.path("diskstats")));
+
+        fixture.file("diskstats", PROC_DISKSTATS_0);
+        let first = state.fetch(None);
+
+        fixture.file("diskstats", PROC_DISKSTATS_1);
+        let second = state.fetch(Some(Duration::from_secs(2)));
+
+        assert_eq!(first.unwrap(), Some(0.0));
+        // nvme0n1: 4096 sectors read, 4096 written; sda: 2048 read
+        let bytes = (4096 + 4096 + 2048) * 512;
+        assert_eq!(
+            second.unwrap(),
+            Some(bytes as f64 / (1024 * 1024) as f64 / 2.0)
+        );
+
+        // The first sample of each direction is zero, like the total.
+        let directions = state.directions();
+        let expected = |sectors: u64| {
+            let latest = (sectors * 512) as f64 / (1024 * 1024) as f64 / 2.0;
+            latest + EXPONENTIAL_DECAY_ALPHA * (0.0 - latest)
+        };
+        assert_eq!(directions.average_incoming(), expected(4096 + 2048));
+        assert_eq!(directions.average_outgoing(), expected(4096));
+    }
+
+    #[test]
+    fn throughput_over_long_and_short_intervals() {
+        let state = State::with_reader(Reader::with_path("unused"));
+        let bytes = |mbyte: u64| DiskCounters {
+            total: DiskBytes {
+                read: mbyte * 1024 * 1024,
+                written: 0,
+            },
+            instances: Default::default(),
+        };
+
+        state.convert(bytes(0), None);
+
+        // e.g. the fetch timer being stopped while the session is locked
+        let after_pause = state.convert(bytes(3600), Some(Duration::from_secs(60 * 60)));
+        assert_eq!(after_pause, Some(1.0));
+
+        let sub_millisecond = state.convert(bytes(3601), Some(Duration::from_micros(100)));
+        assert_eq!(sub_millisecond, Some(10_000.0));
+
+        // No time passed, so there's no throughput to sample.
+        let no_time = state.convert(bytes(3602), Some(Duration::ZERO));
+        assert_eq!(no_time, None);
+
+        // The bytes from the zero-length interval are counted in the next fetch.
+        let next = state.convert(bytes(3603), Some(Duration::from_secs(1)));
+        assert_eq!(next, Some(2.0));
+    }
+
+    #[test]
+    fn instance_selection() {
+        let fixture = Fixture::new();
+        let state = State::with_reader(Reader::with_path(fixture.path("diskstats")));
+        let mbyte = |sectors: u64| Some((sectors * 512) as f64 / (1024 * 1024) as f64 / 2.0);
+
+        let fetch_with = |instance: DiskInstance| {
+            *state.instance().borrow_mut() = instance;
+            fixture.file("diskstats", PROC_DISKSTATS_0);
+            state.fetch(None).unwrap();
+            fixture.file("diskstats", PROC_DISKSTATS_1);
+            state.fetch(Some(Duration::from_secs(2))).unwrap()
+        };
+
+        // nvme0n1: 4096 sectors read, 4096 written
+        assert_eq!(fetch_with(DiskInstance::Busiest), mbyte(4096 + 4096));
+        // sda: 2048 sectors read
+        assert_eq!(
+            fetch_with(DiskInstance::Named("SDA".to_owned())),
+            mbyte(2048)
+        );
+        // nvme0n1p1 is idle
+        assert_eq!(
+            fetch_with(DiskInstance::Named("nvme0n1p?".to_owned())),
+            mbyte(4096 + 4096)
+        );
+        assert_eq!(fetch_with(DiskInstance::Named("sdz".to_owned())), Some(0.0));
+        assert_eq!(fetch_with(DiskInstance::Total), mbyte(4096 + 4096 + 2048));
+    }
+
+    #[test]
+    fn old_recordings_without_instances() {
+        let counters: DiskCounters = serde_json::from_str(r#"{"read":1,"written":2}"#).unwrap();
+        assert_eq!(
+            counters,
+            DiskCounters {
+                total: DiskBytes {
+                    read: 1,
+                    written: 2
+                },
+                instances: Default::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn read_live() {
+        Reader::new().unwrap().read_counters().unwrap();
+    }
+}
@@ -0,0 +1,80 @@
+use crate::metrics::disk::{DiskBytes, DiskCounters};
+use crate::perf::{MultiCounter, PerfQueries, find_counters};
+use std::collections::BTreeMap;
+use windows::Win32::Foundation::ERROR_NOT_FOUND;
+use windows::core::Result;
+
+pub use windows::core::Error;
+
+// Found by name, since the IDs are only stable as long as Microsoft doesn't change them.
+// This is a counterset of type PERF_COUNTERSET_MULTI_AGGREGATE.
+const FILESYSTEM_DISK_ACTIVITY_COUNTERSET: &str = "FileSystem Disk Activity";
+const FILESYSTEM_BYTES_READ_COUNTER: &str = "FileSystem Bytes Read";
+const FILESYSTEM_BYTES_WRITTEN_COUNTER: &str = "FileSystem Bytes Written";
+
+// Per-volume counters, since "FileSystem Disk Activity" only has a "default" instance besides the total.
+// Instances are drive letters like "C:", or e.g. "HarddiskVolume1" for volumes without one.
+const LOGICAL_DISK_COUNTERSET: &str = "LogicalDisk";
+const LOGICAL_DISK_BYTES_READ_COUNTER: &str = "Disk Read Bytes/sec";
+const LOGICAL_DISK_BYTES_WRITTEN_COUNTER: &str = "Disk Write Bytes/sec";
+
+// The total is reported as its own instance, in both countersets.
+const TOTAL_INSTANCE_NAME: &str = "_Total";
+
+pub struct Reader {
+    filesystem: PerfQueries<MultiCounter, 2, u64>,
+    logical_disk: PerfQueries<MultiCounter, 2, u64>,
+}
+
+impl Reader {
+    pub fn new() -> Result<Self> {
+        let (counterset, counter_ids) = find_counters(
+            FILESYSTEM_DISK_ACTIVITY_COUNTERSET,
+            [
+                FILESYSTEM_BYTES_READ_COUNTER,
+                FILESYSTEM_BYTES_WRITTEN_COUNTER,
+            ],
+        )?;
+        let filesystem = PerfQueries::new_all_instances(counterset, &counter_ids)?;
+
+        let (counterset, counter_ids) = find_counters(
+            LOGICAL_DISK_COUNTERSET,
+            [
+                LOGICAL_DISK_BYTES_READ_COUNTER,
+                LOGICAL_DISK_BYTES_WRITTEN_COUNTER,
+            ],
+        )?;
+        let logical_disk = PerfQueries::new_all_instances(counterset, &counter_ids)?;
+
+        Ok(Self {
+            filesystem,
+            logical_disk,
+        })
+    }
+
+    pub fn read_counters(&self) -> Result<DiskCounters> {
+        // The filesystem total also counts I/O served from the cache, unlike the volumes, so it's kept for the total.
+        // Without it, reporting zero would look like a reset on the next fetch, so fail instead.
+        let Some([read, written]) = self.filesystem.query_data()?.remove(TOTAL_INSTANCE_NAME)
+        else {
+            return Err(Error::new(
+                ERROR_NOT_FOUND.to_hresult(),
+                format!(
+                    "missing {TOTAL_INSTANCE_NAME} instance of {FILESYSTEM_DISK_ACTIVITY_COUNTERSET}"
+                ),
+            ));
+        };
+        let total = DiskBytes { read, written };
+
+        // Despite their names, these counters are cumulative byte counts; rates are computed from them like any other.
+        let instances = self
+            .logical_disk
+            .query_data()?
+            .into_iter()
+            .filter(|(name, _)| name != TOTAL_INSTANCE_NAME)
+            .map(|(name, [read, written])| (name, DiskBytes { read, written }))
+            .collect::<BTreeMap<_, _>>();
+
+        Ok(DiskCounters { total, instances })
+    }
+}
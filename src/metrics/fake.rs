@@ -0,0 +1,105 @@
+use crate::metrics::MetricSource;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::rc::Rc;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub struct FakeError;
+
+impl Display for FakeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("fake error")
+    }
+}
+
+impl Error for FakeError {}
+
+/// Metric source which returns a scripted sequence of results.
+///
+/// Clones share the same script, so tests can keep a handle to inspect calls after passing a clone to `Metrics`.
+#[derive(Clone)]
+pub struct Scripted(Rc<Inner>);
+
+struct Inner {
+    results: RefCell<VecDeque<Result<f64, FakeError>>>,
+    /// Returned once the script is exhausted; if `None`, running out of results is a test failure.
+    fallback: Option<f64>,
+    time_deltas: RefCell<Vec<Option<Duration>>>,
+}
+
+impl Scripted {
+    pub fn new(results: impl IntoIterator<Item = Result<f64, FakeError>>) -> Self {
+        Self(Rc::new(Inner {
+            results: RefCell::new(results.into_iter().collect()),
+            fallback: None,
+            time_deltas: Default::default(),
+        }))
+    }
+
+    /// A source which always returns zero.
+    pub fn zeros() -> Self {
+        Self(Rc::new(Inner {
+            results: Default::default(),
+            fallback: Some(0.0),
+            time_deltas: Default::default(),
+        }))
+    }
+
+    /// Time deltas passed to each call to `convert`, in order.
+    pub fn time_deltas(&self) -> Vec<Option<Duration>> {
+        self.0.time_deltas.borrow().clone()
+    }
+}
+
+impl MetricSource for Scripted {
+    type Raw = f64;
+    type Sample = f64;
+    type Error = FakeError;
+
+    fn name(&self) -> &str {
+        "fake"
+    }
+
+    fn unit(&self) -> &str {
+        "units"
+    }
+
+    fn read(&self) -> Result<f64, FakeError> {
+        match self.0.results.borrow_mut().pop_front() {
+            Some(result) => result,
+            None => match self.0.fallback {
+                Some(fallback) => Ok(fallback),
+                None => panic!("scripted source ran out of results"),
+            },
+        }
+    }
+
+    fn convert(&self, raw: f64, time_delta: Option<Duration>) -> f64 {
+        self.0.time_deltas.borrow_mut().push(time_delta);
+        raw
+    }
+}
+
+/// Metric source which always returns the same sample, e.g. `None` for a machine without a battery.
+pub struct Constant<T>(pub T);
+
+impl<T: Clone> MetricSource for Constant<T> {
+    type Raw = ();
+    type Sample = T;
+    type Error = FakeError;
+
+    fn name(&self) -> &str {
+        "constant"
+    }
+
+    fn read(&self) -> Result<(), FakeError> {
+        Ok(())
+    }
+
+    fn convert(&self, _raw: (), _time_delta: Option<Duration>) -> T {
+        self.0.clone()
+    }
+}
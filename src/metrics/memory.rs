@@ -1,23 +1,190 @@
-use std::mem;
-use windows::Win32::System::SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
-use windows::core::Result;
+use crate::constants::SAMPLE_COUNT;
+use crate::metrics::MetricSource;
+use crate::opt::MemoryUsage;
+use crate::stats::CircularBuffer;
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+use std::time::Duration;
 
-#[derive(Default)]
-pub struct State;
+#[cfg_attr(windows, path = "memory/windows.rs")]
+#[cfg_attr(target_os = "linux", path = "memory/linux.rs")]
+mod sys;
+
+/// Physical memory counters, in bytes.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MemoryStatus {
+    total: u64,
+    /// Memory that can be used without swapping, including reclaimable cache.
+    available: u64,
+    /// Memory that is completely unused, if known.
+    free: Option<u64>,
+    /// Memory used for buffers and reclaimable cache, if known.
+    buffers_and_cache: Option<u64>,
+    /// Swap space, or the commit limit on Windows, i.e. physical memory plus page files.
+    /// Older recordings don't have this.
+    #[serde(default)]
+    swap_total: u64,
+    /// Swap space that's unused, or commit charge that's still available on Windows.
+    #[serde(default)]
+    swap_available: u64,
+}
+
+pub struct State {
+    usage: MemoryUsage,
+    swap: Rc<CircularBuffer<f64, SAMPLE_COUNT>>,
+}
 
 impl State {
-    pub fn fetch_percent(&self) -> Result<f64> {
-        let mut mem_status = MEMORYSTATUSEX {
-            dwLength: mem::size_of::<MEMORYSTATUSEX>() as u32,
-            ..Default::default()
+    pub fn new(usage: MemoryUsage) -> Self {
+        Self {
+            usage,
+            swap: Default::default(),
+        }
+    }
+
+    /// Handle to the swap usage, which is updated whenever this source is fetched.
+    pub fn swap(&self) -> Rc<CircularBuffer<f64, SAMPLE_COUNT>> {
+        Rc::clone(&self.swap)
+    }
+}
+
+fn used_percent(status: MemoryStatus, usage: MemoryUsage) -> f64 {
+    let MemoryStatus {
+        total,
+        available,
+        free,
+        buffers_and_cache,
+        ..
+    } = status;
+
+    // If the platform doesn't provide the necessary counters, fall back to available memory.
+    let unused = match (usage, free, buffers_and_cache) {
+        (MemoryUsage::Available, _, _) => available,
+        (MemoryUsage::Free, Some(free), _) => free,
+        (MemoryUsage::ExcludingCache, Some(free), Some(buffers_and_cache)) => {
+            free.saturating_add(buffers_and_cache)
+        }
+        (MemoryUsage::Free | MemoryUsage::ExcludingCache, _, _) => available,
+    };
+
+    let used = total.saturating_sub(unused);
+    (used * 100) as f64 / total as f64
+}
+
+fn swap_used_percent(status: MemoryStatus) -> f64 {
+    let MemoryStatus {
+        swap_total,
+        swap_available,
+        ..
+    } = status;
+
+    // Swap is often disabled on Linux.
+    if swap_total == 0 {
+        return 0.0;
+    }
+
+    let used = swap_total.saturating_sub(swap_available);
+    (used * 100) as f64 / swap_total as f64
+}
+
+impl MetricSource for State {
+    type Raw = MemoryStatus;
+    type Sample = f64;
+    type Error = sys::Error;
+
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    fn unit(&self) -> &str {
+        "%"
+    }
+
+    fn read(&self) -> Result<MemoryStatus, sys::Error> {
+        sys::read_status()
+    }
+
+    fn convert(&self, status: MemoryStatus, _time_delta: Option<Duration>) -> f64 {
+        self.swap.push(swap_used_percent(status));
+        used_percent(status, self.usage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATUS: MemoryStatus = MemoryStatus {
+        total: 1000,
+        available: 600,
+        free: Some(200),
+        buffers_and_cache: Some(500),
+        swap_total: 2000,
+        swap_available: 1500,
+    };
+
+    #[test]
+    fn usage_definitions() {
+        assert_eq!(used_percent(STATUS, MemoryUsage::Available), 40.0);
+        assert_eq!(used_percent(STATUS, MemoryUsage::Free), 80.0);
+        assert_eq!(used_percent(STATUS, MemoryUsage::ExcludingCache), 30.0);
+    }
+
+    #[test]
+    fn falls_back_to_available() {
+        let status = MemoryStatus {
+            free: None,
+            buffers_and_cache: None,
+            ..STATUS
         };
-        // SAFETY: `mem_status` is a valid `MEMORYSTATUSEX`
-        unsafe { GlobalMemoryStatusEx(&mut mem_status)? };
+        assert_eq!(used_percent(status, MemoryUsage::Available), 40.0);
+        assert_eq!(used_percent(status, MemoryUsage::Free), 40.0);
+        assert_eq!(used_percent(status, MemoryUsage::ExcludingCache), 40.0);
+    }
 
-        let used = mem_status.ullTotalPhys - mem_status.ullAvailPhys;
-        let total = mem_status.ullTotalPhys;
-        let percent = (used * 100) as f64 / total as f64;
+    #[test]
+    fn swap() {
+        assert_eq!(swap_used_percent(STATUS), 25.0);
 
-        Ok(percent)
+        // No swap, or more available than the total after the page file shrinks between reads
+        let status = MemoryStatus {
+            swap_total: 0,
+            swap_available: 0,
+            ..STATUS
+        };
+        assert_eq!(swap_used_percent(status), 0.0);
+        let status = MemoryStatus {
+            swap_available: 3000,
+            ..STATUS
+        };
+        assert_eq!(swap_used_percent(status), 0.0);
+    }
+
+    #[test]
+    fn swap_is_updated_with_memory() {
+        let state = State::new(MemoryUsage::Available);
+        let swap = state.swap();
+
+        assert_eq!(state.convert(STATUS, None), 40.0);
+        assert_eq!(swap.exponential_moving_average(0.5), 25.0);
+    }
+
+    #[test]
+    fn old_recordings_without_swap() {
+        let status: MemoryStatus = serde_json::from_str(
+            r#"{"total":1000,"available":600,"free":null,"buffers_and_cache":null}"#,
+        )
+        .unwrap();
+        assert_eq!(swap_used_percent(status), 0.0);
+    }
+
+    #[test]
+    fn never_negative() {
+        let status = MemoryStatus {
+            free: Some(900),
+            buffers_and_cache: Some(900),
+            ..STATUS
+        };
+        assert_eq!(used_percent(status, MemoryUsage::ExcludingCache), 0.0);
     }
 }
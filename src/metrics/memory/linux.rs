@@ -0,0 +1,155 @@
+use crate::metrics::memory::MemoryStatus;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+pub use std::io::Error;
+
+const PROC_MEMINFO_PATH: &str = "/proc/meminfo";
+
+pub fn read_status() -> io::Result<MemoryStatus> {
+    read_status_from(PROC_MEMINFO_PATH)
+}
+
+pub fn read_status_from(path: impl AsRef<Path>) -> io::Result<MemoryStatus> {
+    parse_status(BufReader::new(File::open(path)?))
+}
+
+/// Parse `/proc/meminfo`, which contains lines like:
+///
+/// ```text
+/// MemTotal:        6147400 kB
+/// MemFree:         4302276 kB
+/// ```
+fn parse_status(reader: impl BufRead) -> io::Result<MemoryStatus> {
+    let mut total = None;
+    let mut free = None;
+    let mut available = None;
+    let mut buffers = None;
+    let mut cached = None;
+    let mut sreclaimable = None;
+    let mut swap_total = None;
+    let mut swap_free = None;
+
+    for line in reader.lines() {
+        let line = line?;
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+
+        let field = match key {
+            "MemTotal" => &mut total,
+            "MemFree" => &mut free,
+            "MemAvailable" => &mut available,
+            "Buffers" => &mut buffers,
+            "Cached" => &mut cached,
+            "SReclaimable" => &mut sreclaimable,
+            "SwapTotal" => &mut swap_total,
+            "SwapFree" => &mut swap_free,
+            _ => continue,
+        };
+
+        let value = value.trim();
+        let kbytes = value.strip_suffix(" kB").unwrap_or(value);
+        let kbytes = kbytes.parse::<u64>().map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid value for {key} `{value}`: {e}"),
+            )
+        })?;
+
+        *field = Some(kbytes * 1024);
+    }
+
+    let missing = |key: &str| io::Error::new(io::ErrorKind::InvalidData, format!("missing {key}"));
+
+    let total = total.ok_or_else(|| missing("MemTotal"))?;
+    let free = free.ok_or_else(|| missing("MemFree"))?;
+    let buffers = buffers.unwrap_or(0);
+    let cached = cached.unwrap_or(0);
+    let sreclaimable = sreclaimable.unwrap_or(0);
+
+    // Same as the `buff/cache` column of `free`.
+    let buffers_and_cache = buffers + cached + sreclaimable;
+
+    // MemAvailable was added in Linux 3.14; before that, estimate it the same way `free` does.
+    let available = available.unwrap_or(free + buffers_and_cache);
+
+    Ok(MemoryStatus {
+        total,
+        available,
+        free: Some(free),
+        buffers_and_cache: Some(buffers_and_cache),
+        // Swap may be disabled, in which case these are zero, or missing if the kernel was built without it.
+        swap_total: swap_total.unwrap_or(0),
+        swap_available: swap_free.unwrap_or(0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROC_MEMINFO: &str = "\
+MemTotal:        6147400 kB
+MemFree:         4302276 kB
+MemAvailable:    5648444 kB
+Buffers:           68704 kB
+Cached:          1473772 kB
+SwapCached:            0 kB
+Active:           604884 kB
+Inactive:        1112392 kB
+SwapTotal:       2097148 kB
+SwapFree:        1572860 kB
+Dirty:                68 kB
+Shmem:              9176 kB
+KReclaimable:      43152 kB
+Slab:              62084 kB
+SReclaimable:      43152 kB
+SUnreclaim:        18932 kB
+HugePages_Total:       0
+HugePages_Free:        0
+Hugepagesize:       2048 kB
+";
+
+    #[test]
+    fn parse() {
+        let status = parse_status(PROC_MEMINFO.as_bytes()).unwrap();
+        assert_eq!(
+            status,
+            MemoryStatus {
+                total: 6147400 * 1024,
+                available: 5648444 * 1024,
+                free: Some(4302276 * 1024),
+                buffers_and_cache: Some((68704 + 1473772 + 43152) * 1024),
+                swap_total: 2097148 * 1024,
+                swap_available: 1572860 * 1024,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_without_mem_available() {
+        let status = parse_status(
+            "MemTotal: 1000 kB\nMemFree: 100 kB\nBuffers: 10 kB\nCached: 200 kB\n".as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(status.available, 310 * 1024);
+        assert_eq!(status.buffers_and_cache, Some(210 * 1024));
+        assert_eq!(status.swap_total, 0);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse_status("".as_bytes()).is_err());
+        assert!(parse_status("MemTotal: 1000 kB\n".as_bytes()).is_err());
+        assert!(parse_status("MemTotal: lots kB\nMemFree: 100 kB\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn read_live() {
+        let status = read_status().unwrap();
+        assert!(status.available <= status.total);
+        assert!(status.swap_available <= status.swap_total);
+    }
+}
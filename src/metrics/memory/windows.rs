@@ -0,0 +1,28 @@
+use crate::metrics::memory::MemoryStatus;
+use std::mem;
+use windows::Win32::System::SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
+use windows::core::Result;
+
+pub use windows::core::Error;
+
+pub fn read_status() -> Result<MemoryStatus> {
+    let mut mem_status = MEMORYSTATUSEX {
+        dwLength: mem::size_of::<MEMORYSTATUSEX>() as u32,
+        ..Default::default()
+    };
+    // SAFETY: `mem_status` is a valid `MEMORYSTATUSEX`
+    unsafe { GlobalMemoryStatusEx(&mut mem_status)? };
+
+    // Windows' available memory already includes the standby list (i.e. cache),
+    // and there's no cheap way to get free memory, so only the available-based definition is supported.
+    // Despite the names, the page file fields are the commit limit and available commit,
+    // which is what runs out when the page file is thrashing.
+    Ok(MemoryStatus {
+        total: mem_status.ullTotalPhys,
+        available: mem_status.ullAvailPhys,
+        free: None,
+        buffers_and_cache: None,
+        swap_total: mem_status.ullTotalPageFile,
+        swap_available: mem_status.ullAvailPageFile,
+    })
+}
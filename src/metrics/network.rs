@@ -1,98 +1,307 @@
+use crate::metrics::{Directions, MetricSource, counter_delta};
+use crate::opt::{InterfaceKind, InterfaceRule, InterfaceRules};
+use crate::utils::glob_matches;
+use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 use std::collections::HashMap;
-use std::mem;
-use std::ptr::addr_of_mut;
+use std::collections::hash_map::Entry;
+use std::fmt::{self, Display};
+use std::rc::Rc;
 use std::time::Duration;
-use windows::Win32::Foundation::WIN32_ERROR;
-use windows::Win32::NetworkManagement::IpHelper::{GetIfTable, MIB_IFROW, MIB_IFTABLE};
-use windows::core::Result;
 
-#[derive(Default)]
+#[cfg_attr(windows, path = "network/windows.rs")]
+#[cfg_attr(target_os = "linux", path = "network/linux.rs")]
+mod sys;
+
+/// Cumulative byte counts for a single network interface, and what's needed to pick which interfaces to count.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Interface {
+    /// Physical (MAC) address, zero-padded to 8 bytes and interpreted as a native-endian integer.
+    addr: u64,
+    /// Bytes received.
+    in_bytes: u64,
+    /// Bytes sent.
+    out_bytes: u64,
+    // Older recordings don't have these, so they're only matched by address rules.
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    kind: InterfaceKind,
+}
+
+/// Convert an address to the representation used by `Interface::addr`,
+/// keeping the last 8 bytes of longer addresses (e.g. InfiniBand), which are the unique part.
+fn pad_address(bytes: &[u8]) -> u64 {
+    let bytes = &bytes[bytes.len().saturating_sub(8)..];
+
+    let mut padded = [0; 8];
+    padded[..bytes.len()].copy_from_slice(bytes);
+    u64::from_ne_bytes(padded)
+}
+
+/// Parse an address like `02:fc:00:00:00:01` or `02-FC-00-00-00-01` into the representation used by `Interface::addr`.
+fn parse_address(address: &str) -> Result<u64, String> {
+    let address = address.trim();
+    let bytes = address
+        .split([':', '-'])
+        .filter(|byte| !byte.is_empty())
+        .map(|byte| {
+            u8::from_str_radix(byte, 16).map_err(|e| format!("invalid address `{address}`: {e}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(pad_address(&bytes))
+}
+
+/// Format an address from `Interface::addr` like `02:fc:00:00:00:01`.
+fn format_address(addr: u64) -> String {
+    let bytes = addr.to_ne_bytes();
+    // Assume the usual 6 bytes, unless the padding isn't all zeros.
+    let len = bytes
+        .iter()
+        .rposition(|&byte| byte != 0)
+        .map_or(0, |i| i + 1)
+        .max(6);
+
+    bytes[..len]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+impl InterfaceRule {
+    fn matches(&self, interface: &Interface) -> bool {
+        match self {
+            Self::Name(pattern) => glob_matches(pattern, &interface.name),
+            Self::Description(pattern) => glob_matches(pattern, &interface.description),
+            Self::Type(kind) => interface.kind == *kind,
+            Self::Mac(address) => parse_address(address) == Ok(interface.addr),
+        }
+    }
+}
+
+/// Whether an interface counts toward network bandwidth, and if not, why.
+#[derive(Debug, PartialEq)]
+enum Verdict<'a> {
+    Counted,
+    /// Interfaces without an address (e.g. loopback and most tunnels) can't be deduplicated,
+    /// so they're only counted if an include rule matches them.
+    NoAddress,
+    NotIncluded,
+    Excluded(&'a InterfaceRule),
+    /// Has the same address as the interface at this index, which is counted instead.
+    Duplicate(usize),
+}
+
+impl Display for Verdict<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Counted => f.write_str("counted"),
+            Self::NoAddress => f.write_str("ignored, no address and not included"),
+            Self::NotIncluded => f.write_str("ignored, not matched by any include rule"),
+            Self::Excluded(rule) => match serde_json::to_string(rule) {
+                Ok(rule) => write!(f, "ignored, excluded by {rule}"),
+                Err(_) => write!(f, "ignored, excluded by {rule:?}"),
+            },
+            Self::Duplicate(_) => f.write_str("ignored, duplicate"),
+        }
+    }
+}
+
+/// Decide which interfaces count toward network bandwidth, returning a verdict for each.
+fn classify<'a>(interfaces: &[Interface], rules: &'a InterfaceRules) -> Vec<Verdict<'a>> {
+    // Windows has many internal copies of the same interface, which results in double-counting.
+    //
+    // For example:
+    // status=INTERNAL_IF_OPER_STATUS(5) type=6 addr=[4, 217, 245, 51, 50, 182, 0, 0] bytes=2288317722 - \DEVICE\TCPIP_{438B8BC2-XXXX-XXXX-XXXX-XXXXXXXXXXXX} Realtek PCIe 2.5GbE Family Controller-WFP Native MAC Layer LightWeight Filter-0000
+    // status=INTERNAL_IF_OPER_STATUS(5) type=6 addr=[4, 217, 245, 51, 50, 182, 0, 0] bytes=2288317722 - \DEVICE\TCPIP_{8C3238C4-XXXX-XXXX-XXXX-XXXXXXXXXXXX} Realtek PCIe 2.5GbE Family Controller-Npcap Packet Driver (NPCAP)-0000
+    // status=INTERNAL_IF_OPER_STATUS(5) type=6 addr=[4, 217, 245, 51, 50, 182, 0, 0] bytes=2288317722 - \DEVICE\TCPIP_{438B8BC4-XXXX-XXXX-XXXX-XXXXXXXXXXXX} Realtek PCIe 2.5GbE Family Controller-QoS Packet Scheduler-0000
+    // status=INTERNAL_IF_OPER_STATUS(5) type=6 addr=[4, 217, 245, 51, 50, 182, 0, 0] bytes=2288317722 - \DEVICE\TCPIP_{438B8BC7-XXXX-XXXX-XXXX-XXXXXXXXXXXX} Realtek PCIe 2.5GbE Family Controller-WFP 802.3 MAC Layer LightWeight Filter-0000
+    //
+    // On Linux, the same happens with bonds and VLANs, which share the address of the underlying interface.
+    //
+    // To avoid this, deduplicate interfaces by address, after applying the rules.
+    // The first interface listed for each address consistently wins.
+
+    let mut first_with_addr = HashMap::new();
+
+    interfaces
+        .iter()
+        .enumerate()
+        .map(|(i, interface)| {
+            let included = rules.include.iter().any(|rule| rule.matches(interface));
+            if interface.addr == 0 && !included {
+                return Verdict::NoAddress;
+            }
+            if !rules.include.is_empty() && !included {
+                return Verdict::NotIncluded;
+            }
+            if let Some(rule) = rules.exclude.iter().find(|rule| rule.matches(interface)) {
+                return Verdict::Excluded(rule);
+            }
+            if interface.addr == 0 {
+                return Verdict::Counted;
+            }
+            match first_with_addr.entry(interface.addr) {
+                Entry::Occupied(first) => Verdict::Duplicate(*first.get()),
+                Entry::Vacant(entry) => {
+                    entry.insert(i);
+                    Verdict::Counted
+                }
+            }
+        })
+        .collect()
+}
+
 pub struct State {
-    prev_byte_counts: RefCell<HashMap<u64, (u32, u32)>>,
+    reader: sys::Reader,
+    /// Bytes received and sent by each interface as of the previous fetch, by address and name,
+    /// which is all that identifies interfaces without an address.
+    prev_byte_counts: RefCell<HashMap<(u64, String), (u64, u64)>>,
+    rules: Rc<RefCell<InterfaceRules>>,
+    directions: Rc<Directions>,
 }
 
 impl State {
-    pub fn fetch_mbit(&self, time_delta: Option<Duration>) -> Result<f64> {
-        /// Identical to MIB_IFTABLE but with more rows.
-        #[repr(C)]
-        struct BIG_MIB_IFTABLE {
-            dw_num_entries: u32,
-            table: [MIB_IFROW; 128],
-        }
-        const {
-            assert!(mem::align_of::<BIG_MIB_IFTABLE>() == mem::align_of::<MIB_IFTABLE>());
-            assert!(
-                mem::offset_of!(BIG_MIB_IFTABLE, dw_num_entries)
-                    == mem::offset_of!(MIB_IFTABLE, dwNumEntries)
-            );
-            assert!(mem::offset_of!(BIG_MIB_IFTABLE, table) == mem::offset_of!(MIB_IFTABLE, table));
+    pub fn new() -> Result<Self, sys::Error> {
+        Ok(Self::with_reader(sys::Reader::new()?))
+    }
+
+    fn with_reader(reader: sys::Reader) -> Self {
+        Self {
+            reader,
+            prev_byte_counts: Default::default(),
+            rules: Default::default(),
+            directions: Default::default(),
         }
+    }
 
-        // SAFETY: MIB_IFTABLE can be safely zero-initialized
-        let mut interfaces: BIG_MIB_IFTABLE = unsafe { mem::zeroed() };
-        let mut size_of_interfaces = mem::size_of_val(&interfaces).try_into().unwrap();
-
-        // SAFETY: BIG_MIB_IFTABLE is layout-compatible with MIB_IFTABLE, but with a larger table
-        unsafe {
-            WIN32_ERROR(GetIfTable(
-                Some(addr_of_mut!(interfaces).cast::<MIB_IFTABLE>()),
-                &mut size_of_interfaces,
-                false,
-            ))
-            .ok()?
-        };
+    /// Handle to the bandwidth received and sent, which is updated whenever this source is fetched.
+    pub fn directions(&self) -> Rc<Directions> {
+        Rc::clone(&self.directions)
+    }
 
-        let interfaces = &mut interfaces.table[..interfaces.dw_num_entries as usize];
+    /// Handle to the rules for which interfaces to count, which are applied whenever this source is fetched.
+    pub fn rules(&self) -> Rc<RefCell<InterfaceRules>> {
+        Rc::clone(&self.rules)
+    }
 
-        // Windows has many internal copies of the same interface, which results in double-counting.
-        //
-        // For example:
-        // status=INTERNAL_IF_OPER_STATUS(5) type=6 addr=[4, 217, 245, 51, 50, 182, 0, 0] bytes=2288317722 - \DEVICE\TCPIP_{438B8BC2-XXXX-XXXX-XXXX-XXXXXXXXXXXX} Realtek PCIe 2.5GbE Family Controller-WFP Native MAC Layer LightWeight Filter-0000
-        // status=INTERNAL_IF_OPER_STATUS(5) type=6 addr=[4, 217, 245, 51, 50, 182, 0, 0] bytes=2288317722 - \DEVICE\TCPIP_{8C3238C4-XXXX-XXXX-XXXX-XXXXXXXXXXXX} Realtek PCIe 2.5GbE Family Controller-Npcap Packet Driver (NPCAP)-0000
-        // status=INTERNAL_IF_OPER_STATUS(5) type=6 addr=[4, 217, 245, 51, 50, 182, 0, 0] bytes=2288317722 - \DEVICE\TCPIP_{438B8BC4-XXXX-XXXX-XXXX-XXXXXXXXXXXX} Realtek PCIe 2.5GbE Family Controller-QoS Packet Scheduler-0000
-        // status=INTERNAL_IF_OPER_STATUS(5) type=6 addr=[4, 217, 245, 51, 50, 182, 0, 0] bytes=2288317722 - \DEVICE\TCPIP_{438B8BC7-XXXX-XXXX-XXXX-XXXXXXXXXXXX} Realtek PCIe 2.5GbE Family Controller-WFP 802.3 MAC Layer LightWeight Filter-0000
-        //
-        // To avoid this, deduplicate interfaces by address.
+    /// Describe each interface, and whether it counts toward network bandwidth.
+    pub fn describe_interfaces(&self) -> Result<Vec<String>, sys::Error> {
+        let interfaces = self.reader.read_interfaces()?;
+        let rules = self.rules.borrow();
+        let verdicts = classify(&interfaces, &rules);
 
-        interfaces.sort_unstable_by_key(|if_row| if_row.bPhysAddr);
+        Ok(interfaces
+            .iter()
+            .zip(&verdicts)
+            .map(|(interface, verdict)| {
+                let Interface {
+                    addr,
+                    name,
+                    description,
+                    kind,
+                    ..
+                } = interface;
+                let kind = format!("{kind:?}").to_lowercase();
+                let addr = match addr {
+                    0 => "no address".to_owned(),
+                    _ => format_address(*addr),
+                };
+                let verdict = match verdict {
+                    Verdict::Duplicate(first) => {
+                        format!("{verdict} of {}", interfaces[*first].name)
+                    }
+                    _ => verdict.to_string(),
+                };
+                format!("{name} ({kind}, {addr}) \"{description}\": {verdict}")
+            })
+            .collect())
+    }
 
-        let mut prev_byte_counts = self.prev_byte_counts.borrow_mut();
-        let mut total_byte_delta = 0;
+    fn update(&self, interfaces: Vec<Interface>, time_delta: Option<Duration>) -> Option<f64> {
+        // If no time has passed, there's no bandwidth to sample,
+        // so keep the previous counts, so that these bytes are counted in the next fetch.
+        if time_delta == Some(Duration::ZERO) {
+            return None;
+        }
 
-        let mut last_addr = 0;
-        for if_row in interfaces {
-            let addr = u64::from_ne_bytes(if_row.bPhysAddr);
-            if addr == last_addr {
-                // Duplicate entry, ignore.
-                continue;
-            }
-            last_addr = addr;
+        let rules = self.rules.borrow();
+        let verdicts = classify(&interfaces, &rules);
 
-            let in_bytes = if_row.dwInOctets;
-            let out_bytes = if_row.dwOutOctets;
+        // Store the counts of every interface, whatever its verdict, so that an interface which becomes counted
+        // (e.g. after a rule change) is compared to the previous fetch, not to whenever it was last counted.
+        let prev_byte_counts = self.prev_byte_counts.take();
+        let mut byte_counts = HashMap::with_capacity(interfaces.len());
+        // Summed as floats, since deltas can be up to half of `u64::MAX`, e.g. in a corrupted recording.
+        let mut in_byte_delta = 0.0;
+        let mut out_byte_delta = 0.0;
 
-            // Compute delta if this interface has been seen before; otherwise just store the current counts
-            if let Some((prev_in_bytes, prev_out_bytes)) =
-                prev_byte_counts.insert(addr, (in_bytes, out_bytes))
-            {
-                let in_byte_delta = in_bytes.wrapping_sub(prev_in_bytes);
-                let out_byte_delta = out_bytes.wrapping_sub(prev_out_bytes);
+        for (interface, verdict) in interfaces.into_iter().zip(verdicts) {
+            let in_bytes = interface.in_bytes;
+            let out_bytes = interface.out_bytes;
 
-                total_byte_delta += u64::from(in_byte_delta) + u64::from(out_byte_delta);
+            // Interfaces which share an address (e.g. VLANs) have their own counts, so they're also keyed by name.
+            let Entry::Vacant(entry) = byte_counts.entry((interface.addr, interface.name)) else {
+                // Older recordings don't have names, and the first interface with each address is the one counted.
+                continue;
+            };
+            let prev = prev_byte_counts.get(entry.key()).copied();
+            entry.insert((in_bytes, out_bytes));
+
+            if verdict != Verdict::Counted {
+                continue;
+            }
+
+            // Compute delta if this interface has been seen before; otherwise just store the current counts.
+            // Counts are 64-bit on all platforms, so they only go backwards if the interface was re-created.
+            if let Some((prev_in_bytes, prev_out_bytes)) = prev {
+                in_byte_delta += counter_delta(prev_in_bytes, in_bytes, u64::MAX) as f64;
+                out_byte_delta += counter_delta(prev_out_bytes, out_bytes, u64::MAX) as f64;
             }
         }
 
+        *self.prev_byte_counts.borrow_mut() = byte_counts;
+
         // On first sample, just return zero.
-        let mbit = match time_delta {
+        let to_mbit = |byte_delta: f64| match time_delta {
             Some(time_delta) => {
-                let bits_per_byte = 8;
-                let bits = total_byte_delta * bits_per_byte;
-                (bits as f64) / 1_000_000.0 / time_delta.as_secs_f64()
+                let bits_per_byte = 8.0;
+                let bits = byte_delta * bits_per_byte;
+                bits / 1_000_000.0 / time_delta.as_secs_f64()
             }
             None => 0.0,
         };
 
-        Ok(mbit)
+        self.directions
+            .push(to_mbit(in_byte_delta), to_mbit(out_byte_delta));
+
+        Some(to_mbit(in_byte_delta + out_byte_delta))
+    }
+}
+
+impl MetricSource for State {
+    type Raw = Vec<Interface>;
+    /// Bandwidth, or `None` if no time has passed since the previous fetch.
+    type Sample = Option<f64>;
+    type Error = sys::Error;
+
+    fn name(&self) -> &str {
+        "network"
+    }
+
+    fn unit(&self) -> &str {
+        "Mb/s"
+    }
+
+    fn read(&self) -> Result<Vec<Interface>, sys::Error> {
+        self.reader.read_interfaces()
+    }
+
+    fn convert(&self, interfaces: Vec<Interface>, time_delta: Option<Duration>) -> Option<f64> {
+        self.update(interfaces, time_delta)
     }
 }
@@ -0,0 +1,566 @@
+use crate::metrics::network::{Interface, parse_address};
+use crate::opt::InterfaceKind;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+pub use std::io::Error;
+
+const PROC_NET_DEV_PATH: &str = "/proc/net/dev";
+const SYS_CLASS_NET_PATH: &str = "/sys/class/net";
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/if_arp.h
+const ARPHRD_ETHER: u16 = 1;
+const ARPHRD_LOOPBACK: u16 = 772;
+/// Used by layer 3 tunnels without a link layer, e.g. `tun` and WireGuard.
+const ARPHRD_NONE: u16 = 65534;
+const ARPHRD_TUNNELS: &[u16] = &[
+    768, // ARPHRD_TUNNEL
+    769, // ARPHRD_TUNNEL6
+    776, // ARPHRD_SIT
+    778, // ARPHRD_IPGRE
+    823, // ARPHRD_IP6GRE
+    ARPHRD_NONE,
+];
+
+pub struct Reader {
+    proc_net_dev: PathBuf,
+    sys_class_net: PathBuf,
+}
+
+impl Reader {
+    pub fn new() -> io::Result<Self> {
+        Ok(Self::with_paths(PROC_NET_DEV_PATH, SYS_CLASS_NET_PATH))
+    }
+
+    pub fn with_paths(proc_net_dev: impl Into<PathBuf>, sys_class_net: impl Into<PathBuf>) -> Self {
+        Self {
+            proc_net_dev: proc_net_dev.into(),
+            sys_class_net: sys_class_net.into(),
+        }
+    }
+
+    pub fn read_interfaces(&self) -> io::Result<Vec<Interface>> {
+        let counts = parse_byte_counts(BufReader::new(File::open(&self.proc_net_dev)?))?;
+
+        let mut interfaces = Vec::with_capacity(counts.len());
+        for (name, in_bytes, out_bytes) in counts {
+            let dir = self.sys_class_net.join(&name);
+
+            let addr = match fs::read_to_string(dir.join("address")) {
+                Ok(address) => parse_address(&address)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+                // The interface was removed after we read its counts.
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+
+            // Only used for matching rules, so if they can't be read, the interface just won't match.
+            let description = fs::read_to_string(dir.join("ifalias")).unwrap_or_default();
+            let kind = read_kind(&dir);
+
+            interfaces.push(Interface {
+                addr,
+                in_bytes,
+                out_bytes,
+                name,
+                description: description.trim().to_owned(),
+                kind,
+            });
+        }
+
+        Ok(interfaces)
+    }
+}
+
+/// Determine the kind of interface from its sysfs directory.
+fn read_kind(dir: &Path) -> InterfaceKind {
+    // TAP devices are tunnels, even though they look like Ethernet.
+    if dir.join("tun_flags").exists() {
+        return InterfaceKind::Tunnel;
+    }
+    if dir.join("bridge").is_dir() {
+        return InterfaceKind::Bridge;
+    }
+
+    let Ok(link_type) = fs::read_to_string(dir.join("type")) else {
+        return InterfaceKind::Other;
+    };
+
+    match link_type.trim().parse::<u16>() {
+        Ok(ARPHRD_ETHER) if dir.join("wireless").is_dir() || dir.join("phy80211").exists() => {
+            InterfaceKind::Wifi
+        }
+        Ok(ARPHRD_ETHER) => InterfaceKind::Ethernet,
+        Ok(ARPHRD_LOOPBACK) => InterfaceKind::Loopback,
+        Ok(link_type) if ARPHRD_TUNNELS.contains(&link_type) => InterfaceKind::Tunnel,
+        _ => InterfaceKind::Other,
+    }
+}
+
+/// Parse `/proc/net/dev`, which looks like:
+///
+/// ```text
+/// Inter-|   Receive                                                |  Transmit
+///  face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+///     lo: 16933260    2075    0    0    0     0          0         0 16933260    2075    0    0    0     0       0          0
+///   eth0:    3366      51    0    0    0     0          0         0     4532      51    0    0    0     0       0          0
+/// ```
+///
+/// Returns the name, bytes received, and bytes sent for each interface.
+fn parse_byte_counts(reader: impl BufRead) -> io::Result<Vec<(String, u64, u64)>> {
+    let mut counts = Vec::new();
+
+    // Skip the two header lines.
+    for line in reader.lines().skip(2) {
+        let line = line?;
+        let invalid = |msg: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{msg} in net/dev line `{line}`"),
+            )
+        };
+
+        let Some((name, values)) = line.split_once(':') else {
+            return Err(invalid("missing interface name"));
+        };
+
+        let values = values.split_ascii_whitespace().collect::<Vec<_>>();
+        let &[in_bytes, _, _, _, _, _, _, _, out_bytes, ..] = values.as_slice() else {
+            return Err(invalid("too few fields"));
+        };
+
+        let parse = |value: &str| {
+            value
+                .parse::<u64>()
+                .map_err(|_| invalid("invalid byte count"))
+        };
+
+        counts.push((name.trim().to_owned(), parse(in_bytes)?, parse(out_bytes)?));
+    }
+
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::EXPONENTIAL_DECAY_ALPHA;
+    use crate::fixture::Fixture;
+    use crate::metrics::MetricSource;
+    use crate::metrics::network::State;
+    use crate::opt::{InterfaceRule, InterfaceRules};
+    use std::time::Duration;
+
+    const PROC_NET_DEV_0: &str = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo: 16933260    2075    0    0    0     0          0         0 16933260    2075    0    0    0     0       0          0
+  eth0: 1000000    5100    0    0    0     0          0         0   200000    3100    0    0    0     0       0          0
+bond0.10:  500000    2000    0    0    0     0          0         0   100000    1000    0    0    0     0       0          0
+ wlan0:  300000     400    0    0    0     0          0         0    40000     200    0    0    0     0       0          0
+docker0:  700000    1000    0    0    0     0          0         0   700000    1000    0    0    0     0       0          0
+vethab12cd:  700000    1000    0    0    0     0          0         0   700000    1000    0    0    0     0       0          0
+  br0:   10000      10    0    0    0     0          0         0    10000      10    0    0    0     0       0          0
+   wg0:   80000     100    0    0    0     0          0         0     8000     100    0    0    0     0       0          0
+  tap0:   60000      60    0    0    0     0          0         0     6000      60    0    0    0     0       0          0
+";
+
+    const PROC_NET_DEV_1: &str = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo: 26933260    3075    0    0    0     0          0         0 26933260    3075    0    0    0     0       0          0
+  eth0: 1250000    5300    0    0    0     0          0         0   300000    3200    0    0    0     0       0          0
+bond0.10:  600000    2100    0    0    0     0          0         0   150000    1100    0    0    0     0       0          0
+ wlan0:  400000     500    0    0    0     0          0         0    50000     300    0    0    0     0       0          0
+docker0:  900000    1200    0    0    0     0          0         0   900000    1200    0    0    0     0       0          0
+vethab12cd:  900000    1200    0    0    0     0          0         0   900000    1200    0    0    0     0       0          0
+  br0:   20000      20    0    0    0     0          0         0    20000      20    0    0    0     0       0          0
+   wg0:   90000     110    0    0    0     0          0         0     9000     110    0    0    0     0       0          0
+  tap0:  160000     160    0    0    0     0          0         0    16000     160    0    0    0     0       0          0
+usb0:    5000000      10    0    0    0     0          0         0  5000000      10    0    0    0     0       0          0
+";
+
+    fn sys_class_net() -> Fixture {
+        let fixture = Fixture::new();
+        fixture
+            .file("net/lo/address", "00:00:00:00:00:00\n")
+            .file("net/lo/type", "772\n")
+            .file("net/eth0/address", "04:d9:f5:33:32:b6\n")
+            .file("net/eth0/type", "1\n")
+            // VLAN on a bond of eth0, so it shares its address
+            .file("net/bond0.10/address", "04:d9:f5:33:32:b6\n")
+            .file("net/bond0.10/type", "1\n")
+            .file("net/wlan0/address", "a4:c3:f0:11:22:33\n")
+            .file("net/wlan0/type", "1\n")
+            .file("net/wlan0/ifalias", "Laptop Wi-Fi\n")
+            .dir("net/wlan0/wireless")
+            .file("net/docker0/address", "02:42:ac:11:00:01\n")
+            .dir("net/docker0/bridge")
+            .file("net/vethab12cd/address", "9a:1b:2c:3d:4e:5f\n")
+            .file("net/br0/address", "52:54:00:12:34:56\n")
+            .dir("net/br0/bridge")
+            // Tunnels don't have an address
+            .file("net/wg0/address", "\n")
+            .file("net/wg0/type", "65534\n")
+            // OpenVPN in TAP mode
+            .file("net/tap0/address", "ce:5f:01:02:03:04\n")
+            .file("net/tap0/type", "1\n")
+            .file("net/tap0/tun_flags", "0x1002\n")
+            .file("net/usb0/address", "02:00:00:00:00:99\n");
+        fixture
+    }
+
+    #[test]
+    fn parse_counts() {
+        let counts = parse_byte_counts(PROC_NET_DEV_0.as_bytes()).unwrap();
+        assert_eq!(counts.len(), 9);
+        assert_eq!(counts[0], ("lo".to_owned(), 16933260, 16933260));
+        assert_eq!(counts[1], ("eth0".to_owned(), 1000000, 200000));
+        assert_eq!(counts[2], ("bond0.10".to_owned(), 500000, 100000));
+    }
+
+    #[test]
+    fn parse_counts_errors() {
+        let header = PROC_NET_DEV_0
+            .lines()
+            .take(2)
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert!(parse_byte_counts(format!("{header}\n  eth0 1 2 3\n").as_bytes()).is_err());
+        assert!(parse_byte_counts(format!("{header}\n  eth0: 1 2 3\n").as_bytes()).is_err());
+        assert!(
+            parse_byte_counts(format!("{header}\n  eth0: 1 2 3 4 5 6 7 8 x 10\n").as_bytes())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn parse_addresses() {
+        assert_eq!(
+            parse_address("04:d9:f5:33:32:b6\n").unwrap(),
+            u64::from_ne_bytes([4, 217, 245, 51, 50, 182, 0, 0])
+        );
+        assert_eq!(parse_address("\n").unwrap(), 0);
+        assert_eq!(parse_address("00:00:00:00:00:00\n").unwrap(), 0);
+        assert_eq!(
+            parse_address("80:00:02:08:fe:80:00:00:00:00:00:00:00:02:c9:03:00:0a:7b:51\n").unwrap(),
+            u64::from_ne_bytes([0x00, 0x02, 0xc9, 0x03, 0x00, 0x0a, 0x7b, 0x51])
+        );
+        assert!(parse_address("04:d9:zz:33:32:b6\n").is_err());
+    }
+
+    #[test]
+    fn reads_every_interface() {
+        let fixture = sys_class_net();
+        fixture.file("net_dev", PROC_NET_DEV_0);
+        let reader = Reader::with_paths(fixture.path("net_dev"), fixture.path("net"));
+
+        let interfaces = reader.read_interfaces().unwrap();
+        let in_bytes = interfaces
+            .iter()
+            .map(|interface| interface.in_bytes)
+            .collect::<Vec<_>>();
+        // lo, eth0, bond0.10, wlan0, docker0, vethab12cd, br0, wg0, tap0
+        assert_eq!(
+            in_bytes,
+            [
+                16933260, 1000000, 500000, 300000, 700000, 700000, 10000, 80000, 60000
+            ]
+        );
+
+        let kinds = interfaces
+            .iter()
+            .map(|interface| interface.kind)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                InterfaceKind::Loopback,
+                InterfaceKind::Ethernet,
+                InterfaceKind::Ethernet,
+                InterfaceKind::Wifi,
+                InterfaceKind::Bridge,
+                InterfaceKind::Other,
+                InterfaceKind::Bridge,
+                InterfaceKind::Tunnel,
+                InterfaceKind::Tunnel,
+            ]
+        );
+        assert_eq!(interfaces[3].name, "wlan0");
+        assert_eq!(interfaces[3].description, "Laptop Wi-Fi");
+    }
+
+    #[test]
+    fn describe_interfaces() {
+        let fixture = sys_class_net();
+        fixture.file("net_dev", PROC_NET_DEV_0);
+        let state = State::with_reader(Reader::with_paths(
+            fixture.path("net_dev"),
+            fixture.path("net"),
+        ));
+
+        assert_eq!(
+            state.describe_interfaces().unwrap(),
+            [
+                "lo (loopback, no address) \"\": ignored, no address and not included",
+                "eth0 (ethernet, 04:d9:f5:33:32:b6) \"\": counted",
+                "bond0.10 (ethernet, 04:d9:f5:33:32:b6) \"\": ignored, duplicate of eth0",
+                "wlan0 (wifi, a4:c3:f0:11:22:33) \"Laptop Wi-Fi\": counted",
+                "docker0 (bridge, 02:42:ac:11:00:01) \"\": ignored, excluded by {\"type\":\"bridge\"}",
+                "vethab12cd (other, 9a:1b:2c:3d:4e:5f) \"\": ignored, excluded by {\"name\":\"veth*\"}",
+                "br0 (bridge, 52:54:00:12:34:56) \"\": ignored, excluded by {\"type\":\"bridge\"}",
+                "wg0 (tunnel, no address) \"\": ignored, no address and not included",
+                "tap0 (tunnel, ce:5f:01:02:03:04) \"\": ignored, excluded by {\"type\":\"tunnel\"}",
+            ]
+        );
+
+        // The default exclusions are rules, so they can be replaced.
+        *state.rules().borrow_mut() = InterfaceRules {
+            include: Vec::new(),
+            exclude: vec![InterfaceRule::Type(InterfaceKind::Tunnel)],
+        };
+        let described = state.describe_interfaces().unwrap();
+        assert_eq!(
+            described[4],
+            "docker0 (bridge, 02:42:ac:11:00:01) \"\": counted"
+        );
+        assert_eq!(
+            described[5],
+            "vethab12cd (other, 9a:1b:2c:3d:4e:5f) \"\": counted"
+        );
+    }
+
+    #[test]
+    fn interface_rules() {
+        let fixture = sys_class_net();
+        let state = State::with_reader(Reader::with_paths(
+            fixture.path("net_dev"),
+            fixture.path("net"),
+        ));
+        *state.rules().borrow_mut() = InterfaceRules {
+            include: vec![
+                InterfaceRule::Type(InterfaceKind::Ethernet),
+                InterfaceRule::Description("*wi-fi".to_owned()),
+            ],
+            exclude: vec![InterfaceRule::Name("eth?".to_owned())],
+        };
+
+        fixture.file("net_dev", PROC_NET_DEV_0);
+        state.fetch(None).unwrap();
+
+        fixture.file("net_dev", PROC_NET_DEV_1);
+        let mbit = state.fetch(Some(Duration::from_secs(2))).unwrap();
+
+        // eth0 is excluded, so bond0.10 is no longer a duplicate: 100000 in + 50000 out
+        // wlan0: 100000 in + 10000 out
+        // tap0: not included
+        let bytes = 100000 + 50000 + 100000 + 10000;
+        assert_eq!(mbit, Some((bytes * 8) as f64 / 1_000_000.0 / 2.0));
+
+        *state.rules().borrow_mut() = InterfaceRules {
+            include: Vec::new(),
+            exclude: vec![InterfaceRule::Mac("A4-C3-F0-11-22-33".to_owned())],
+        };
+        assert_eq!(
+            state.describe_interfaces().unwrap()[3],
+            "wlan0 (wifi, a4:c3:f0:11:22:33) \"Laptop Wi-Fi\": ignored, excluded by {\"mac\":\"A4-C3-F0-11-22-33\"}"
+        );
+    }
+
+    #[test]
+    fn bandwidth_between_snapshots() {
+        let fixture = sys_class_net();
+        let state = State::with_reader(Reader::with_paths(
+            fixture.path("net_dev"),
+            fixture.path("net"),
+        ));
+
+        fixture.file("net_dev", PROC_NET_DEV_0);
+        assert_eq!(state.fetch(None).unwrap(), Some(0.0));
+
+        fixture.file("net_dev", PROC_NET_DEV_1);
+        let mbit = state.fetch(Some(Duration::from_secs(2))).unwrap();
+
+        // eth0 (deduplicated with bond0.10): 250000 in + 100000 out
+        // wlan0: 100000 in + 10000 out
+        // wg0: no address, ignored
+        // tap0: tunnel, excluded by default
+        // usb0: newly added, so no delta yet
+        let bytes = 250000 + 100000 + 100000 + 10000;
+        assert_eq!(mbit, Some((bytes * 8) as f64 / 1_000_000.0 / 2.0));
+
+        // The first sample of each direction is zero, like the total.
+        let directions = state.directions();
+        let expected = |bytes: u64| {
+            let latest = (bytes * 8) as f64 / 1_000_000.0 / 2.0;
+            latest + EXPONENTIAL_DECAY_ALPHA * (0.0 - latest)
+        };
+        assert_eq!(directions.average_incoming(), expected(250000 + 100000));
+        assert_eq!(directions.average_outgoing(), expected(100000 + 10000));
+    }
+
+    #[test]
+    fn bandwidth_over_long_and_short_intervals() {
+        let state = State::with_reader(Reader::with_paths("unused", "unused"));
+        let interfaces = |in_bytes: u64| {
+            vec![Interface {
+                addr: 1,
+                in_bytes,
+                out_bytes: 0,
+                name: "eth0".to_owned(),
+                description: String::new(),
+                kind: InterfaceKind::Ethernet,
+            }]
+        };
+
+        state.convert(interfaces(0), None);
+
+        // e.g. the fetch timer being stopped while the session is locked
+        let after_pause =
+            state.convert(interfaces(450_000_000), Some(Duration::from_secs(60 * 60)));
+        assert_eq!(after_pause, Some(1.0));
+
+        let sub_millisecond =
+            state.convert(interfaces(450_000_125), Some(Duration::from_micros(100)));
+        assert_eq!(sub_millisecond, Some(10.0));
+
+        // No time passed, so there's no bandwidth to sample.
+        let no_time = state.convert(interfaces(450_000_250), Some(Duration::ZERO));
+        assert_eq!(no_time, None);
+
+        // The bytes from the zero-length interval are counted in the next fetch.
+        let next = state.convert(interfaces(450_250_125), Some(Duration::from_secs(1)));
+        assert_eq!(next, Some(2.0));
+    }
+
+    #[test]
+    fn interface_recreated() {
+        let state = State::with_reader(Reader::with_paths("unused", "unused"));
+        let interfaces = |in_bytes: u64| {
+            vec![Interface {
+                addr: 1,
+                in_bytes,
+                out_bytes: 0,
+                name: "eth0".to_owned(),
+                description: String::new(),
+                kind: InterfaceKind::Ethernet,
+            }]
+        };
+
+        state.convert(interfaces(5_000_000_000), None);
+
+        // The counts restarted from zero, which isn't a wrap of a 64-bit counter.
+        let recreated = state.convert(interfaces(1_000), Some(Duration::from_secs(1)));
+        assert_eq!(recreated, Some(0.0));
+
+        // Past 32 bits, which would have wrapped a `MIB_IFROW` counter.
+        let next = state.convert(interfaces(4_500_001_000), Some(Duration::from_secs(36)));
+        assert_eq!(next, Some(1000.0));
+    }
+
+    #[test]
+    fn tunnels_included_by_name() {
+        let fixture = sys_class_net();
+        let state = State::with_reader(Reader::with_paths(
+            fixture.path("net_dev"),
+            fixture.path("net"),
+        ));
+        *state.rules().borrow_mut() = InterfaceRules {
+            include: vec![InterfaceRule::Name("wg0".to_owned())],
+            exclude: Vec::new(),
+        };
+
+        fixture.file("net_dev", PROC_NET_DEV_0);
+        assert_eq!(
+            state.describe_interfaces().unwrap()[7],
+            "wg0 (tunnel, no address) \"\": counted"
+        );
+        state.fetch(None).unwrap();
+
+        fixture.file("net_dev", PROC_NET_DEV_1);
+        let mbit = state.fetch(Some(Duration::from_secs(2))).unwrap();
+
+        // wg0: 10000 in + 1000 out
+        let bytes = 10000 + 1000;
+        assert_eq!(mbit, Some((bytes * 8) as f64 / 1_000_000.0 / 2.0));
+    }
+
+    #[test]
+    fn rules_changed_between_fetches() {
+        let state = State::with_reader(Reader::with_paths("unused", "unused"));
+        let interfaces = |eth0_bytes: u64, eth1_bytes: u64| {
+            [(1, eth0_bytes), (2, eth1_bytes)]
+                .map(|(addr, in_bytes)| Interface {
+                    addr,
+                    in_bytes,
+                    out_bytes: 0,
+                    name: format!("eth{}", addr - 1),
+                    description: String::new(),
+                    kind: InterfaceKind::Ethernet,
+                })
+                .to_vec()
+        };
+        let set_exclude = |exclude: Vec<InterfaceRule>| {
+            *state.rules().borrow_mut() = InterfaceRules {
+                include: Vec::new(),
+                exclude,
+            };
+        };
+
+        state.convert(interfaces(0, 0), None);
+
+        set_exclude(vec![InterfaceRule::Name("eth1".to_owned())]);
+        let excluded = state.convert(
+            interfaces(125_000, 125_000_000),
+            Some(Duration::from_secs(1)),
+        );
+        assert_eq!(excluded, Some(1.0));
+
+        // eth1 is only compared to the previous fetch, not to when it was last counted.
+        set_exclude(Vec::new());
+        let included = state.convert(
+            interfaces(250_000, 125_125_000),
+            Some(Duration::from_secs(1)),
+        );
+        assert_eq!(included, Some(2.0));
+    }
+
+    #[test]
+    fn huge_jumps_do_not_overflow() {
+        let state = State::with_reader(Reader::with_paths("unused", "unused"));
+        let interfaces = |bytes: u64| {
+            [1, 2]
+                .map(|addr| Interface {
+                    addr,
+                    in_bytes: bytes,
+                    out_bytes: bytes,
+                    name: format!("eth{addr}"),
+                    description: String::new(),
+                    kind: InterfaceKind::Ethernet,
+                })
+                .to_vec()
+        };
+
+        // e.g. a corrupted or hand-edited recording
+        state.convert(interfaces(0), None);
+        let mbit = state.convert(interfaces(u64::MAX), Some(Duration::from_secs(1)));
+        assert_eq!(mbit, Some(4.0 * u64::MAX as f64 * 8.0 / 1_000_000.0));
+    }
+
+    #[test]
+    fn interface_removed_between_reads() {
+        let fixture = sys_class_net();
+        fixture.file("net_dev", PROC_NET_DEV_1);
+        // usb0 is listed in net/dev, but was unplugged before we read its address
+        fs::remove_dir_all(fixture.path("net/usb0")).unwrap();
+        let reader = Reader::with_paths(fixture.path("net_dev"), fixture.path("net"));
+
+        assert_eq!(reader.read_interfaces().unwrap().len(), 9);
+    }
+
+    #[test]
+    fn read_live() {
+        Reader::new().unwrap().read_interfaces().unwrap();
+    }
+}
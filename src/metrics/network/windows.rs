@@ -0,0 +1,66 @@
+use crate::metrics::network::{Interface, pad_address};
+use crate::opt::InterfaceKind;
+use std::slice;
+use windows::Win32::NetworkManagement::IpHelper::{
+    FreeMibTable, GetIfTable2, IF_TYPE_ETHERNET_CSMACD, IF_TYPE_IEEE80211, IF_TYPE_PROP_VIRTUAL,
+    IF_TYPE_SOFTWARE_LOOPBACK, IF_TYPE_TUNNEL, MIB_IF_TABLE2,
+};
+use windows::core::Result;
+
+pub use windows::core::Error;
+
+pub struct Reader;
+
+impl Reader {
+    pub fn new() -> Result<Self> {
+        Ok(Self)
+    }
+
+    /// Read the byte counts of every interface.
+    ///
+    /// Uses `MIB_IF_ROW2` instead of `MIB_IFROW`, since the latter's 32-bit octet counts can wrap more than once
+    /// between fetches on fast links, e.g. after resuming from sleep.
+    pub fn read_interfaces(&self) -> Result<Vec<Interface>> {
+        let mut table: *mut MIB_IF_TABLE2 = std::ptr::null_mut();
+
+        // SAFETY: on success, `table` is set to a table which must be freed with `FreeMibTable`
+        unsafe { GetIfTable2(&mut table).ok()? };
+
+        // SAFETY: GetIfTable2 succeeded, so `table` points to `NumEntries` rows
+        let rows =
+            unsafe { slice::from_raw_parts((*table).Table.as_ptr(), (*table).NumEntries as usize) };
+
+        let interfaces = rows
+            .iter()
+            .map(|if_row| {
+                let len = (if_row.PhysicalAddressLength as usize).min(if_row.PhysicalAddress.len());
+                Interface {
+                    addr: pad_address(&if_row.PhysicalAddress[..len]),
+                    in_bytes: if_row.InOctets,
+                    out_bytes: if_row.OutOctets,
+                    name: from_wide(&if_row.Alias),
+                    description: from_wide(&if_row.Description),
+                    kind: match if_row.Type {
+                        IF_TYPE_ETHERNET_CSMACD => InterfaceKind::Ethernet,
+                        IF_TYPE_IEEE80211 => InterfaceKind::Wifi,
+                        // Used by e.g. WireGuard and other VPNs built on Wintun.
+                        IF_TYPE_TUNNEL | IF_TYPE_PROP_VIRTUAL => InterfaceKind::Tunnel,
+                        IF_TYPE_SOFTWARE_LOOPBACK => InterfaceKind::Loopback,
+                        _ => InterfaceKind::Other,
+                    },
+                }
+            })
+            .collect();
+
+        // SAFETY: `table` was allocated by GetIfTable2, and `rows` is no longer used
+        unsafe { FreeMibTable(table.cast()) };
+
+        Ok(interfaces)
+    }
+}
+
+/// Convert a null-terminated UTF-16 buffer to a string.
+fn from_wide(buf: &[u16]) -> String {
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    String::from_utf16_lossy(&buf[..len])
+}
@@ -0,0 +1,72 @@
+use crate::metrics::MetricSource;
+use crate::opt::PressureSeries;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[cfg_attr(windows, path = "pressure/windows.rs")]
+#[cfg_attr(target_os = "linux", path = "pressure/linux.rs")]
+mod sys;
+
+/// Percentage of time in which tasks were stalled waiting for a resource, averaged over the last 10 seconds.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Stall {
+    /// At least one task was stalled.
+    pub some: f64,
+    /// Every non-idle task was stalled at once, i.e. the machine did no work at all.
+    pub full: f64,
+}
+
+/// Pressure stall information for each resource.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Pressures {
+    pub cpu: Stall,
+    pub memory: Stall,
+    pub io: Stall,
+}
+
+impl Pressures {
+    pub fn get(&self, series: PressureSeries) -> f64 {
+        match series {
+            PressureSeries::CpuSome => self.cpu.some,
+            PressureSeries::CpuFull => self.cpu.full,
+            PressureSeries::MemorySome => self.memory.some,
+            PressureSeries::MemoryFull => self.memory.full,
+            PressureSeries::IoSome => self.io.some,
+            PressureSeries::IoFull => self.io.full,
+        }
+    }
+}
+
+pub struct State {
+    reader: sys::Reader,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self {
+            reader: sys::Reader::new(),
+        }
+    }
+}
+
+impl MetricSource for State {
+    type Raw = Option<Pressures>;
+    type Sample = Option<Pressures>;
+    type Error = sys::Error;
+
+    fn name(&self) -> &str {
+        "pressure"
+    }
+
+    fn read(&self) -> Result<Option<Pressures>, sys::Error> {
+        self.reader.read_pressures()
+    }
+
+    fn convert(
+        &self,
+        pressures: Option<Pressures>,
+        _time_delta: Option<Duration>,
+    ) -> Option<Pressures> {
+        pressures
+    }
+}
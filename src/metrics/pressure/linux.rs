@@ -0,0 +1,175 @@
+use crate::metrics::pressure::{Pressures, Stall};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub use std::io::Error;
+
+const PROC_PRESSURE_PATH: &str = "/proc/pressure";
+
+pub struct Reader {
+    proc_pressure: PathBuf,
+}
+
+impl Reader {
+    pub fn new() -> Self {
+        Self::with_path(PROC_PRESSURE_PATH)
+    }
+
+    pub fn with_path(proc_pressure: impl Into<PathBuf>) -> Self {
+        Self {
+            proc_pressure: proc_pressure.into(),
+        }
+    }
+
+    /// Read `/proc/pressure/{cpu,memory,io}`, or `None` if the kernel doesn't track pressure.
+    ///
+    /// https://docs.kernel.org/accounting/psi.html
+    pub fn read_pressures(&self) -> io::Result<Option<Pressures>> {
+        match self.read_each_resource() {
+            Ok(pressures) => Ok(Some(pressures)),
+            // Missing if the kernel was built without `CONFIG_PSI`, and unsupported if booted with `psi=0`.
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::NotFound | io::ErrorKind::Unsupported
+                ) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn read_each_resource(&self) -> io::Result<Pressures> {
+        let read = |resource| read_stall(&self.proc_pressure.join(resource));
+        Ok(Pressures {
+            cpu: read("cpu")?,
+            memory: read("memory")?,
+            io: read("io")?,
+        })
+    }
+}
+
+fn read_stall(path: &Path) -> io::Result<Stall> {
+    parse_stall(&fs::read_to_string(path)?)
+}
+
+/// Parse a pressure file, which looks like:
+///
+/// ```text
+/// some avg10=0.00 avg60=0.00 avg300=0.00 total=0
+/// full avg10=0.00 avg60=0.00 avg300=0.00 total=0
+/// ```
+///
+/// Kernels before 5.13 don't have a `full` line for `cpu`, which is always zero anyway.
+fn parse_stall(contents: &str) -> io::Result<Stall> {
+    let mut stall = Stall::default();
+    let mut found_some = false;
+    for line in contents.lines() {
+        let Some((kind, fields)) = line.split_once(' ') else {
+            continue;
+        };
+        let avg10 = fields
+            .split_ascii_whitespace()
+            .find_map(|field| field.strip_prefix("avg10="))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing avg10"))?
+            .parse::<f64>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        match kind {
+            "some" => {
+                stall.some = avg10;
+                found_some = true;
+            }
+            "full" => stall.full = avg10,
+            _ => {}
+        }
+    }
+    if !found_some {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "no some line in pressure",
+        ));
+    }
+    Ok(stall)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::Fixture;
+
+    #[test]
+    fn reads_each_resource() {
+        let fixture = Fixture::new();
+        // No `full` line, as before 5.13
+        fixture.file(
+            "cpu",
+            "some avg10=12.50 avg60=3.20 avg300=1.00 total=123456\n",
+        );
+        fixture.file(
+            "memory",
+            "some avg10=4.25 avg60=1.00 avg300=0.20 total=9999\nfull avg10=1.75 avg60=0.50 avg300=0.10 total=5555\n",
+        );
+        fixture.file(
+            "io",
+            "some avg10=30.00 avg60=10.00 avg300=2.00 total=1\nfull avg10=20.00 avg60=5.00 avg300=1.00 total=1\n",
+        );
+
+        assert_eq!(
+            Reader::with_path(fixture.path(""))
+                .read_pressures()
+                .unwrap(),
+            Some(Pressures {
+                cpu: Stall {
+                    some: 12.5,
+                    full: 0.0,
+                },
+                memory: Stall {
+                    some: 4.25,
+                    full: 1.75,
+                },
+                io: Stall {
+                    some: 30.0,
+                    full: 20.0,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn not_tracked() {
+        let fixture = Fixture::new();
+        assert_eq!(
+            Reader::with_path(fixture.path("missing"))
+                .read_pressures()
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        let fixture = Fixture::new();
+        fixture.file("cpu", "some avg10=x\n");
+        fixture.file("memory", "some avg10=0.00\n");
+        fixture.file("io", "some avg10=0.00\n");
+        assert!(
+            Reader::with_path(fixture.path(""))
+                .read_pressures()
+                .is_err()
+        );
+
+        fixture.file("cpu", "\n");
+        assert!(
+            Reader::with_path(fixture.path(""))
+                .read_pressures()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn read_live() {
+        Reader::new().read_pressures().unwrap();
+    }
+}
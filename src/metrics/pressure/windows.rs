@@ -0,0 +1,17 @@
+use crate::metrics::pressure::Pressures;
+use windows::core::Result;
+
+pub use windows::core::Error;
+
+pub struct Reader;
+
+impl Reader {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Windows doesn't track pressure stalls, so this is always `None`.
+    pub fn read_pressures(&self) -> Result<Option<Pressures>> {
+        Ok(None)
+    }
+}
@@ -0,0 +1,307 @@
+use crate::metrics::MetricSource;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+#[cfg_attr(windows, path = "processes/windows.rs")]
+#[cfg_attr(target_os = "linux", path = "processes/linux.rs")]
+mod sys;
+
+const BYTES_PER_MEGABYTE: f64 = 1_000_000.0;
+
+/// Identifies a process across fetches, even once its pid has been reused.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ProcessId {
+    pid: u32,
+    /// When the process started, in clock ticks since boot, or zero where that isn't available (on Windows).
+    start_time: u64,
+}
+
+/// Cumulative usage of a single process.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProcessCounters {
+    id: ProcessId,
+    name: String,
+    /// Time spent running in user and kernel mode, in seconds.
+    cpu_seconds: f64,
+    /// Resident memory, in bytes.
+    working_set: u64,
+    /// Bytes read and written, including files, the network, and devices.
+    io_bytes: u64,
+}
+
+/// Usage of all processes with the same name, e.g. every `chrome` process, since the previous fetch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProcessUsage {
+    pub name: String,
+    /// CPU usage as a percentage of a single logical processor, so it can exceed 100%.
+    pub cpu_percent: f64,
+    pub working_set_mbyte: f64,
+    pub io_mbyte: f64,
+}
+
+/// The processes using the most of each resource, busiest first.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TopProcesses {
+    pub cpu: Vec<ProcessUsage>,
+    pub memory: Vec<ProcessUsage>,
+    /// Processes ranked by I/O bandwidth, where `io_mbyte` is per second.
+    pub io: Vec<ProcessUsage>,
+}
+
+pub struct State {
+    reader: sys::Reader,
+    /// Whether to read processes on the next fetch, since that's much more expensive than the other metrics.
+    enabled: Rc<Cell<bool>>,
+    /// Number of processes to list for each resource.
+    count: Rc<Cell<usize>>,
+    /// Counters of each process from the previous fetch, or empty if processes weren't read.
+    prev: RefCell<BTreeMap<ProcessId, ProcessCounters>>,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self {
+            reader: sys::Reader::new(),
+            enabled: Default::default(),
+            count: Default::default(),
+            prev: Default::default(),
+        }
+    }
+
+    /// Handle to whether processes are read, which is checked whenever this source is fetched.
+    pub fn enabled(&self) -> Rc<Cell<bool>> {
+        Rc::clone(&self.enabled)
+    }
+
+    /// Handle to the number of processes to list for each resource, which is read whenever this source is fetched.
+    pub fn count(&self) -> Rc<Cell<usize>> {
+        Rc::clone(&self.count)
+    }
+}
+
+/// Pick the `count` processes with the highest `key`, skipping any that aren't using that resource at all.
+fn rank(usages: &[ProcessUsage], count: usize, key: fn(&ProcessUsage) -> f64) -> Vec<ProcessUsage> {
+    let mut ranked = usages
+        .iter()
+        .filter(|usage| key(usage) > 0.0)
+        .cloned()
+        .collect::<Vec<_>>();
+    ranked.sort_by(|a, b| key(b).total_cmp(&key(a)).then_with(|| a.name.cmp(&b.name)));
+    ranked.truncate(count);
+    ranked
+}
+
+impl MetricSource for State {
+    type Raw = Vec<ProcessCounters>;
+    type Sample = TopProcesses;
+    type Error = sys::Error;
+
+    fn name(&self) -> &str {
+        "processes"
+    }
+
+    fn read(&self) -> Result<Vec<ProcessCounters>, sys::Error> {
+        if !self.enabled.get() {
+            return Ok(Vec::new());
+        }
+        self.reader.read_processes()
+    }
+
+    fn convert(
+        &self,
+        processes: Vec<ProcessCounters>,
+        time_delta: Option<Duration>,
+    ) -> TopProcesses {
+        let processes = processes
+            .into_iter()
+            .map(|counters| (counters.id, counters))
+            .collect::<BTreeMap<_, _>>();
+        let prev = self.prev.replace(processes);
+        let processes = self.prev.borrow();
+
+        // Deltas are per process, and then summed by name, so that processes which exit between fetches don't take
+        // their earlier usage out of the sum for their name.
+        // Processes which weren't running in the previous fetch, or all of them if processes weren't read then,
+        // only count toward memory, since they don't have deltas yet.
+        let seconds = time_delta.map_or(0.0, |time_delta| time_delta.as_secs_f64());
+        let mut usages = BTreeMap::<&str, ProcessUsage>::new();
+        for (id, counters) in processes.iter() {
+            let (cpu_percent, io_mbyte) = match prev.get(id) {
+                // The name also changes if the pid is reused without a start time to tell them apart.
+                Some(prev) if seconds > 0.0 && prev.name == counters.name => (
+                    (counters.cpu_seconds - prev.cpu_seconds).max(0.0) * 100.0 / seconds,
+                    counters.io_bytes.saturating_sub(prev.io_bytes) as f64
+                        / BYTES_PER_MEGABYTE
+                        / seconds,
+                ),
+                _ => (0.0, 0.0),
+            };
+            let usage = usages
+                .entry(&counters.name)
+                .or_insert_with(|| ProcessUsage {
+                    name: counters.name.clone(),
+                    cpu_percent: 0.0,
+                    working_set_mbyte: 0.0,
+                    io_mbyte: 0.0,
+                });
+            usage.cpu_percent += cpu_percent;
+            usage.working_set_mbyte += counters.working_set as f64 / BYTES_PER_MEGABYTE;
+            usage.io_mbyte += io_mbyte;
+        }
+        let usages = usages.into_values().collect::<Vec<_>>();
+
+        let count = self.count.get();
+        TopProcesses {
+            cpu: rank(&usages, count, |usage| usage.cpu_percent),
+            memory: rank(&usages, count, |usage| usage.working_set_mbyte),
+            io: rank(&usages, count, |usage| usage.io_mbyte),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(
+        pid: u32,
+        name: &str,
+        cpu_seconds: f64,
+        working_set: u64,
+        io_bytes: u64,
+    ) -> ProcessCounters {
+        ProcessCounters {
+            id: ProcessId { pid, start_time: 0 },
+            name: name.to_owned(),
+            cpu_seconds,
+            working_set,
+            io_bytes,
+        }
+    }
+
+    fn names(usages: &[ProcessUsage]) -> Vec<&str> {
+        usages.iter().map(|usage| usage.name.as_str()).collect()
+    }
+
+    #[test]
+    fn ranks_by_each_resource() {
+        let state = State::new();
+        state.count.set(2);
+
+        let first = vec![
+            process(1, "chrome", 10.0, 2_000_000_000, 0),
+            process(2, "code", 5.0, 900_000_000, 1_000_000),
+            process(3, "rustc", 1.0, 300_000_000, 0),
+        ];
+        let top = state.convert(first, None);
+        // Only memory can be ranked without a previous sample.
+        assert_eq!(top.cpu, []);
+        assert_eq!(names(&top.memory), ["chrome", "code"]);
+        assert_eq!(top.io, []);
+
+        let second = vec![
+            process(1, "chrome", 10.5, 2_000_000_000, 0),
+            process(2, "code", 5.0, 900_000_000, 41_000_000),
+            process(3, "rustc", 2.5, 1_200_000_000, 11_000_000),
+        ];
+        let top = state.convert(second, Some(Duration::from_secs(2)));
+        assert_eq!(names(&top.cpu), ["rustc", "chrome"]);
+        assert_eq!(top.cpu[0].cpu_percent, 75.0);
+        assert_eq!(top.cpu[1].cpu_percent, 25.0);
+        assert_eq!(names(&top.memory), ["chrome", "rustc"]);
+        assert_eq!(top.memory[1].working_set_mbyte, 1200.0);
+        assert_eq!(names(&top.io), ["code", "rustc"]);
+        assert_eq!(top.io[0].io_mbyte, 20.0);
+    }
+
+    #[test]
+    fn combines_processes_with_the_same_name() {
+        let state = State::new();
+        state.count.set(5);
+
+        state.convert(
+            vec![
+                process(10, "cc1plus", 1.0, 100_000_000, 0),
+                process(11, "cc1plus", 2.0, 200_000_000, 0),
+            ],
+            None,
+        );
+        let top = state.convert(
+            vec![
+                process(10, "cc1plus", 1.5, 100_000_000, 0),
+                process(11, "cc1plus", 3.0, 200_000_000, 0),
+            ],
+            Some(Duration::from_secs(1)),
+        );
+
+        assert_eq!(names(&top.cpu), ["cc1plus"]);
+        assert_eq!(top.cpu[0].cpu_percent, 150.0);
+        assert_eq!(top.memory[0].working_set_mbyte, 300.0);
+    }
+
+    #[test]
+    fn exited_processes_do_not_hide_the_rest_of_their_name() {
+        let state = State::new();
+        state.count.set(5);
+
+        state.convert(
+            vec![
+                process(10, "rustc", 120.0, 1_000_000, 50_000_000),
+                process(11, "rustc", 1.0, 1_000_000, 0),
+            ],
+            None,
+        );
+        // The long-running compiler exits, while another one keeps a core busy.
+        let top = state.convert(
+            vec![process(11, "rustc", 2.0, 1_000_000, 4_000_000)],
+            Some(Duration::from_secs(1)),
+        );
+
+        assert_eq!(names(&top.cpu), ["rustc"]);
+        assert_eq!(top.cpu[0].cpu_percent, 100.0);
+        assert_eq!(top.io[0].io_mbyte, 4.0);
+    }
+
+    #[test]
+    fn reused_pids_are_not_counted_as_the_same_process() {
+        let state = State::new();
+        state.count.set(5);
+
+        let make = |start_time, cpu_seconds| ProcessCounters {
+            id: ProcessId {
+                pid: 10,
+                start_time,
+            },
+            ..process(10, "make", cpu_seconds, 1_000_000, 0)
+        };
+        state.convert(vec![make(100, 30.0)], None);
+        // A new process with the same pid, which started later, and happens to have used more CPU time.
+        let top = state.convert(vec![make(200, 40.0)], Some(Duration::from_secs(1)));
+        assert_eq!(top.cpu, []);
+
+        // Without a start time, a different name shows that the pid was reused.
+        state.convert(vec![process(10, "make", 1.0, 0, 0)], None);
+        let top = state.convert(
+            vec![process(10, "sh", 2.0, 0, 0)],
+            Some(Duration::from_secs(1)),
+        );
+        assert_eq!(top.cpu, []);
+    }
+
+    #[test]
+    fn not_read_unless_enabled() {
+        let state = State::new();
+        state.count.set(5);
+        assert_eq!(state.read().unwrap(), []);
+
+        let top = state.convert(vec![process(1, "chrome", 10.0, 1_000_000, 0)], None);
+        assert_ne!(top, TopProcesses::default());
+
+        // Disabling clears the previous results, so they don't linger.
+        let top = state.convert(Vec::new(), Some(Duration::from_secs(1)));
+        assert_eq!(top, TopProcesses::default());
+    }
+}
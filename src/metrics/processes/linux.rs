@@ -0,0 +1,251 @@
+use crate::metrics::processes::{ProcessCounters, ProcessId};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub use std::io::Error;
+
+const PROC_PATH: &str = "/proc";
+
+/// Clock ticks per second in `/proc/[pid]/stat`, i.e. `USER_HZ`, which is 100 on every architecture that matters.
+const TICKS_PER_SECOND: f64 = 100.0;
+
+const BYTES_PER_KILOBYTE: u64 = 1024;
+
+pub struct Reader {
+    proc: PathBuf,
+}
+
+impl Reader {
+    pub fn new() -> Self {
+        Self::with_path(PROC_PATH)
+    }
+
+    pub fn with_path(proc: impl Into<PathBuf>) -> Self {
+        Self { proc: proc.into() }
+    }
+
+    /// Read the usage of every process in `/proc`, ordered by pid.
+    ///
+    /// Processes can exit while they're being read, so any which can't be read are skipped.
+    pub fn read_processes(&self) -> io::Result<Vec<ProcessCounters>> {
+        let mut processes = Vec::new();
+        for entry in fs::read_dir(&self.proc)? {
+            let dir = entry?.path();
+            let Some(pid) = dir
+                .file_name()
+                .and_then(|name| name.to_str()?.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            match read_process(pid, &dir) {
+                Ok(counters) => processes.push(counters),
+                Err(e) => log::trace!("Failed to read process `{}`: {e}", dir.display()),
+            }
+        }
+        processes.sort_by_key(|counters| counters.id);
+        Ok(processes)
+    }
+}
+
+fn read_process(pid: u32, dir: &Path) -> io::Result<ProcessCounters> {
+    let Stat {
+        name,
+        cpu_seconds,
+        start_time,
+    } = parse_stat(&fs::read_to_string(dir.join("stat"))?)?;
+    // `rss` in `stat` is in pages, whose size isn't fixed, so use `status` instead, which is in kB.
+    let working_set = parse_status_rss(&fs::read_to_string(dir.join("status"))?);
+    // Only readable for our own processes (unless we're root), and missing without I/O accounting,
+    // so those don't count toward I/O.
+    let io_bytes = match fs::read_to_string(dir.join("io")) {
+        Ok(io) => parse_io(&io),
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::PermissionDenied | io::ErrorKind::NotFound
+            ) =>
+        {
+            0
+        }
+        Err(e) => return Err(e),
+    };
+    Ok(ProcessCounters {
+        id: ProcessId { pid, start_time },
+        name,
+        cpu_seconds,
+        working_set,
+        io_bytes,
+    })
+}
+
+struct Stat {
+    name: String,
+    cpu_seconds: f64,
+    /// In clock ticks since boot.
+    start_time: u64,
+}
+
+/// Parse the name, CPU time, and start time from `/proc/[pid]/stat`, which looks like:
+///
+/// ```text
+/// pid (comm) state ppid pgrp session tty_nr tpgid flags minflt cminflt majflt cmajflt utime stime
+///     cutime cstime priority nice num_threads itrealvalue starttime ...
+/// ```
+///
+/// `comm` can contain spaces and parentheses, so it extends to the last `)`.
+///
+/// https://man7.org/linux/man-pages/man5/proc_pid_stat.5.html
+fn parse_stat(stat: &str) -> io::Result<Stat> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed stat");
+
+    let (_, rest) = stat.split_once(" (").ok_or_else(invalid)?;
+    let (name, fields) = rest.rsplit_once(") ").ok_or_else(invalid)?;
+    let mut fields = fields.split_ascii_whitespace().skip(11);
+    let mut ticks = |skip| -> io::Result<u64> {
+        let field = fields.nth(skip).ok_or_else(invalid)?;
+        field
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    };
+    let user = ticks(0)?;
+    let system = ticks(0)?;
+    let start_time = ticks(6)?;
+
+    Ok(Stat {
+        name: name.to_owned(),
+        cpu_seconds: (user + system) as f64 / TICKS_PER_SECOND,
+        start_time,
+    })
+}
+
+/// Parse the resident memory in bytes from the `VmRSS:  1234 kB` line of `/proc/[pid]/status`.
+///
+/// Kernel threads don't have one, so they're zero.
+fn parse_status_rss(status: &str) -> u64 {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|value| value.trim().strip_suffix(" kB"))
+        .and_then(|kilobytes| kilobytes.trim().parse::<u64>().ok())
+        .map_or(0, |kilobytes| kilobytes * BYTES_PER_KILOBYTE)
+}
+
+/// Parse the bytes read and written from `/proc/[pid]/io`, which looks like:
+///
+/// ```text
+/// rchar: 323934931
+/// wchar: 323929600
+/// ...
+/// ```
+///
+/// These include sockets and pipes, not just storage, like `IO Data Bytes` on Windows.
+fn parse_io(io: &str) -> u64 {
+    io.lines()
+        .filter_map(|line| line.split_once(": "))
+        .filter(|(name, _)| matches!(*name, "rchar" | "wchar"))
+        .filter_map(|(_, value)| value.trim().parse::<u64>().ok())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::Fixture;
+
+    fn process(fixture: &Fixture, pid: u32, comm: &str, ticks: (u64, u64), rss_kb: Option<u64>) {
+        let (utime, stime) = ticks;
+        fixture.file(
+            format!("{pid}/stat"),
+            &format!("{pid} ({comm}) S 1 {pid} {pid} 0 -1 4194560 2573 0 0 0 {utime} {stime} 0 0 20 0 1 0 4108 17571840 1024 18446744073709551615\n"),
+        );
+        let rss = rss_kb.map_or(String::new(), |kb| format!("VmRSS:\t    {kb} kB\n"));
+        fixture.file(
+            format!("{pid}/status"),
+            &format!("Name:\t{comm}\nState:\tS (sleeping)\n{rss}Threads:\t1\n"),
+        );
+    }
+
+    fn counters(
+        pid: u32,
+        name: &str,
+        cpu_seconds: f64,
+        working_set: u64,
+        io_bytes: u64,
+    ) -> ProcessCounters {
+        ProcessCounters {
+            id: ProcessId {
+                pid,
+                start_time: 4108,
+            },
+            name: name.to_owned(),
+            cpu_seconds,
+            working_set,
+            io_bytes,
+        }
+    }
+
+    #[test]
+    fn reads_each_process() {
+        let fixture = Fixture::new();
+        process(&fixture, 1, "systemd", (150, 250), Some(12_000));
+        process(&fixture, 200, "chrome", (1000, 500), Some(100_000));
+        process(&fixture, 201, "chrome", (300, 200), Some(50_000));
+        fixture.file(
+            "200/io",
+            "rchar: 1000\nwchar: 500\nsyscr: 10\nsyscw: 5\nread_bytes: 4096\nwrite_bytes: 0\ncancelled_write_bytes: 0\n",
+        );
+        fixture.file("201/io", "rchar: 2000\nwchar: 0\n");
+        // Not processes
+        fixture.file("stat", "cpu  1 2 3 4\n");
+        fixture.dir("self");
+
+        let processes = Reader::with_path(fixture.path(""))
+            .read_processes()
+            .unwrap();
+        assert_eq!(
+            processes,
+            [
+                counters(1, "systemd", 4.0, 12_000 * 1024, 0),
+                counters(200, "chrome", 15.0, 100_000 * 1024, 1500),
+                counters(201, "chrome", 5.0, 50_000 * 1024, 2000),
+            ]
+        );
+    }
+
+    #[test]
+    fn names_with_spaces_and_parentheses() {
+        let fixture = Fixture::new();
+        process(&fixture, 42, "tmux: server) (x", (1, 2), None);
+
+        let processes = Reader::with_path(fixture.path(""))
+            .read_processes()
+            .unwrap();
+        assert_eq!(processes, [counters(42, "tmux: server) (x", 0.03, 0, 0)]);
+    }
+
+    #[test]
+    fn exited_processes_are_skipped() {
+        let fixture = Fixture::new();
+        process(&fixture, 1, "init", (1, 1), Some(1));
+        // Listed, but gone by the time it's read
+        fixture.dir("999");
+
+        let processes = Reader::with_path(fixture.path(""))
+            .read_processes()
+            .unwrap();
+        assert_eq!(
+            processes
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>(),
+            ["init"]
+        );
+    }
+
+    #[test]
+    fn read_live() {
+        let processes = Reader::new().read_processes().unwrap();
+        assert!(!processes.is_empty());
+    }
+}
@@ -0,0 +1,84 @@
+use crate::metrics::processes::{ProcessCounters, ProcessId};
+use crate::perf::{MultiCounter, PerfQueries, find_counters};
+use std::cell::OnceCell;
+use windows::core::Result;
+
+pub use windows::core::Error;
+
+const PROCESS_COUNTERSET: &str = "Process";
+/// Identifies processes with the same name, which are otherwise separate instances with the same name.
+const PROCESS_ID_COUNTER: &str = "ID Process";
+/// Time spent running in user and kernel mode, in 100ns intervals.
+const PROCESSOR_TIME_COUNTER: &str = "% Processor Time";
+/// Resident memory, in bytes.
+const WORKING_SET_COUNTER: &str = "Working Set";
+/// Bytes read and written, including files, the network, and devices.
+const IO_DATA_BYTES_COUNTER: &str = "IO Data Bytes/sec";
+/// Instances which aren't processes: the total of all processes, and the time that each processor was idle.
+const NON_PROCESS_INSTANCES: [&str; 2] = ["_Total", "Idle"];
+
+const INTERVALS_PER_SECOND: f64 = 10_000_000.0;
+
+#[derive(Default)]
+pub struct Reader {
+    /// Opened on first use, since processes are only read while they're shown.
+    queries: OnceCell<PerfQueries<MultiCounter, 4, u64>>,
+}
+
+impl Reader {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    fn queries(&self) -> Result<&PerfQueries<MultiCounter, 4, u64>> {
+        if let Some(queries) = self.queries.get() {
+            return Ok(queries);
+        }
+        let (counterset, counter_ids) = find_counters(
+            PROCESS_COUNTERSET,
+            [
+                PROCESS_ID_COUNTER,
+                PROCESSOR_TIME_COUNTER,
+                WORKING_SET_COUNTER,
+                IO_DATA_BYTES_COUNTER,
+            ],
+        )?;
+        let queries = PerfQueries::new_all_instances(counterset, &counter_ids)?;
+        Ok(self.queries.get_or_init(|| queries))
+    }
+
+    /// Read the usage of every process from the `Process` counterset.
+    pub fn read_processes(&self) -> Result<Vec<ProcessCounters>> {
+        Ok(self
+            .queries()?
+            .query_instances()?
+            .into_iter()
+            .filter(|(instance, _)| !NON_PROCESS_INSTANCES.contains(&instance.as_str()))
+            .map(
+                |(instance, [pid, intervals, working_set, io_bytes])| ProcessCounters {
+                    // There's no start time, so reused pids are told apart by name.
+                    id: ProcessId {
+                        pid: pid as u32,
+                        start_time: 0,
+                    },
+                    name: process_name(&instance).to_owned(),
+                    cpu_seconds: intervals as f64 / INTERVALS_PER_SECOND,
+                    working_set,
+                    io_bytes,
+                },
+            )
+            .collect())
+    }
+}
+
+/// Strip the `#1` suffix which distinguishes processes with the same name, e.g. `chrome#1`.
+fn process_name(instance: &str) -> &str {
+    match instance.rsplit_once('#') {
+        Some((name, number))
+            if !number.is_empty() && number.bytes().all(|c| c.is_ascii_digit()) =>
+        {
+            name
+        }
+        _ => instance,
+    }
+}
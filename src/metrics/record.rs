@@ -0,0 +1,279 @@
+use crate::metrics::MetricSource;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, LineWriter, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// A single fetch of a single metric, stored as one line of JSON in a recording.
+#[derive(Serialize, Deserialize)]
+struct Entry<M, R> {
+    /// Name of the metric, from `MetricSource::name`.
+    metric: M,
+    /// Time since the previous fetch, in nanoseconds.
+    time_delta_ns: Option<u64>,
+    /// Raw counters, or the error message if reading them failed.
+    raw: Result<R, String>,
+}
+
+/// Writes the raw counters behind every sample to a file, so they can be replayed later.
+pub struct Recorder {
+    // Flush every line, since the process is usually terminated instead of exiting cleanly.
+    out: RefCell<LineWriter<File>>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            out: RefCell::new(LineWriter::new(File::create(path)?)),
+        })
+    }
+
+    pub fn record<R: Serialize, E: Display>(
+        &self,
+        metric: &str,
+        time_delta: Option<Duration>,
+        raw: &Result<R, E>,
+    ) {
+        if let Err(e) = self.record_fallible(metric, time_delta, raw) {
+            log::error!("Failed to record {metric}: {e}");
+        }
+    }
+
+    fn record_fallible<R: Serialize, E: Display>(
+        &self,
+        metric: &str,
+        time_delta: Option<Duration>,
+        raw: &Result<R, E>,
+    ) -> io::Result<()> {
+        let entry = Entry {
+            metric,
+            time_delta_ns: time_delta.map(|d| u64::try_from(d.as_nanos()).unwrap_or(u64::MAX)),
+            raw: raw.as_ref().map_err(|e| e.to_string()),
+        };
+
+        let mut out = self.out.borrow_mut();
+        serde_json::to_writer(&mut *out, &entry)?;
+        out.write_all(b"\n")
+    }
+}
+
+/// Recorded entries, grouped by metric name.
+type Entries = HashMap<String, VecDeque<Entry<String, serde_json::Value>>>;
+
+/// Raw counters loaded from a recording.
+///
+/// Clones share the same entries, so each metric's `Replay` source consumes its own entries in order.
+#[derive(Clone)]
+pub struct Recording {
+    entries: Rc<RefCell<Entries>>,
+    fetch_count: usize,
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::parse(BufReader::new(File::open(path)?))
+    }
+
+    fn parse(reader: impl BufRead) -> io::Result<Self> {
+        let mut entries = Entries::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: Entry<String, serde_json::Value> = serde_json::from_str(&line)?;
+            entries
+                .entry(entry.metric.clone())
+                .or_default()
+                .push_back(entry);
+        }
+
+        let fetch_count = entries.values().map(VecDeque::len).max().unwrap_or(0);
+
+        Ok(Self {
+            entries: Rc::new(RefCell::new(entries)),
+            fetch_count,
+        })
+    }
+
+    /// Number of fetches in the recording.
+    pub fn fetch_count(&self) -> usize {
+        self.fetch_count
+    }
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    /// No more entries were recorded for this metric.
+    Exhausted,
+    /// Reading raw counters failed when recording.
+    Recorded(String),
+    /// The recorded raw counters couldn't be parsed.
+    Invalid(serde_json::Error),
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exhausted => f.write_str("end of recording"),
+            Self::Recorded(e) => write!(f, "recorded error: {e}"),
+            Self::Invalid(e) => write!(f, "invalid recorded counters: {e}"),
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+/// Metric source which feeds recorded raw counters through another source's conversion.
+pub struct Replay<S> {
+    source: S,
+    recording: Recording,
+}
+
+impl<S: MetricSource> Replay<S> {
+    pub fn new(source: S, recording: &Recording) -> Self {
+        Self {
+            source,
+            recording: recording.clone(),
+        }
+    }
+}
+
+impl<S: MetricSource> MetricSource for Replay<S> {
+    /// Raw counters, along with the time delta they were originally fetched with.
+    type Raw = (Option<Duration>, S::Raw);
+    type Sample = S::Sample;
+    type Error = ReplayError;
+
+    fn name(&self) -> &str {
+        self.source.name()
+    }
+
+    fn unit(&self) -> &str {
+        self.source.unit()
+    }
+
+    fn read(&self) -> Result<Self::Raw, ReplayError> {
+        let entry = self
+            .recording
+            .entries
+            .borrow_mut()
+            .get_mut(self.source.name())
+            .and_then(VecDeque::pop_front)
+            .ok_or(ReplayError::Exhausted)?;
+
+        let raw = entry.raw.map_err(ReplayError::Recorded)?;
+        let raw = serde_json::from_value(raw).map_err(ReplayError::Invalid)?;
+
+        Ok((entry.time_delta_ns.map(Duration::from_nanos), raw))
+    }
+
+    fn convert(&self, raw: Self::Raw, _time_delta: Option<Duration>) -> S::Sample {
+        // Use the recorded time delta, not the time since the previous replayed fetch.
+        let (time_delta, raw) = raw;
+        self.source.convert(raw, time_delta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::Fixture;
+    use crate::metrics::Metrics;
+    use crate::metrics::fake::{FakeError, Scripted};
+    use crate::opt::MemoryUsage;
+
+    #[test]
+    fn round_trip() {
+        let fixture = Fixture::new();
+        let path = fixture.path("recording.jsonl");
+
+        let recorder = Recorder::create(&path).unwrap();
+        recorder.record("fake", None, &Ok::<_, FakeError>(1.0));
+        recorder.record(
+            "fake",
+            Some(Duration::from_secs(1)),
+            &Err::<f64, _>(FakeError),
+        );
+        recorder.record(
+            "fake",
+            Some(Duration::from_nanos(1_000_000_001)),
+            &Ok::<_, FakeError>(3.0),
+        );
+        drop(recorder);
+
+        let recording = Recording::load(&path).unwrap();
+        assert_eq!(recording.fetch_count(), 3);
+
+        let source = Scripted::zeros();
+        let replay = Replay::new(source.clone(), &recording);
+
+        assert_eq!(replay.fetch(None).unwrap(), 1.0);
+        assert!(matches!(
+            replay.fetch(Some(Duration::from_millis(1))),
+            Err(ReplayError::Recorded(e)) if e == "fake error"
+        ));
+        assert_eq!(replay.fetch(Some(Duration::from_millis(1))).unwrap(), 3.0);
+        assert!(matches!(replay.fetch(None), Err(ReplayError::Exhausted)));
+
+        assert_eq!(
+            source.time_deltas(),
+            [None, Some(Duration::from_nanos(1_000_000_001))]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Recording::parse("not json\n".as_bytes()).is_err());
+        assert!(Recording::parse("{\"metric\":\"CPU\"}\n".as_bytes()).is_err());
+
+        let recording = Recording::parse(
+            "{\"metric\":\"fake\",\"time_delta_ns\":null,\"raw\":{\"Ok\":\"x\"}}\n".as_bytes(),
+        )
+        .unwrap();
+        let replay = Replay::new(Scripted::zeros(), &recording);
+        assert!(matches!(replay.fetch(None), Err(ReplayError::Invalid(_))));
+    }
+
+    #[test]
+    fn replay_matches_live() {
+        let fixture = Fixture::new();
+        let path = fixture.path("recording.jsonl");
+
+        let live = Metrics::new(MemoryUsage::Available).unwrap();
+        live.start_recording(Recorder::create(&path).unwrap());
+        let mut live_averages = Vec::new();
+        for _ in 0..3 {
+            live.fetch();
+            std::thread::sleep(Duration::from_millis(10));
+            live_averages.push(averages(&live));
+        }
+        drop(live);
+
+        let recording = Recording::load(&path).unwrap();
+        let replayed = Metrics::replay(MemoryUsage::Available, &recording).unwrap();
+        let mut replayed_averages = Vec::new();
+        for _ in 0..recording.fetch_count() {
+            replayed.fetch();
+            replayed_averages.push(averages(&replayed));
+        }
+
+        assert_eq!(replayed_averages, live_averages);
+    }
+
+    fn averages(metrics: &Metrics) -> [f64; 4] {
+        [
+            metrics.avg_cpu_percent(),
+            metrics.avg_memory_percent(),
+            metrics.avg_disk_mbyte(),
+            metrics.avg_network_mbit(),
+        ]
+    }
+}
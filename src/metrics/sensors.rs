@@ -0,0 +1,191 @@
+use crate::metrics::MetricSource;
+use crate::utils::glob_matches;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Duration;
+
+#[cfg_attr(windows, path = "sensors/windows.rs")]
+#[cfg_attr(target_os = "linux", path = "sensors/linux.rs")]
+mod sys;
+
+/// What a sensor measures, which determines the unit of its readings.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SensorKind {
+    /// Degrees Celsius.
+    Temperature,
+    /// Revolutions per minute.
+    Fan,
+}
+
+/// The current reading of a single temperature or fan sensor.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Reading {
+    /// Device that the sensor belongs to, e.g. `coretemp` or `nvme` on Linux.
+    chip: String,
+    /// Name of the sensor on that device, e.g. `Package id 0`.
+    label: String,
+    kind: SensorKind,
+    value: f64,
+}
+
+impl Reading {
+    /// Whether a glob matches either the label alone (e.g. `Package id 0`) or the chip and label (e.g. `coretemp/Package id 0`).
+    fn matches(&self, pattern: &str) -> bool {
+        glob_matches(pattern, &self.label)
+            || glob_matches(pattern, &format!("{}/{}", self.chip, self.label))
+    }
+}
+
+pub struct State {
+    reader: sys::Reader,
+    /// Glob matching the sensor to show, or `None` to not read sensors at all.
+    selection: Rc<RefCell<Option<String>>>,
+    /// Kind of the sensor that matched the selection in the latest fetch, if any.
+    kind: Rc<Cell<Option<SensorKind>>>,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self::with_reader(sys::Reader::new())
+    }
+
+    fn with_reader(reader: sys::Reader) -> Self {
+        Self {
+            reader,
+            selection: Default::default(),
+            kind: Default::default(),
+        }
+    }
+
+    /// Handle to the glob selecting which sensor to show, which is read whenever this source is fetched.
+    pub fn selection(&self) -> Rc<RefCell<Option<String>>> {
+        Rc::clone(&self.selection)
+    }
+
+    /// Handle to the kind of the selected sensor, which is updated whenever this source is fetched.
+    pub fn kind(&self) -> Rc<Cell<Option<SensorKind>>> {
+        Rc::clone(&self.kind)
+    }
+
+    /// Describe each sensor and its current reading.
+    pub fn describe_sensors(&self) -> Result<Vec<String>, sys::Error> {
+        Ok(self
+            .reader
+            .read_sensors()?
+            .iter()
+            .map(|reading| {
+                let Reading {
+                    chip,
+                    label,
+                    kind,
+                    value,
+                } = reading;
+                let value = match kind {
+                    SensorKind::Temperature => format!("{value:.1}°C"),
+                    SensorKind::Fan => format!("{value:.0} RPM"),
+                };
+                format!("{chip}/{label}: {value}")
+            })
+            .collect())
+    }
+}
+
+/// Pick the reading matching `pattern`, or the highest of them if there are several, e.g. `Core *`.
+fn select<'a>(readings: &'a [Reading], pattern: &str) -> Option<&'a Reading> {
+    readings
+        .iter()
+        .filter(|reading| reading.matches(pattern))
+        .max_by(|a, b| a.value.total_cmp(&b.value))
+}
+
+impl MetricSource for State {
+    type Raw = Vec<Reading>;
+    type Sample = f64;
+    type Error = sys::Error;
+
+    fn name(&self) -> &str {
+        "sensors"
+    }
+
+    fn unit(&self) -> &str {
+        match self.kind.get() {
+            Some(SensorKind::Temperature) => "°C",
+            Some(SensorKind::Fan) => "RPM",
+            None => "",
+        }
+    }
+
+    fn read(&self) -> Result<Vec<Reading>, sys::Error> {
+        // Reading sensors can be slow (e.g. waking up a sleeping disk), so skip it unless one is shown.
+        if self.selection.borrow().is_none() {
+            return Ok(Vec::new());
+        }
+        self.reader.read_sensors()
+    }
+
+    fn convert(&self, readings: Vec<Reading>, _time_delta: Option<Duration>) -> f64 {
+        let selection = self.selection.borrow();
+        let selected = selection
+            .as_deref()
+            .and_then(|pattern| select(&readings, pattern));
+
+        self.kind.set(selected.map(|reading| reading.kind));
+        selected.map_or(0.0, |reading| reading.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(chip: &str, label: &str, kind: SensorKind, value: f64) -> Reading {
+        Reading {
+            chip: chip.to_owned(),
+            label: label.to_owned(),
+            kind,
+            value,
+        }
+    }
+
+    fn readings() -> Vec<Reading> {
+        vec![
+            reading("coretemp", "Package id 0", SensorKind::Temperature, 45.0),
+            reading("coretemp", "Core 0", SensorKind::Temperature, 43.0),
+            reading("coretemp", "Core 1", SensorKind::Temperature, 51.0),
+            reading("thinkpad", "fan1", SensorKind::Fan, 2900.0),
+        ]
+    }
+
+    #[test]
+    fn selection() {
+        let readings = readings();
+        let select = |pattern| select(&readings, pattern).map(|reading| reading.value);
+
+        assert_eq!(select("Package id 0"), Some(45.0));
+        assert_eq!(select("package ID 0"), Some(45.0));
+        assert_eq!(select("coretemp/Core 0"), Some(43.0));
+        assert_eq!(select("thinkpad/*"), Some(2900.0));
+        // Hottest of several
+        assert_eq!(select("Core *"), Some(51.0));
+        assert_eq!(select("nvme/*"), None);
+    }
+
+    #[test]
+    fn selected_kind_follows_readings() {
+        let state = State::with_reader(sys::Reader::new());
+
+        assert_eq!(state.convert(readings(), None), 0.0);
+        assert_eq!(state.kind.get(), None);
+
+        *state.selection.borrow_mut() = Some("fan1".to_owned());
+        assert_eq!(state.convert(readings(), None), 2900.0);
+        assert_eq!(state.kind.get(), Some(SensorKind::Fan));
+        assert_eq!(MetricSource::unit(&state), "RPM");
+
+        // The fan disappeared, e.g. after resuming from sleep.
+        assert_eq!(state.convert(Vec::new(), None), 0.0);
+        assert_eq!(state.kind.get(), None);
+    }
+}
@@ -0,0 +1,211 @@
+use crate::metrics::sensors::{Reading, SensorKind};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub use std::io::Error;
+
+const SYS_CLASS_HWMON_PATH: &str = "/sys/class/hwmon";
+
+pub struct Reader {
+    sys_class_hwmon: PathBuf,
+}
+
+impl Reader {
+    pub fn new() -> Self {
+        Self::with_path(SYS_CLASS_HWMON_PATH)
+    }
+
+    pub fn with_path(sys_class_hwmon: impl Into<PathBuf>) -> Self {
+        Self {
+            sys_class_hwmon: sys_class_hwmon.into(),
+        }
+    }
+
+    /// Read every temperature and fan sensor, from `/sys/class/hwmon/hwmon*/{temp,fan}*_input`.
+    ///
+    /// Each `hwmonN` directory is one chip, with a `name` like `coretemp`, and inputs like `temp1_input`,
+    /// optionally labeled by `temp1_label`.
+    ///
+    /// https://www.kernel.org/doc/Documentation/hwmon/sysfs-interface
+    pub fn read_sensors(&self) -> io::Result<Vec<Reading>> {
+        let mut chips = match fs::read_dir(&self.sys_class_hwmon) {
+            Ok(entries) => entries
+                .map(|entry| Ok(entry?.path()))
+                .collect::<io::Result<Vec<_>>>()?,
+            // Containers and some VMs don't have any sensors.
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        // Directory order is arbitrary, but hwmon numbering is stable within a boot.
+        chips.sort();
+
+        let mut readings = Vec::new();
+        for dir in chips {
+            read_chip(&dir, &mut readings)?;
+        }
+        Ok(readings)
+    }
+}
+
+fn read_chip(dir: &Path, readings: &mut Vec<Reading>) -> io::Result<()> {
+    let chip = match fs::read_to_string(dir.join("name")) {
+        Ok(name) => name.trim().to_owned(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => dir
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+        Err(e) => return Err(e),
+    };
+
+    let mut inputs = fs::read_dir(dir)?
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            let (kind, divisor) = if name.starts_with("temp") {
+                // Millidegrees Celsius
+                (SensorKind::Temperature, 1000.0)
+            } else if name.starts_with("fan") {
+                (SensorKind::Fan, 1.0)
+            } else {
+                return None;
+            };
+            let sensor = name.strip_suffix("_input")?.to_owned();
+            Some((sensor, kind, divisor))
+        })
+        .collect::<Vec<_>>();
+    // Sort numerically, so `temp10` comes after `temp9`.
+    inputs.sort_by_key(|(sensor, kind, _)| {
+        let index = sensor.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+        (
+            *kind == SensorKind::Fan,
+            index.parse::<u32>().unwrap_or(u32::MAX),
+        )
+    });
+
+    for (sensor, kind, divisor) in inputs {
+        // Some sensors fail to read while their device is asleep or disconnected, so just skip them.
+        let raw = match fs::read_to_string(dir.join(format!("{sensor}_input"))) {
+            Ok(raw) => raw,
+            Err(e) => {
+                log::debug!("Failed to read {chip}/{sensor}: {e}");
+                continue;
+            }
+        };
+        // Likewise, one misbehaving driver shouldn't hide every other sensor.
+        let value = match raw.trim().parse::<i64>() {
+            Ok(value) => value,
+            Err(e) => {
+                log::debug!("Invalid value `{}` for {chip}/{sensor}: {e}", raw.trim());
+                continue;
+            }
+        };
+
+        let label = fs::read_to_string(dir.join(format!("{sensor}_label")))
+            .map(|label| label.trim().to_owned())
+            .unwrap_or(sensor);
+
+        readings.push(Reading {
+            chip: chip.clone(),
+            label,
+            kind,
+            value: value as f64 / divisor,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::Fixture;
+
+    fn sys_class_hwmon() -> Fixture {
+        let fixture = Fixture::new();
+        fixture
+            .file("hwmon/hwmon0/name", "acpitz\n")
+            .file("hwmon/hwmon0/temp1_input", "27800\n")
+            .file("hwmon/hwmon1/name", "coretemp\n")
+            .file("hwmon/hwmon1/temp1_input", "45000\n")
+            .file("hwmon/hwmon1/temp1_label", "Package id 0\n")
+            .file("hwmon/hwmon1/temp1_crit", "100000\n")
+            .file("hwmon/hwmon1/temp2_input", "43000\n")
+            .file("hwmon/hwmon1/temp2_label", "Core 0\n")
+            .file("hwmon/hwmon1/temp10_input", "51000\n")
+            .file("hwmon/hwmon1/temp10_label", "Core 8\n")
+            .file("hwmon/hwmon2/name", "thinkpad\n")
+            .file("hwmon/hwmon2/fan1_input", "2900\n")
+            .file("hwmon/hwmon2/pwm1", "128\n")
+            // Below freezing
+            .file("hwmon/hwmon3/name", "drivetemp\n")
+            .file("hwmon/hwmon3/temp1_input", "-1500\n");
+        fixture
+    }
+
+    fn reading(chip: &str, label: &str, kind: SensorKind, value: f64) -> Reading {
+        Reading {
+            chip: chip.to_owned(),
+            label: label.to_owned(),
+            kind,
+            value,
+        }
+    }
+
+    #[test]
+    fn read_from_sysfs() {
+        let fixture = sys_class_hwmon();
+        let reader = Reader::with_path(fixture.path("hwmon"));
+
+        assert_eq!(
+            reader.read_sensors().unwrap(),
+            [
+                reading("acpitz", "temp1", SensorKind::Temperature, 27.8),
+                reading("coretemp", "Package id 0", SensorKind::Temperature, 45.0),
+                reading("coretemp", "Core 0", SensorKind::Temperature, 43.0),
+                reading("coretemp", "Core 8", SensorKind::Temperature, 51.0),
+                reading("thinkpad", "fan1", SensorKind::Fan, 2900.0),
+                reading("drivetemp", "temp1", SensorKind::Temperature, -1.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_sensors() {
+        let fixture = Fixture::new();
+        let reader = Reader::with_path(fixture.path("hwmon"));
+        assert_eq!(reader.read_sensors().unwrap(), []);
+
+        fixture.dir("hwmon");
+        assert_eq!(reader.read_sensors().unwrap(), []);
+    }
+
+    #[test]
+    fn invalid_values() {
+        let fixture = Fixture::new();
+        fixture
+            .file("hwmon/hwmon0/name", "coretemp\n")
+            .file("hwmon/hwmon0/temp1_input", "hot\n")
+            .file("hwmon/hwmon0/temp2_input", "45000\n");
+        let reader = Reader::with_path(fixture.path("hwmon"));
+
+        let readings = reader.read_sensors().unwrap();
+        let labels = readings
+            .iter()
+            .map(|reading| reading.label.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(labels, ["temp2"]);
+    }
+
+    #[test]
+    fn read_live() {
+        for reading in Reader::new().read_sensors().unwrap() {
+            if reading.kind == SensorKind::Temperature {
+                assert!(
+                    reading.value > -100.0 && reading.value < 200.0,
+                    "{reading:?}"
+                );
+            }
+        }
+    }
+}
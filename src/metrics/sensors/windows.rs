@@ -0,0 +1,54 @@
+use crate::metrics::sensors::{Reading, SensorKind};
+use crate::perf::{MultiCounter, PerfQueries, find_counters};
+use std::cell::OnceCell;
+use windows::core::Result;
+
+pub use windows::core::Error;
+
+// ACPI thermal zones, which is the only temperature source Windows exposes without a vendor driver.
+// Fans aren't exposed at all.
+const THERMAL_ZONE_COUNTERSET: &str = "Thermal Zone Information";
+/// In Kelvin.
+const TEMPERATURE_COUNTER: &str = "Temperature";
+const CHIP_NAME: &str = "Thermal Zone";
+
+const ZERO_CELSIUS_IN_KELVIN: f64 = 273.15;
+
+pub struct Reader {
+    /// Opened on first use, since many machines (especially VMs) don't have thermal zones,
+    /// and that only matters if a sensor is shown.
+    queries: OnceCell<PerfQueries<MultiCounter, 1, u64>>,
+}
+
+impl Reader {
+    pub fn new() -> Self {
+        Self {
+            queries: OnceCell::new(),
+        }
+    }
+
+    fn queries(&self) -> Result<&PerfQueries<MultiCounter, 1, u64>> {
+        if let Some(queries) = self.queries.get() {
+            return Ok(queries);
+        }
+        let (counterset, counter_ids) =
+            find_counters(THERMAL_ZONE_COUNTERSET, [TEMPERATURE_COUNTER])?;
+        let queries = PerfQueries::new_all_instances(counterset, &counter_ids)?;
+        Ok(self.queries.get_or_init(|| queries))
+    }
+
+    /// Read the temperature of each thermal zone, named like `\_TZ.THRM`.
+    pub fn read_sensors(&self) -> Result<Vec<Reading>> {
+        Ok(self
+            .queries()?
+            .query_data()?
+            .into_iter()
+            .map(|(name, [kelvin])| Reading {
+                chip: CHIP_NAME.to_owned(),
+                label: name,
+                kind: SensorKind::Temperature,
+                value: kelvin as f64 - ZERO_CELSIUS_IN_KELVIN,
+            })
+            .collect())
+    }
+}
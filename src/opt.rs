@@ -1,6 +1,14 @@
-use crate::constants::{DEFAULT_KEEP_AWAKE_WHILE_UNLOCKED, DEFAULT_MIC_HOTKEY};
+use crate::constants::{
+    DEFAULT_COUNTER_SCALE, DEFAULT_DISK_DISPLAY, DEFAULT_DISK_INSTANCE,
+    DEFAULT_EXCLUDED_INTERFACE_KINDS, DEFAULT_EXCLUDED_INTERFACE_NAMES,
+    DEFAULT_KEEP_AWAKE_WHILE_UNLOCKED, DEFAULT_MEMORY_DISPLAY, DEFAULT_MEMORY_USAGE,
+    DEFAULT_MIC_HOTKEY, DEFAULT_NETWORK_DISPLAY, DEFAULT_SHOW_BATTERY, DEFAULT_SHOW_CPU_CORES,
+    DEFAULT_SHOW_CPU_FREQUENCY, DEFAULT_TOP_PROCESS_COUNT,
+};
 use argh::FromArgs;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
 /// Computer info "deskband".
 #[derive(FromArgs)]
@@ -12,6 +20,101 @@ pub struct Cli {
     /// whether to make the window more visible and interactible for debugging
     #[argh(switch)]
     pub debug_paint: bool,
+
+    /// render metrics in the terminal instead of on the taskbar
+    #[argh(switch)]
+    pub tui: bool,
+
+    /// record raw counters to a file, for reproducing metrics later with --replay
+    #[argh(option)]
+    pub record: Option<PathBuf>,
+
+    /// replay a file created by --record, printing the resulting metrics after each fetch
+    #[argh(option)]
+    pub replay: Option<PathBuf>,
+
+    /// print each network interface and whether it counts toward NET, then exit
+    #[argh(switch)]
+    pub list_interfaces: bool,
+
+    /// print each temperature and fan sensor with its current reading, then exit
+    #[argh(switch)]
+    pub list_sensors: bool,
+
+    #[argh(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+pub enum Command {
+    Render(Render),
+}
+
+/// Render the band with fixture metrics to a PNG file, without a taskbar.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "render")]
+pub struct Render {
+    /// path of the PNG file to write
+    #[argh(option)]
+    pub out: PathBuf,
+
+    /// display scaling, as a percentage (e.g. 150 for 144 DPI)
+    #[argh(option, default = "100")]
+    pub dpi: u32,
+
+    /// render without the microphone warning
+    #[argh(switch)]
+    pub muted: bool,
+
+    /// fill in the background, as with --debug-paint
+    #[argh(switch)]
+    pub debug_paint: bool,
+
+    /// include the per-core CPU strip
+    #[argh(switch)]
+    pub cpu_cores: bool,
+
+    /// include the CPU frequency
+    #[argh(switch)]
+    pub cpu_frequency: bool,
+
+    /// include the battery
+    #[argh(switch)]
+    pub battery: bool,
+
+    /// highlight the CPU frequency when below this many GHz
+    #[argh(option)]
+    pub cpu_frequency_warning_ghz: Option<f64>,
+
+    /// network direction(s) to show: total, both, download, or upload
+    #[argh(
+        option,
+        default = "NetworkDisplay::Total",
+        from_str_fn(parse_config_value)
+    )]
+    pub network: NetworkDisplay,
+
+    /// memory to show: used, swap, or pressure
+    #[argh(
+        option,
+        default = "MemoryDisplay::Used",
+        from_str_fn(parse_config_value)
+    )]
+    pub memory: MemoryDisplay,
+
+    /// disk direction(s) to show: total, both, read, or write
+    #[argh(
+        option,
+        default = "DiskDisplay::Total",
+        from_str_fn(parse_config_value)
+    )]
+    pub disk: DiskDisplay,
+}
+
+/// Parse an option the same way as the corresponding config file value.
+fn parse_config_value<T: DeserializeOwned>(value: &str) -> Result<T, String> {
+    serde_json::from_value(serde_json::Value::from(value)).map_err(|e| e.to_string())
 }
 
 #[derive(Serialize, Deserialize)]
@@ -20,6 +123,30 @@ pub struct ConfigFile {
     pub mic_hotkey: Option<MicrophoneHotkey>,
     #[serde(default)]
     pub keep_awake_while_unlocked: bool,
+    /// Stop keeping the machine awake when running on battery below this charge, as a percentage.
+    pub keep_awake_min_battery_percent: Option<f64>,
+    pub memory_usage: MemoryUsage,
+    /// Whether to show a strip with the CPU usage of each logical processor.
+    pub show_cpu_cores: bool,
+    /// Whether to show the effective CPU frequency next to CPU usage.
+    pub show_cpu_frequency: bool,
+    /// Highlight the CPU frequency when it's below this many GHz, e.g. when throttled on battery.
+    pub cpu_frequency_warning_ghz: Option<f64>,
+    /// Whether to show the battery charge, if there is a battery.
+    pub show_battery: bool,
+    pub memory_display: MemoryDisplay,
+    pub network_display: NetworkDisplay,
+    pub disk_display: DiskDisplay,
+    pub network_interfaces: InterfaceRules,
+    pub disk_instance: DiskInstance,
+    /// Extra metrics, shown to the left of the built-in ones.
+    pub counters: Vec<CounterMetric>,
+    /// Glob matching a temperature or fan sensor to show, by its label or `chip/label`.
+    pub sensor: Option<String>,
+    /// When to find which processes are using the most CPU, memory, and I/O, besides while the tooltip is open.
+    pub top_processes: ProcessSampling,
+    /// Pressure stall series to show as extra metrics, after any sensor. Linux only.
+    pub pressure: Vec<PressureSeries>,
 }
 
 impl Default for ConfigFile {
@@ -27,6 +154,21 @@ impl Default for ConfigFile {
         Self {
             mic_hotkey: DEFAULT_MIC_HOTKEY,
             keep_awake_while_unlocked: DEFAULT_KEEP_AWAKE_WHILE_UNLOCKED,
+            keep_awake_min_battery_percent: None,
+            memory_usage: DEFAULT_MEMORY_USAGE,
+            show_cpu_cores: DEFAULT_SHOW_CPU_CORES,
+            show_cpu_frequency: DEFAULT_SHOW_CPU_FREQUENCY,
+            cpu_frequency_warning_ghz: None,
+            show_battery: DEFAULT_SHOW_BATTERY,
+            memory_display: DEFAULT_MEMORY_DISPLAY,
+            network_display: DEFAULT_NETWORK_DISPLAY,
+            disk_display: DEFAULT_DISK_DISPLAY,
+            network_interfaces: Default::default(),
+            disk_instance: DEFAULT_DISK_INSTANCE,
+            counters: Vec::new(),
+            sensor: None,
+            top_processes: Default::default(),
+            pressure: Vec::new(),
         }
     }
 }
@@ -43,3 +185,243 @@ pub struct MicrophoneHotkey {
     #[serde(default)]
     pub alt: bool,
 }
+
+/// Which memory counts as "used" when computing memory usage.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryUsage {
+    /// Total minus available memory, i.e. memory that can't be freed up without swapping.
+    Available,
+    /// Total minus completely unused memory, i.e. including all buffers and cache.
+    ///
+    /// Linux only.
+    Free,
+    /// Total minus unused memory, buffers, and reclaimable cache.
+    ///
+    /// Linux only.
+    ExcludingCache,
+}
+
+/// Which memory metric to show in the RAM cell.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryDisplay {
+    /// Physical memory usage, as defined by `MemoryUsage`.
+    #[default]
+    Used,
+    /// Swap usage on Linux, or commit charge (as a percentage of the commit limit) on Windows.
+    Swap,
+    /// Memory pressure, i.e. how often tasks stall waiting for memory, from `PressureSeries::MemorySome`.
+    ///
+    /// Linux only.
+    Pressure,
+}
+
+/// Which direction(s) of network bandwidth to show.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkDisplay {
+    /// Received plus sent.
+    #[default]
+    Total,
+    /// Received and sent, side by side.
+    Both,
+    /// Received only.
+    Download,
+    /// Sent only.
+    Upload,
+}
+
+/// Which direction(s) of disk bandwidth to show.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiskDisplay {
+    /// Read plus written.
+    #[default]
+    Total,
+    /// Read and written, side by side.
+    Both,
+    /// Read only.
+    Read,
+    /// Written only.
+    Write,
+}
+
+/// Which disk or volume to measure bandwidth for.
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiskInstance {
+    /// All disks.
+    ///
+    /// On Windows, this also counts reads served from the file cache, unlike the volumes measured by the other options.
+    #[default]
+    Total,
+    /// Whichever disk or volume transferred the most bytes since the previous fetch.
+    Busiest,
+    /// Disks or volumes whose name matches a glob, e.g. `nvme0n1` or `md*` on Linux, or `C:` on Windows. Case-insensitive.
+    Named(String),
+}
+
+/// Which network interfaces count toward network bandwidth.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InterfaceRules {
+    /// If not empty, only interfaces matching at least one of these rules are counted.
+    pub include: Vec<InterfaceRule>,
+    /// Interfaces matching any of these rules are not counted, even if they're included.
+    pub exclude: Vec<InterfaceRule>,
+}
+
+impl Default for InterfaceRules {
+    fn default() -> Self {
+        Self {
+            include: Vec::new(),
+            exclude: DEFAULT_EXCLUDED_INTERFACE_KINDS
+                .iter()
+                .copied()
+                .map(InterfaceRule::Type)
+                .chain(
+                    DEFAULT_EXCLUDED_INTERFACE_NAMES
+                        .iter()
+                        .map(|&name| InterfaceRule::Name(name.to_owned())),
+                )
+                .collect(),
+        }
+    }
+}
+
+/// A condition on a network interface, e.g. `{ "name": "eth*" }` or `{ "type": "tunnel" }`.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InterfaceRule {
+    /// Name glob, e.g. `eth*` on Linux, or `Wi-Fi*` on Windows. Case-insensitive.
+    Name(String),
+    /// Description glob, e.g. `*Virtual*`. Case-insensitive.
+    Description(String),
+    Type(InterfaceKind),
+    /// Physical address, e.g. `04:d9:f5:33:32:b6` or `04-D9-F5-33-32-B6`.
+    Mac(String),
+}
+
+/// Kind of network interface.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InterfaceKind {
+    Ethernet,
+    Wifi,
+    /// VPNs and other tunnels, whose traffic is also sent over a physical interface.
+    Tunnel,
+    Loopback,
+    /// Bridges, e.g. between virtual machines or containers and a physical interface. Linux only.
+    Bridge,
+    #[default]
+    Other,
+}
+
+/// An extra metric read from a performance counter, e.g. GPU usage. Windows only.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CounterMetric {
+    /// Path of the counter using English names, e.g. `\GPU Engine(*engtype_3D)\Utilization Percentage`.
+    /// The instance is a case-insensitive glob, and can be left out for countersets without instances.
+    pub path: String,
+    /// Shown after the value, e.g. `GPU`.
+    pub label: String,
+    /// Shown right after the value, e.g. `%`, or ` MB/s` with a space.
+    pub unit: String,
+    pub kind: CounterKind,
+    /// How to combine the values of the instances matching the path.
+    pub aggregation: Aggregation,
+    /// Factor to multiply the combined value by, e.g. `0.000001` to convert bytes to MB.
+    pub scale: f64,
+    /// Number of digits to show after the decimal point.
+    pub decimals: usize,
+}
+
+impl Default for CounterMetric {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            label: String::new(),
+            unit: String::new(),
+            kind: Default::default(),
+            aggregation: Default::default(),
+            scale: DEFAULT_COUNTER_SCALE,
+            decimals: 0,
+        }
+    }
+}
+
+/// How to interpret the raw value of a performance counter.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CounterKind {
+    /// The current value, e.g. a queue length or a temperature.
+    #[default]
+    Value,
+    /// A cumulative count, shown per second, e.g. bytes transferred.
+    ///
+    /// Timers which count 100ns intervals, like `% Processor Time` or `Utilization Percentage`, are rates too:
+    /// scale them by `0.00001` to get a percentage.
+    Rate,
+    /// A cumulative count divided by its `<counter> Base` companion, e.g. `% Processor Utility`,
+    /// i.e. the increase in the counter over the increase in its base since the previous fetch.
+    Ratio,
+}
+
+/// How to combine the values of multiple instances of a performance counter.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggregation {
+    #[default]
+    Sum,
+    Average,
+    Min,
+    Max,
+}
+
+/// How many of the busiest processes to list, and which thresholds to start listing them at.
+///
+/// Reading every process is much more expensive than the other metrics, so it's only done while the tooltip is open,
+/// or while one of these thresholds is crossed.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessSampling {
+    /// Number of processes to list for each resource.
+    pub count: usize,
+    /// CPU usage, as a percentage.
+    pub cpu_percent: Option<f64>,
+    /// Memory usage, as a percentage.
+    pub memory_percent: Option<f64>,
+    /// Disk bandwidth, in MB/s.
+    pub disk_mbyte: Option<f64>,
+    /// Network bandwidth, in Mb/s.
+    pub network_mbit: Option<f64>,
+}
+
+impl Default for ProcessSampling {
+    fn default() -> Self {
+        Self {
+            count: DEFAULT_TOP_PROCESS_COUNT,
+            cpu_percent: None,
+            memory_percent: None,
+            disk_mbyte: None,
+            network_mbit: None,
+        }
+    }
+}
+
+/// A pressure stall series, i.e. the percentage of the last 10 seconds in which tasks were stalled waiting for a resource.
+///
+/// `some` counts time in which at least one task was stalled, and `full` counts time in which every task was.
+/// Linux only.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PressureSeries {
+    CpuSome,
+    CpuFull,
+    MemorySome,
+    MemoryFull,
+    IoSome,
+    IoFull,
+}
@@ -0,0 +1,454 @@
+use std::error::Error;
+use std::fmt::{self, Display};
+
+// Layouts from perflib.h, which are the same on all architectures.
+// Everything is little-endian, and each structure is padded to a multiple of 8 bytes.
+
+/// `PERF_DATA_HEADER`: `dwTotalSize`, `dwNumCounters`, then timestamps.
+const DATA_HEADER_SIZE: usize = 48;
+/// `PERF_COUNTER_HEADER`: `dwStatus`, `dwType`, `dwSize`, `Reserved`.
+const COUNTER_HEADER_SIZE: usize = 16;
+/// `PERF_COUNTER_DATA`: `dwDataSize`, `dwSize`, then the value.
+const COUNTER_DATA_SIZE: usize = 8;
+/// `PERF_INSTANCE_HEADER`: `Size`, `InstanceId`, then the name.
+const INSTANCE_HEADER_SIZE: usize = 8;
+/// `PERF_MULTI_COUNTERS`: `dwSize`, `dwCounters`, then the counter IDs.
+const MULTI_COUNTERS_SIZE: usize = 8;
+/// `PERF_MULTI_INSTANCES`: `dwTotalSize`, `dwInstances`.
+const MULTI_INSTANCES_SIZE: usize = 8;
+/// `PERF_STRING_BUFFER_HEADER`: `dwSize`, `dwCounters`.
+const STRING_BUFFER_HEADER_SIZE: usize = 8;
+/// `PERF_STRING_COUNTER_HEADER`: `dwCounterId`, `dwOffset`.
+const STRING_COUNTER_HEADER_SIZE: usize = 8;
+
+// PerfCounterDataType
+const PERF_SINGLE_COUNTER: u32 = 1;
+const PERF_MULTIPLE_COUNTERS: u32 = 2;
+const PERF_MULTIPLE_INSTANCES: u32 = 4;
+
+/// Results for one of the counters in a query, i.e. one `PERF_COUNTER_HEADER` block.
+#[derive(Debug, PartialEq)]
+pub enum Block {
+    /// Win32 error code from reading the counter.
+    Failed(u32),
+    /// `PERF_SINGLE_COUNTER`: the counter's value.
+    Single(u64),
+    /// `PERF_MULTIPLE_COUNTERS`: the value of each counter in the counterset, by counter ID.
+    Counters(Vec<(u32, u64)>),
+    /// `PERF_MULTIPLE_INSTANCES`: the counter's value for each instance, by instance name, in the order returned.
+    Instances(Vec<(String, u64)>),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    /// A structure extends past the end of the buffer.
+    Truncated { offset: usize },
+    /// A structure's size is too small to hold its own header.
+    InvalidSize { offset: usize, size: u32 },
+    /// The block type isn't one we know how to parse, e.g. `PERF_COUNTERSET`.
+    UnsupportedType(u32),
+    /// Counter values must be 32 or 64 bits.
+    UnsupportedDataSize(u32),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated { offset } => write!(f, "perf data truncated at offset {offset}"),
+            Self::InvalidSize { offset, size } => {
+                write!(f, "invalid size {size} at offset {offset} in perf data")
+            }
+            Self::UnsupportedType(ty) => write!(f, "unsupported perf counter block type {ty}"),
+            Self::UnsupportedDataSize(size) => {
+                write!(f, "unsupported perf counter data size {size}")
+            }
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Parse the buffer filled in by `PerfQueryCounterData`, returning one block for each counter in the query.
+///
+/// This only trusts the sizes in the buffer as far as the buffer itself goes, so any input is safe to parse.
+pub fn parse(buf: &[u8]) -> Result<Vec<Block>, ParseError> {
+    let total_size = read_size(buf, 0, DATA_HEADER_SIZE)?;
+    let num_counters = read_u32(buf, 4)?;
+    let buf = slice(buf, 0, total_size)?;
+
+    let mut blocks = Vec::new();
+    let mut offset = DATA_HEADER_SIZE;
+    for _ in 0..num_counters {
+        let status = read_u32(buf, offset)?;
+        let ty = read_u32(buf, offset + 4)?;
+        let size = read_size(buf, offset + 8, COUNTER_HEADER_SIZE)?;
+        let block = slice(buf, offset, size)?;
+
+        blocks.push(if status != 0 {
+            Block::Failed(status)
+        } else {
+            // Offsets within the block are relative to the end of the header, but errors should point into `buf`.
+            let body_offset = offset + COUNTER_HEADER_SIZE;
+            parse_body(ty, &block[COUNTER_HEADER_SIZE..]).map_err(|e| e.relative_to(body_offset))?
+        });
+
+        offset += size;
+    }
+
+    Ok(blocks)
+}
+
+/// Parse the buffer filled in by `PerfQueryCounterSetRegistrationInfo` for `PERF_REG_COUNTER_ENGLISH_NAMES`,
+/// returning the name of each counter by counter ID.
+pub fn parse_counter_names(buf: &[u8]) -> Result<Vec<(u32, String)>, ParseError> {
+    let size = read_size(buf, 0, STRING_BUFFER_HEADER_SIZE)?;
+    let num_counters = read_u32(buf, 4)?;
+    let buf = slice(buf, 0, size)?;
+
+    (0..num_counters as usize)
+        .map(|i| {
+            // Saturate, so a huge count is reported as truncated, rather than overflowing.
+            let offset = STRING_BUFFER_HEADER_SIZE
+                .saturating_add(i.saturating_mul(STRING_COUNTER_HEADER_SIZE));
+            let id = read_u32(buf, offset)?;
+            // Relative to the start of the buffer.
+            let name_offset = read_u32(buf, offset + 4)? as usize;
+            let name = buf.get(name_offset..).ok_or(ParseError::Truncated {
+                offset: name_offset,
+            })?;
+            Ok((id, read_name(name)))
+        })
+        .collect()
+}
+
+fn parse_body(ty: u32, body: &[u8]) -> Result<Block, ParseError> {
+    match ty {
+        PERF_SINGLE_COUNTER => {
+            let (value, _) = read_counter_data(body, 0)?;
+            Ok(Block::Single(value))
+        }
+        PERF_MULTIPLE_COUNTERS => {
+            let ids_end = read_size(body, 0, MULTI_COUNTERS_SIZE)?;
+            let count = read_u32(body, 4)?;
+
+            let mut counters = Vec::new();
+            let mut offset = ids_end;
+            for i in 0..count as usize {
+                let id = read_u32(body, MULTI_COUNTERS_SIZE + i * 4)?;
+                let (value, size) = read_counter_data(body, offset)?;
+                counters.push((id, value));
+                offset += size;
+            }
+            Ok(Block::Counters(counters))
+        }
+        PERF_MULTIPLE_INSTANCES => {
+            let count = read_u32(body, 4)?;
+
+            let mut instances = Vec::new();
+            let mut offset = MULTI_INSTANCES_SIZE;
+            for _ in 0..count {
+                let header_size = read_size(body, offset, INSTANCE_HEADER_SIZE)?;
+                let name = slice(body, offset, header_size)?;
+                let name = read_name(&name[INSTANCE_HEADER_SIZE..]);
+                offset += header_size;
+
+                let (value, size) = read_counter_data(body, offset)?;
+                instances.push((name, value));
+                offset += size;
+            }
+            Ok(Block::Instances(instances))
+        }
+        _ => Err(ParseError::UnsupportedType(ty)),
+    }
+}
+
+/// Read a `PERF_COUNTER_DATA` and its value, returning the value and the size of the whole structure.
+fn read_counter_data(buf: &[u8], offset: usize) -> Result<(u64, usize), ParseError> {
+    let data_size = read_u32(buf, offset)?;
+    let size = read_size(buf, offset + 4, COUNTER_DATA_SIZE)?;
+    let data = slice(buf, offset, size)?;
+    let value = match data[COUNTER_DATA_SIZE..].get(..data_size as usize) {
+        Some(&[a, b, c, d]) => u64::from(u32::from_le_bytes([a, b, c, d])),
+        Some(&[a, b, c, d, e, f, g, h]) => u64::from_le_bytes([a, b, c, d, e, f, g, h]),
+        Some(_) => return Err(ParseError::UnsupportedDataSize(data_size)),
+        None => return Err(ParseError::Truncated { offset }),
+    };
+    Ok((value, size))
+}
+
+/// Read a null-terminated (and possibly padded) UTF-16 name, e.g. of an instance, counter or counterset.
+pub fn read_name(buf: &[u8]) -> String {
+    let units = buf
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .take_while(|&unit| unit != 0)
+        .collect::<Vec<_>>();
+    String::from_utf16_lossy(&units)
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> Result<u32, ParseError> {
+    match slice(buf, offset, 4)? {
+        &[a, b, c, d] => Ok(u32::from_le_bytes([a, b, c, d])),
+        _ => unreachable!("slice has length 4"),
+    }
+}
+
+/// Read the size of a structure, which must be at least `min` to hold its own header.
+fn read_size(buf: &[u8], offset: usize, min: usize) -> Result<usize, ParseError> {
+    let size = read_u32(buf, offset)?;
+    if (size as usize) < min {
+        return Err(ParseError::InvalidSize { offset, size });
+    }
+    Ok(size as usize)
+}
+
+fn slice(buf: &[u8], offset: usize, len: usize) -> Result<&[u8], ParseError> {
+    offset
+        .checked_add(len)
+        .and_then(|end| buf.get(offset..end))
+        .ok_or(ParseError::Truncated { offset })
+}
+
+impl ParseError {
+    fn relative_to(self, base: usize) -> Self {
+        match self {
+            Self::Truncated { offset } => Self::Truncated {
+                offset: base + offset,
+            },
+            Self::InvalidSize { offset, size } => Self::InvalidSize {
+                offset: base + offset,
+                size,
+            },
+            e => e,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pad(mut bytes: Vec<u8>) -> Vec<u8> {
+        bytes.resize(bytes.len().next_multiple_of(8), 0);
+        bytes
+    }
+
+    fn u32s(values: &[u32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    fn results(blocks: &[Vec<u8>]) -> Vec<u8> {
+        let size = DATA_HEADER_SIZE + blocks.iter().map(Vec::len).sum::<usize>();
+        let mut buf = u32s(&[size as u32, blocks.len() as u32]);
+        buf.resize(DATA_HEADER_SIZE, 0);
+        buf.extend(blocks.concat());
+        buf
+    }
+
+    fn block(status: u32, ty: u32, body: Vec<u8>) -> Vec<u8> {
+        let mut block = u32s(&[status, ty, (COUNTER_HEADER_SIZE + body.len()) as u32, 0]);
+        block.extend(body);
+        block
+    }
+
+    fn counter_data(value: &[u8]) -> Vec<u8> {
+        let size = pad(value.to_vec()).len() + COUNTER_DATA_SIZE;
+        let mut data = u32s(&[value.len() as u32, size as u32]);
+        data.extend(pad(value.to_vec()));
+        data
+    }
+
+    fn utf16(name: &str) -> Vec<u8> {
+        name.encode_utf16()
+            .chain([0])
+            .flat_map(u16::to_le_bytes)
+            .collect()
+    }
+
+    fn instance(id: u32, name: &str, value: u64) -> Vec<u8> {
+        let name = pad(utf16(name));
+        let mut instance = u32s(&[(INSTANCE_HEADER_SIZE + name.len()) as u32, id]);
+        instance.extend(name);
+        instance.extend(counter_data(&value.to_le_bytes()));
+        instance
+    }
+
+    fn instances(instances: &[Vec<u8>]) -> Vec<u8> {
+        let size = MULTI_INSTANCES_SIZE + instances.iter().map(Vec::len).sum::<usize>();
+        let mut body = u32s(&[size as u32, instances.len() as u32]);
+        body.extend(instances.concat());
+        body
+    }
+
+    /// Same shape as the results of querying bytes read and written from "FileSystem Disk Activity" for all instances.
+    fn disk_activity() -> Vec<u8> {
+        results(&[
+            block(
+                0,
+                PERF_MULTIPLE_INSTANCES,
+                instances(&[
+                    instance(0, "default", 1_000_000),
+                    instance(1, "_Total", 1_000_000),
+                ]),
+            ),
+            block(
+                0,
+                PERF_MULTIPLE_INSTANCES,
+                instances(&[
+                    instance(0, "default", 5_000_000_000),
+                    instance(1, "_Total", 5_000_000_000),
+                ]),
+            ),
+        ])
+    }
+
+    #[test]
+    fn multiple_instances() {
+        assert_eq!(
+            parse(&disk_activity()),
+            Ok(vec![
+                Block::Instances(vec![
+                    ("default".to_owned(), 1_000_000),
+                    ("_Total".to_owned(), 1_000_000),
+                ]),
+                Block::Instances(vec![
+                    ("default".to_owned(), 5_000_000_000),
+                    ("_Total".to_owned(), 5_000_000_000),
+                ]),
+            ])
+        );
+    }
+
+    #[test]
+    fn single_and_multiple_counters() {
+        let mut counters = u32s(&[MULTI_COUNTERS_SIZE as u32 + 8, 2, 7, 9]);
+        counters.extend(counter_data(&42u32.to_le_bytes()));
+        counters.extend(counter_data(&43u64.to_le_bytes()));
+
+        let buf = results(&[
+            block(0, PERF_SINGLE_COUNTER, counter_data(&5u32.to_le_bytes())),
+            block(0, PERF_MULTIPLE_COUNTERS, counters),
+            // ERROR_FILE_NOT_FOUND
+            block(2, PERF_SINGLE_COUNTER, Vec::new()),
+        ]);
+
+        assert_eq!(
+            parse(&buf),
+            Ok(vec![
+                Block::Single(5),
+                Block::Counters(vec![(7, 42), (9, 43)]),
+                Block::Failed(2),
+            ])
+        );
+    }
+
+    #[test]
+    fn no_instances() {
+        let buf = results(&[block(0, PERF_MULTIPLE_INSTANCES, instances(&[]))]);
+        assert_eq!(parse(&buf), Ok(vec![Block::Instances(Vec::new())]));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse(&[]), Err(ParseError::Truncated { offset: 0 }));
+
+        let buf = results(&[block(0, 6, Vec::new())]);
+        assert_eq!(parse(&buf), Err(ParseError::UnsupportedType(6)));
+
+        let buf = results(&[block(0, PERF_SINGLE_COUNTER, counter_data(&[1, 2]))]);
+        assert_eq!(parse(&buf), Err(ParseError::UnsupportedDataSize(2)));
+
+        // An instance header which claims to be smaller than itself would otherwise loop forever.
+        let mut buf = disk_activity();
+        let first_instance = DATA_HEADER_SIZE + COUNTER_HEADER_SIZE + MULTI_INSTANCES_SIZE;
+        buf[first_instance..first_instance + 4].copy_from_slice(&0u32.to_le_bytes());
+        assert_eq!(
+            parse(&buf),
+            Err(ParseError::InvalidSize {
+                offset: first_instance,
+                size: 0
+            })
+        );
+    }
+
+    /// Same shape as the English counter names of "FileSystem Disk Activity".
+    fn disk_activity_names() -> Vec<u8> {
+        let names = [
+            utf16("FileSystem Bytes Read"),
+            utf16("FileSystem Bytes Written"),
+        ];
+        let strings_offset = STRING_BUFFER_HEADER_SIZE + 2 * STRING_COUNTER_HEADER_SIZE;
+        let size = strings_offset + names.iter().map(Vec::len).sum::<usize>();
+        let mut buf = u32s(&[
+            size as u32,
+            2,
+            0,
+            strings_offset as u32,
+            1,
+            (strings_offset + names[0].len()) as u32,
+        ]);
+        buf.extend(names.concat());
+        buf
+    }
+
+    #[test]
+    fn counter_names() {
+        assert_eq!(
+            parse_counter_names(&disk_activity_names()),
+            Ok(vec![
+                (0, "FileSystem Bytes Read".to_owned()),
+                (1, "FileSystem Bytes Written".to_owned()),
+            ])
+        );
+
+        let buf = u32s(&[STRING_BUFFER_HEADER_SIZE as u32, 0]);
+        assert_eq!(parse_counter_names(&buf), Ok(Vec::new()));
+
+        // A string offset past the end of the buffer.
+        let mut buf = disk_activity_names();
+        buf[12..16].copy_from_slice(&1000u32.to_le_bytes());
+        assert_eq!(
+            parse_counter_names(&buf),
+            Err(ParseError::Truncated { offset: 1000 })
+        );
+
+        assert_eq!(
+            read_name(&utf16("FileSystem Disk Activity")),
+            "FileSystem Disk Activity"
+        );
+    }
+
+    #[test]
+    fn truncated_or_corrupted_buffers_are_errors() {
+        let buf = disk_activity();
+
+        // Shortened buffers, without fixing up the total size.
+        for len in 0..buf.len() {
+            assert!(parse(&buf[..len]).is_err(), "length {len}");
+        }
+
+        // Every byte set to a few interesting values, which must not panic.
+        for i in 0..buf.len() {
+            for byte in [0x00, 0x01, 0x7f, 0xff] {
+                let mut corrupted = buf.clone();
+                corrupted[i] = byte;
+                let _ = parse(&corrupted);
+            }
+        }
+
+        let buf = disk_activity_names();
+        for len in 0..buf.len() {
+            assert!(parse_counter_names(&buf[..len]).is_err(), "length {len}");
+        }
+        for i in 0..buf.len() {
+            for byte in [0x00, 0x01, 0x7f, 0xff] {
+                let mut corrupted = buf.clone();
+                corrupted[i] = byte;
+                let _ = parse_counter_names(&corrupted);
+            }
+        }
+    }
+}
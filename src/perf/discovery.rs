@@ -0,0 +1,121 @@
+use crate::perf::{data, names};
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use windows::Win32::Foundation::{
+    ERROR_INVALID_DATA, ERROR_NOT_ENOUGH_MEMORY, ERROR_NOT_FOUND, ERROR_SUCCESS, WIN32_ERROR,
+};
+use windows::Win32::System::Performance::{
+    PERF_REG_COUNTER_ENGLISH_NAMES, PERF_REG_COUNTERSET_ENGLISH_NAME, PerfEnumerateCounterSet,
+    PerfQueryCounterSetRegistrationInfo, PerfRegInfoType,
+};
+use windows::core::{Error, GUID, Result};
+
+/// English names of every counterset, and of the counters in each counterset that's been looked up.
+///
+/// These only change when perf providers are installed or removed, so they're only read once.
+#[derive(Default)]
+struct Registry {
+    countersets: Option<Vec<(GUID, String)>>,
+    counters: HashMap<GUID, Vec<(u32, String)>>,
+}
+
+static REGISTRY: Mutex<Option<Registry>> = Mutex::new(None);
+
+/// Find a counterset and the IDs of some of its counters from their English names.
+///
+/// Unlike the IDs, which are only stable as long as the provider doesn't change them,
+/// and the localized names, which depend on the display language, the English names are the same everywhere.
+/// (These are the names in e.g. `\FileSystem Disk Activity(*)\FileSystem Bytes Read`.)
+pub fn find_counters<const N: usize>(
+    counterset: &str,
+    counters: [&str; N],
+) -> Result<(GUID, [u32; N])> {
+    let mut registry = REGISTRY.lock().unwrap_or_else(PoisonError::into_inner);
+    let registry = registry.get_or_insert_default();
+
+    if registry.countersets.is_none() {
+        registry.countersets = Some(read_counterset_names()?);
+    }
+    let countersets = registry.countersets.as_deref().unwrap_or_default();
+    let id = names::find_counterset(countersets, counterset).map_err(not_found)?;
+
+    if !registry.counters.contains_key(&id) {
+        let buf = query_registration_info(&id, PERF_REG_COUNTER_ENGLISH_NAMES)?;
+        let counter_names = data::parse_counter_names(&buf)
+            .map_err(|e| Error::new(ERROR_INVALID_DATA.to_hresult(), e.to_string()))?;
+        registry.counters.insert(id, counter_names);
+    }
+    let counter_names = &registry.counters[&id];
+    let counter_ids =
+        names::find_counters(counterset, counter_names, counters).map_err(not_found)?;
+
+    Ok((id, counter_ids))
+}
+
+/// Read the English name of every counterset that's registered on this machine.
+fn read_counterset_names() -> Result<Vec<(GUID, String)>> {
+    let mut ids = Vec::new();
+    loop {
+        let mut count = 0;
+        // SAFETY: `count` is valid for writes
+        let result = WIN32_ERROR(unsafe {
+            PerfEnumerateCounterSet(
+                None,
+                (!ids.is_empty()).then_some(ids.as_mut_slice()),
+                &mut count,
+            )
+        });
+        match result {
+            ERROR_SUCCESS => {
+                ids.truncate(count as usize);
+                break;
+            }
+            // Providers can also be registered before the next call, in which case we need to ask again.
+            ERROR_NOT_ENOUGH_MEMORY => ids.resize(count as usize, GUID::zeroed()),
+            e => return Err(e.into()),
+        }
+    }
+
+    let mut countersets = Vec::with_capacity(ids.len());
+    for id in ids {
+        // Providers can be registered without their strings being installed, so just skip those.
+        match query_registration_info(&id, PERF_REG_COUNTERSET_ENGLISH_NAME) {
+            Ok(name) => countersets.push((id, data::read_name(&name))),
+            Err(e) => log::debug!("Failed to get the name of counterset {id:?}: {e}"),
+        }
+    }
+
+    Ok(countersets)
+}
+
+/// Query registration info for a counterset, returning the buffer it was written to.
+fn query_registration_info(id: &GUID, request: PerfRegInfoType) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    loop {
+        let mut size = 0;
+        // SAFETY: `id` is a valid GUID; `size` is valid for writes.
+        // The language is ignored when requesting English names.
+        let result = WIN32_ERROR(unsafe {
+            PerfQueryCounterSetRegistrationInfo(
+                None,
+                id,
+                request,
+                0,
+                (!buf.is_empty()).then_some(buf.as_mut_slice()),
+                &mut size,
+            )
+        });
+        match result {
+            ERROR_SUCCESS if !buf.is_empty() || size == 0 => {
+                buf.truncate(size as usize);
+                return Ok(buf);
+            }
+            ERROR_SUCCESS | ERROR_NOT_ENOUGH_MEMORY => buf.resize(size as usize, 0),
+            e => return Err(e.into()),
+        }
+    }
+}
+
+fn not_found(e: names::NotFound) -> Error {
+    Error::new(ERROR_NOT_FOUND.to_hresult(), e.to_string())
+}
@@ -0,0 +1,128 @@
+use std::error::Error;
+use std::fmt::{self, Display};
+
+/// A counterset or counter which isn't registered on this machine.
+#[derive(Debug, PartialEq)]
+pub enum NotFound {
+    Counterset(String),
+    Counter {
+        counterset: String,
+        counter: String,
+        /// The names of the counters which the counterset does have, to help fix typos.
+        available: Vec<String>,
+    },
+}
+
+impl Display for NotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Counterset(name) => write!(
+                f,
+                "perf counterset \"{name}\" not found (names must be in English, even on localized systems)"
+            ),
+            Self::Counter {
+                counterset,
+                counter,
+                available,
+            } => write!(
+                f,
+                "perf counter \"{counter}\" not found in counterset \"{counterset}\" (available: {})",
+                available.join(", ")
+            ),
+        }
+    }
+}
+
+impl Error for NotFound {}
+
+/// Find a counterset's ID by its English name.
+///
+/// Names are compared ignoring case, like perfmon and typeperf do.
+pub fn find_counterset<Id: Copy>(countersets: &[(Id, String)], name: &str) -> Result<Id, NotFound> {
+    find(countersets, name).ok_or_else(|| NotFound::Counterset(name.to_owned()))
+}
+
+/// Find the IDs of some of a counterset's counters by their English names, in the same order.
+pub fn find_counters<const N: usize>(
+    counterset: &str,
+    counters: &[(u32, String)],
+    names: [&str; N],
+) -> Result<[u32; N], NotFound> {
+    let mut ids = [0; N];
+    for (id, name) in ids.iter_mut().zip(names) {
+        *id = find(counters, name).ok_or_else(|| NotFound::Counter {
+            counterset: counterset.to_owned(),
+            counter: name.to_owned(),
+            available: counters.iter().map(|(_, name)| name.clone()).collect(),
+        })?;
+    }
+    Ok(ids)
+}
+
+fn find<Id: Copy>(items: &[(Id, String)], name: &str) -> Option<Id> {
+    items
+        .iter()
+        .find(|(_, item)| item.eq_ignore_ascii_case(name))
+        .map(|&(id, _)| id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named<Id>(items: impl IntoIterator<Item = (Id, &'static str)>) -> Vec<(Id, String)> {
+        items
+            .into_iter()
+            .map(|(id, name)| (id, name.to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn countersets() {
+        let countersets = named([
+            (10, "Processor Information"),
+            (20, "FileSystem Disk Activity"),
+        ]);
+
+        assert_eq!(
+            find_counterset(&countersets, "FileSystem Disk Activity"),
+            Ok(20)
+        );
+        assert_eq!(
+            find_counterset(&countersets, "filesystem disk activity"),
+            Ok(20)
+        );
+        assert_eq!(
+            find_counterset(&countersets, "GPU Engine"),
+            Err(NotFound::Counterset("GPU Engine".to_owned()))
+        );
+    }
+
+    #[test]
+    fn counters() {
+        let counters = named([
+            (0, "FileSystem Bytes Read"),
+            (1, "FileSystem Bytes Written"),
+        ]);
+
+        assert_eq!(
+            find_counters(
+                "FileSystem Disk Activity",
+                &counters,
+                ["FileSystem Bytes Written", "filesystem bytes read"]
+            ),
+            Ok([1, 0])
+        );
+        assert_eq!(
+            find_counters("FileSystem Disk Activity", &counters, []),
+            Ok([])
+        );
+
+        let e = find_counters("FileSystem Disk Activity", &counters, ["Bytes Read"]).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "perf counter \"Bytes Read\" not found in counterset \"FileSystem Disk Activity\" \
+             (available: FileSystem Bytes Read, FileSystem Bytes Written)"
+        );
+    }
+}
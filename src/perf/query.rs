@@ -0,0 +1,281 @@
+use crate::perf::data::{self, Block};
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+use std::mem;
+use std::slice;
+use windows::Win32::Foundation::{
+    ERROR_INVALID_DATA, ERROR_NOT_ENOUGH_MEMORY, ERROR_SUCCESS, HANDLE, WIN32_ERROR,
+};
+use windows::Win32::System::Performance::{
+    PERF_COUNTER_IDENTIFIER, PERF_DATA_HEADER, PERF_MULTIPLE_INSTANCES, PERF_WILDCARD_COUNTER,
+    PerfAddCounters, PerfCloseQueryHandle, PerfCounterDataType, PerfOpenQueryHandle,
+    PerfQueryCounterData, PerfQueryCounterInfo,
+};
+use windows::core::{Error, GUID, Result};
+
+/// Represents the type of data that will be fetched from a performance counter,
+/// which impacts the memory layout of the blocks that will be generated by PerfQueryCounterData.
+/// Corresponds to the PerfCounterDataType enum.
+///
+/// Workaround for lack of const generic enum variants.
+pub trait PerfCounterType {
+    const TYPE: PerfCounterDataType;
+}
+
+/// Represents PERF_MULTIPLE_INSTANCES, i.e. every instance of each counter.
+pub struct MultiCounter;
+
+impl PerfCounterType for MultiCounter {
+    const TYPE: PerfCounterDataType = PERF_MULTIPLE_INSTANCES;
+}
+
+/// Represents an open performance query handle.
+/// Can be repeatedly queried to get perf data.
+pub struct PerfQueries<Type, const COUNTERS: usize, CounterValue>
+where
+    Type: PerfCounterType,
+    CounterValue: Copy + Default,
+{
+    /// The handle to the performance query.
+    // SAFETY: must not be modified or dropped until this struct is dropped.
+    handle: HANDLE,
+    /// Indexes of the counters in query results (since for some reason this is not guaranteed)
+    counter_indexes: [u32; COUNTERS],
+    /// The structore of data that will be returned by this perf query (e.g. single or multi value).
+    _type: PhantomData<Type>,
+    /// The type of data that will be fetched from this handle.
+    /// Usually u64 or u32.
+    _value: PhantomData<CounterValue>,
+}
+
+impl<Type, const COUNTERS: usize, CounterValue> Drop for PerfQueries<Type, COUNTERS, CounterValue>
+where
+    Type: PerfCounterType,
+    CounterValue: Copy + Default,
+{
+    fn drop(&mut self) {
+        // SAFETY: handle is valid and hasn't been closed due to our safety invariant.
+        if let Err(e) = unsafe { WIN32_ERROR(PerfCloseQueryHandle(self.handle)).ok() } {
+            log::error!("Failed to close PerfQueryHandle: {e}");
+        }
+    }
+}
+
+impl<Type, const COUNTERS: usize, CounterValue> PerfQueries<Type, COUNTERS, CounterValue>
+where
+    Type: PerfCounterType,
+    CounterValue: Copy + Default,
+{
+    /// Query the given counterset, for the given counter ids, filtered to the given instance name filter.
+    fn open<const N: usize>(
+        counterset: GUID,
+        counter_ids: &[u32; COUNTERS],
+        instance_name_filter: &[u8; N],
+    ) -> Result<Self> {
+        let instance_name_filter = instance_name_filter.map(|c| {
+            let mut one_char = [0; 1];
+            let c = char::from_u32(u32::from(c))
+                .unwrap_or_else(|| panic!("Filter string must be valid UTF-8"));
+            c.encode_utf16(&mut one_char);
+            one_char[0]
+        });
+
+        // Create handle to hold counters which we will repeatedly query.
+        let handle = {
+            let mut handle = HANDLE::default();
+            // SAFETY: handle is a valid pointer to PerfQueryHandle
+            unsafe { WIN32_ERROR(PerfOpenQueryHandle(None, &mut handle)).ok()? };
+            handle
+        };
+
+        // Create instance right after handle so the handle will be dropped if we error.
+        let mut queries = PerfQueries {
+            handle,
+            counter_indexes: [0; COUNTERS], // will be filled in later
+            _type: PhantomData,
+            _value: PhantomData,
+        };
+
+        // Add counters to the query handle.
+
+        #[repr(C)]
+        #[repr(align(8))]
+        struct PERF_COUNTER_IDENTIFIER_WITH_NAME<const N: usize> {
+            identifier: PERF_COUNTER_IDENTIFIER,
+            name_filter: [u16; N],
+            null: u16,
+        }
+
+        let mut counters = counter_ids.map(|counter_id| PERF_COUNTER_IDENTIFIER_WITH_NAME {
+            identifier: PERF_COUNTER_IDENTIFIER {
+                CounterSetGuid: counterset,
+                Size: mem::size_of::<PERF_COUNTER_IDENTIFIER_WITH_NAME<N>>()
+                    .try_into()
+                    .unwrap(),
+                CounterId: counter_id,
+                // Note that, per https://learn.microsoft.com/en-us/windows/win32/api/perflib/ns-perflib-perf_instance_header#remarks,
+                // each instance is identified by _both_ its instance id and name combined...
+                // In practice, I do see duplicate instance IDs frequently, but I don't see duplicate names,
+                // so we use the wildcard instance ID here and only filter on the name (below).
+                InstanceId: PERF_WILDCARD_COUNTER,
+                ..Default::default()
+            },
+            name_filter: instance_name_filter,
+            null: 0,
+        });
+        let counters_size = mem::size_of_val(&counters).try_into().unwrap();
+
+        // SAFETY: handle is valid, counters matches the defined layout for PERF_COUNTER_IDENTIFIER blocks.
+        // https://learn.microsoft.com/en-us/windows/win32/api/perflib/ns-perflib-perf_counter_identifier
+        unsafe {
+            WIN32_ERROR(PerfAddCounters(
+                handle,
+                counters.as_mut_ptr().cast::<PERF_COUNTER_IDENTIFIER>(),
+                counters_size,
+            ))
+            .ok()?
+        };
+
+        // Consume status from adding each identifier.
+        for counter in &counters {
+            WIN32_ERROR(counter.identifier.Status).ok()?;
+        }
+
+        // Populate query indexes for the counters.
+        // (For some reason data is not always returned in an order matching the order queries were added.)
+
+        unsafe {
+            WIN32_ERROR(PerfQueryCounterInfo(
+                handle,
+                Some(counters.as_mut_ptr().cast::<PERF_COUNTER_IDENTIFIER>()),
+                counters_size,
+                &mut 0,
+            ))
+            .ok()?
+        };
+
+        queries.counter_indexes = counters.map(|counter| counter.identifier.Index);
+
+        Ok(queries)
+    }
+}
+
+impl<const COUNTERS: usize> PerfQueries<MultiCounter, COUNTERS, u64> {
+    /// Query the given counterset, for the given counter ids, for all instances.
+    pub fn new_all_instances(counterset: GUID, counter_ids: &[u32; COUNTERS]) -> Result<Self> {
+        Self::open(counterset, counter_ids, b"*")
+    }
+
+    /// Query data from perf counters, returning the values of each counter by instance name.
+    ///
+    /// Instances which have the same name (which is allowed, since instances are identified by name and ID) are summed.
+    /// Counters from single-instance countersets are returned under an empty name.
+    pub fn query_data(&self) -> Result<BTreeMap<String, [u64; COUNTERS]>> {
+        let blocks = self.query_blocks()?;
+
+        let mut values = BTreeMap::<String, [u64; COUNTERS]>::new();
+        for (i, &index) in self.counter_indexes.iter().enumerate() {
+            let Some(block) = blocks.get(index as usize) else {
+                return Err(Error::new(
+                    ERROR_INVALID_DATA.to_hresult(),
+                    format!("missing results for counter {i}"),
+                ));
+            };
+            match block {
+                Block::Failed(status) => WIN32_ERROR(*status).ok()?,
+                Block::Single(value) => values.entry(String::new()).or_default()[i] = *value,
+                Block::Instances(instances) => {
+                    for (name, value) in instances {
+                        let sum = &mut values.entry(name.clone()).or_default()[i];
+                        *sum = sum.wrapping_add(*value);
+                    }
+                }
+                // Only returned when querying every counter in a counterset, but we add counters by ID.
+                Block::Counters(_) => {
+                    return Err(Error::new(
+                        ERROR_INVALID_DATA.to_hresult(),
+                        format!("unexpected multiple counters for counter {i}"),
+                    ));
+                }
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// Query data from perf counters, returning the values of each counter for each instance, in the order returned.
+    ///
+    /// Unlike `query_data`, instances with the same name are kept apart, e.g. to tell processes apart by their ID.
+    pub fn query_instances(&self) -> Result<Vec<(String, [u64; COUNTERS])>> {
+        let blocks = self.query_blocks()?;
+
+        let mut values = Vec::<(String, [u64; COUNTERS])>::new();
+        for (i, &index) in self.counter_indexes.iter().enumerate() {
+            let instances = match blocks.get(index as usize) {
+                Some(Block::Failed(status)) => return Err(WIN32_ERROR(*status).into()),
+                Some(Block::Instances(instances)) => instances,
+                _ => {
+                    return Err(Error::new(
+                        ERROR_INVALID_DATA.to_hresult(),
+                        format!("missing instances for counter {i}"),
+                    ));
+                }
+            };
+            if i == 0 {
+                values = instances
+                    .iter()
+                    .map(|(name, _)| (name.clone(), [0; COUNTERS]))
+                    .collect();
+            }
+            // Each counter lists the same instances in the same order, since they're all from the same snapshot.
+            if instances.len() != values.len()
+                || instances.iter().zip(&values).any(|((a, _), (b, _))| a != b)
+            {
+                return Err(Error::new(
+                    ERROR_INVALID_DATA.to_hresult(),
+                    format!("instances of counter {i} don't match the first counter"),
+                ));
+            }
+            for ((_, value), (_, values)) in instances.iter().zip(&mut values) {
+                values[i] = *value;
+            }
+        }
+
+        Ok(values)
+    }
+
+    fn query_blocks(&self) -> Result<Vec<Block>> {
+        // The number of instances varies, so ask how big of a buffer we need first.
+        // Instances can also be added before the next call, in which case we need to ask again.
+        // Use u64s so the buffer is aligned like PERF_DATA_HEADER.
+        let mut buf = Vec::<u64>::new();
+        let mut size = 0;
+        loop {
+            let buf_size = mem::size_of_val(buf.as_slice()).try_into().unwrap();
+            // SAFETY: `handle` is valid; `buf` is valid for writes of `buf_size` bytes
+            let result = WIN32_ERROR(unsafe {
+                PerfQueryCounterData(
+                    self.handle,
+                    (!buf.is_empty()).then(|| buf.as_mut_ptr().cast::<PERF_DATA_HEADER>()),
+                    buf_size,
+                    &mut size,
+                )
+            });
+            match result {
+                // A size of zero would never fill the buffer, so stop, and let parsing reject the empty result.
+                ERROR_SUCCESS if !buf.is_empty() || size == 0 => break,
+                ERROR_SUCCESS | ERROR_NOT_ENOUGH_MEMORY => {
+                    buf = vec![0; (size as usize).div_ceil(mem::size_of::<u64>())];
+                }
+                e => return Err(e.into()),
+            }
+        }
+
+        // SAFETY: `buf` is valid for reads of its whole size, and any bytes are valid u8s
+        let bytes = unsafe {
+            slice::from_raw_parts(buf.as_ptr().cast::<u8>(), mem::size_of_val(buf.as_slice()))
+        };
+        data::parse(&bytes[..size as usize])
+            .map_err(|e| Error::new(ERROR_INVALID_DATA.to_hresult(), e.to_string()))
+    }
+}
@@ -0,0 +1,240 @@
+use crate::constants::{
+    CPU_CORE_BAR_COLOR, CPU_CORE_TRACK_COLOR, DEBUG_BACKGROUND_COLOR, MICROPHONE_WARNING_COLOR,
+    TEXT_COLOR, THROTTLE_WARNING_COLOR,
+};
+use crate::layout::{Align, Fill, Item, Options, Rect, Values, layout};
+use crate::metrics::{Battery, PowerState};
+use crate::render::font::{ADVANCE, GLYPH_HEIGHT, GLYPH_WIDTH, glyph};
+use crate::utils::{Rgb, ScaleBy, ScalingFactor, Unscaled};
+
+mod font;
+mod png;
+
+/// Size of the rendered band, which is normally determined by the taskbar.
+const WIDTH: Unscaled<i32> = Unscaled::new(300);
+const HEIGHT: Unscaled<i32> = Unscaled::new(48);
+
+/// Fixed metric values, so that renders are reproducible.
+pub fn fixture_values() -> Values {
+    Values {
+        cpu: 6.4,
+        cpu_frequency: Some(1234.0),
+        mem: 41.5,
+        swap: 12.6,
+        mem_pressure: Some(3.2),
+        net: 12.2,
+        net_received: 9.4,
+        net_sent: 2.8,
+        dsk: 1234.0,
+        dsk_read: 1000.0,
+        dsk_written: 234.0,
+        // One pegged core
+        cpu_cores: vec![3.0, 100.0, 12.0, 0.0, 45.0, 7.0, 2.0, 60.0],
+        extra_cells: Vec::new(),
+        battery: Some(Battery {
+            percent: 42.0,
+            state: PowerState::Discharging,
+            seconds_remaining: Some(65 * 60),
+        }),
+    }
+}
+
+/// 8-bit RGBA pixels, in rows from top to bottom, initially transparent.
+pub struct Image {
+    width: i32,
+    height: i32,
+    pixels: Vec<[u8; 4]>,
+}
+
+impl Image {
+    fn new(width: i32, height: i32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![[0; 4]; (width * height) as usize],
+        }
+    }
+
+    /// Fill a rectangle with an opaque color, clipped to the image.
+    fn fill(&mut self, rect: Rect, color: Rgb) {
+        let Rgb(r, g, b) = color;
+        for y in rect.top.max(0)..rect.bottom.min(self.height) {
+            for x in rect.left.max(0)..rect.right.min(self.width) {
+                self.pixels[(y * self.width + x) as usize] = [r, g, b, 0xff];
+            }
+        }
+    }
+
+    pub fn to_png(&self) -> Vec<u8> {
+        png::encode(
+            self.width as u32,
+            self.height as u32,
+            self.pixels.as_flattened(),
+        )
+    }
+}
+
+/// Render the band with fixture metrics, using the same layout as the window, but a bundled bitmap font.
+pub fn render(dpi: ScalingFactor, options: Options, is_muted: bool, debug: bool) -> Image {
+    let width = WIDTH.scale_by(dpi);
+    let height = HEIGHT.scale_by(dpi);
+
+    let mut image = Image::new(width, height);
+
+    let values = fixture_values();
+
+    for item in layout(width, height, dpi, &values, options, is_muted, debug) {
+        match item {
+            Item::Rect { rect, fill } => {
+                let color = match fill {
+                    Fill::DebugBackground => DEBUG_BACKGROUND_COLOR,
+                    Fill::MicrophoneWarning => MICROPHONE_WARNING_COLOR,
+                    Fill::ThrottleWarning => THROTTLE_WARNING_COLOR,
+                    Fill::CpuCoreTrack => CPU_CORE_TRACK_COLOR,
+                    Fill::CpuCoreBar => CPU_CORE_BAR_COLOR,
+                };
+                image.fill(rect, color);
+            }
+            Item::Text { text, x, y, align } => draw_text(&mut image, dpi, &text, x, y, align),
+        }
+    }
+
+    image
+}
+
+fn draw_text(image: &mut Image, dpi: ScalingFactor, text: &str, x: i32, y: i32, align: Align) {
+    let chars = text.chars().count() as i32;
+    // No spacing after the last glyph.
+    let text_width = (chars * ADVANCE - 1).max(0).scale_by(dpi);
+    let text_height = GLYPH_HEIGHT.scale_by(dpi);
+
+    let left = match align {
+        Align::Left => x,
+        Align::Center => x - text_width / 2,
+        Align::Right => x - text_width,
+    };
+    let top = y - text_height / 2;
+
+    for (i, c) in text.chars().enumerate() {
+        let origin = i as i32 * ADVANCE;
+        for (row, bits) in (0..).zip(glyph(c)) {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) == 0 {
+                    continue;
+                }
+                // Scale pixel edges rather than sizes, so that glyphs tile without gaps at fractional scales.
+                image.fill(
+                    Rect {
+                        left: left + (origin + col).scale_by(dpi),
+                        top: top + row.scale_by(dpi),
+                        right: left + (origin + col + 1).scale_by(dpi),
+                        bottom: top + (row + 1).scale_by(dpi),
+                    },
+                    TEXT_COLOR,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opt::{DiskDisplay, NetworkDisplay};
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Compare a render against a checked-in PNG.
+    ///
+    /// Run with `UPDATE_GOLDEN=1` to overwrite the golden images instead, after checking the changes by eye.
+    fn check_golden(name: &str, scale_percent: u32, options: Options, is_muted: bool) {
+        let dpi = ScalingFactor::from_ratio(scale_percent, 100);
+        let actual = render(dpi, options, is_muted, false).to_png();
+
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata/golden")
+            .join(format!("{name}.png"));
+
+        if env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, &actual).unwrap();
+            return;
+        }
+
+        let expected =
+            fs::read(&path).unwrap_or_else(|e| panic!("Failed to read `{}`: {e}", path.display()));
+        assert!(
+            actual == expected,
+            "Render differs from `{}`, rerun with UPDATE_GOLDEN=1 to update it",
+            path.display()
+        );
+    }
+
+    #[test]
+    fn golden_muted_at_100_percent() {
+        check_golden("band-100-muted", 100, Options::default(), true);
+    }
+
+    #[test]
+    fn golden_unmuted_at_150_percent() {
+        check_golden("band-150", 150, Options::default(), false);
+    }
+
+    #[test]
+    fn golden_unmuted_at_200_percent() {
+        check_golden("band-200", 200, Options::default(), false);
+    }
+
+    #[test]
+    fn golden_cpu_cores_at_150_percent() {
+        let options = Options {
+            cpu_cores: true,
+            ..Default::default()
+        };
+        check_golden("band-150-cpu-cores", 150, options, true);
+    }
+
+    #[test]
+    fn golden_split_at_150_percent() {
+        let options = Options {
+            cpu_cores: true,
+            network: NetworkDisplay::Both,
+            disk: DiskDisplay::Both,
+            ..Default::default()
+        };
+        check_golden("band-150-split", 150, options, true);
+    }
+
+    #[test]
+    fn golden_cpu_frequency_warning_at_150_percent() {
+        let options = Options {
+            cpu_frequency: true,
+            cpu_frequency_warning_ghz: Some(2.0),
+            ..Default::default()
+        };
+        check_golden("band-150-cpu-frequency", 150, options, true);
+    }
+
+    #[test]
+    fn text_alignment() {
+        let dpi = ScalingFactor::ONE;
+        let lit = |image: &Image| {
+            (0..image.height)
+                .flat_map(|y| (0..image.width).map(move |x| (x, y)))
+                .filter(|&(x, y)| image.pixels[(y * image.width + x) as usize][3] != 0)
+                .collect::<Vec<_>>()
+        };
+        let bounds = |align| {
+            let mut image = Image::new(40, 20);
+            // "-" is a single full-width row, in the middle of the glyph.
+            draw_text(&mut image, dpi, "--", 20, 10, align);
+            let lit = lit(&image);
+            (lit.first().copied().unwrap(), lit.last().copied().unwrap())
+        };
+
+        assert_eq!(bounds(Align::Left), ((20, 10), (30, 10)));
+        assert_eq!(bounds(Align::Center), ((15, 10), (25, 10)));
+        assert_eq!(bounds(Align::Right), ((9, 10), (19, 10)));
+    }
+}
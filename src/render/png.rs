@@ -0,0 +1,209 @@
+// Minimal PNG encoder, so we don't need an image library just to write test images.
+//
+// The images are mostly solid colors, so compression is just run-length encoding,
+// using deflate's fixed Huffman codes and back-references with a distance of one.
+
+/// Encode 8-bit RGBA pixels, in rows from top to bottom, as a PNG file.
+pub fn encode(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let stride = width as usize * 4;
+    assert_eq!(rgba.len(), stride * height as usize);
+
+    // https://www.w3.org/TR/png/#9Filter-type-1-Sub
+    // Solid runs of any color become runs of zeros.
+    let mut filtered = Vec::with_capacity(rgba.len() + height as usize);
+    for row in rgba.chunks_exact(stride.max(1)).take(height as usize) {
+        filtered.push(1);
+        filtered.extend(
+            row.iter()
+                .enumerate()
+                .map(|(i, &byte)| byte.wrapping_sub(if i >= 4 { row[i - 4] } else { 0 })),
+        );
+    }
+
+    let mut header = Vec::new();
+    header.extend(width.to_be_bytes());
+    header.extend(height.to_be_bytes());
+    header.extend([
+        8, // bit depth
+        6, // color type: RGBA
+        0, // compression method: deflate
+        0, // filter method: adaptive
+        0, // interlace method: none
+    ]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib(&filtered));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    png.extend(kind);
+    png.extend(data);
+    png.extend(crc32(&[kind, data]).to_be_bytes());
+}
+
+/// https://www.rfc-editor.org/rfc/rfc1950
+fn zlib(data: &[u8]) -> Vec<u8> {
+    // Deflate, with a 32K window and no preset dictionary; the check bits make the header a multiple of 31.
+    let mut out = vec![0x78, 0x01];
+    out.extend(deflate(data));
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+/// https://www.rfc-editor.org/rfc/rfc1951
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut out = BitWriter::default();
+
+    // Single final block, with fixed Huffman codes.
+    out.bits(1, 1);
+    out.bits(0b01, 2);
+
+    let mut i = 0;
+    while i < data.len() {
+        if i > 0 {
+            let run = data[i..]
+                .iter()
+                .take(258)
+                .take_while(|&&byte| byte == data[i - 1])
+                .count();
+            if run >= 3 {
+                out.length(run as u16);
+                // Distance code 0 (a distance of one), with no extra bits.
+                out.huffman(0, 5);
+                i += run;
+                continue;
+            }
+        }
+        out.literal(data[i].into());
+        i += 1;
+    }
+
+    out.literal(256); // end of block
+    out.finish()
+}
+
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    /// Write `count` bits of `value`, least significant bit first.
+    fn bits(&mut self, value: u32, count: u32) {
+        self.buffer |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Write a Huffman code, which is packed most significant bit first.
+    fn huffman(&mut self, code: u32, len: u32) {
+        self.bits(code.reverse_bits() >> (32 - len), len);
+    }
+
+    /// Write a literal/length symbol using the fixed Huffman code.
+    fn literal(&mut self, symbol: u16) {
+        let symbol = u32::from(symbol);
+        match symbol {
+            0..=143 => self.huffman(0b0011_0000 + symbol, 8),
+            144..=255 => self.huffman(0b1_1001_0000 + symbol - 144, 9),
+            256..=279 => self.huffman(symbol - 256, 7),
+            _ => self.huffman(0b1100_0000 + symbol - 280, 8),
+        }
+    }
+
+    /// Write a match length, between 3 and 258.
+    fn length(&mut self, length: u16) {
+        const BASES: [u16; 29] = [
+            3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99,
+            115, 131, 163, 195, 227, 258,
+        ];
+        let index = BASES.iter().rposition(|&base| base <= length).unwrap();
+        let extra_bits = match index {
+            0..8 | 28 => 0,
+            _ => (index as u32 - 4) / 4,
+        };
+        self.literal(257 + index as u16);
+        self.bits(u32::from(length - BASES[index]), extra_bits);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.out
+    }
+}
+
+fn crc32(parts: &[&[u8]]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut n = 0;
+        while n < 256 {
+            let mut c = n as u32;
+            let mut k = 0;
+            while k < 8 {
+                c = if c & 1 != 0 {
+                    0xedb88320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                };
+                k += 1;
+            }
+            table[n] = c;
+            n += 1;
+        }
+        table
+    };
+
+    let mut crc = !0u32;
+    for &byte in parts.iter().copied().flatten() {
+        crc = TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(&[b"123456789"]), 0xcbf43926);
+        assert_eq!(crc32(&[b"1234", b"56789"]), 0xcbf43926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+    }
+
+    #[test]
+    fn length_codes() {
+        let symbols = |length| {
+            let mut out = BitWriter::default();
+            out.length(length);
+            out.finish()
+        };
+        // Lengths 3 and 10 have no extra bits; 11 and 12 share a code, distinguished by one extra bit.
+        assert_eq!(symbols(3), [0b0100_0000]);
+        assert_eq!(symbols(11), [0b0100_1000]);
+        assert_eq!(symbols(12), [0b1100_1000]);
+        // 258 has its own code, with no extra bits.
+        assert_eq!(symbols(258), [0b1010_0011]);
+    }
+}
@@ -0,0 +1,87 @@
+use crate::clock::Clock;
+use crate::constants::{FETCH_TIMER_MS, REDRAW_EVERY_N_FETCHES};
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+const FETCH_INTERVAL: Duration = Duration::from_millis(FETCH_TIMER_MS as u64);
+
+/// Whether to redraw after the given fetch, i.e. every `REDRAW_EVERY_N_FETCHES` fetches.
+pub fn is_redraw_due(fetch_count: usize) -> bool {
+    fetch_count.is_multiple_of(REDRAW_EVERY_N_FETCHES)
+}
+
+/// Fetch timing for frontends which don't have a system timer to do it for them.
+pub struct Schedule<C> {
+    clock: C,
+    next_fetch: Cell<Option<Instant>>,
+}
+
+impl<C: Clock> Schedule<C> {
+    pub fn new(clock: C) -> Self {
+        Self {
+            clock,
+            next_fetch: Default::default(),
+        }
+    }
+
+    /// Time to wait until the next fetch is due.
+    ///
+    /// Fetches are scheduled at a fixed cadence, so time spent fetching and drawing doesn't accumulate.
+    pub fn time_until_next_fetch(&self) -> Duration {
+        let now = self.clock.now();
+        let next_fetch = match self.next_fetch.get() {
+            Some(prev_fetch) if prev_fetch + FETCH_INTERVAL >= now => prev_fetch + FETCH_INTERVAL,
+            // On the first fetch, or if we fell behind (e.g. the system was suspended),
+            // restart the cadence instead of fetching repeatedly to catch up.
+            _ => now + FETCH_INTERVAL,
+        };
+        self.next_fetch.set(Some(next_fetch));
+        next_fetch - now
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    #[test]
+    fn redraw_every_n_fetches() {
+        assert!(!is_redraw_due(1));
+        assert!(is_redraw_due(REDRAW_EVERY_N_FETCHES));
+        assert!(!is_redraw_due(REDRAW_EVERY_N_FETCHES + 1));
+        assert!(is_redraw_due(REDRAW_EVERY_N_FETCHES * 2));
+    }
+
+    #[test]
+    fn fixed_cadence() {
+        let clock = ManualClock::new();
+        let schedule = Schedule::new(clock.clone());
+
+        assert_eq!(schedule.time_until_next_fetch(), FETCH_INTERVAL);
+
+        // Fetching took some time, so wait less.
+        clock.advance(FETCH_INTERVAL + Duration::from_millis(30));
+        assert_eq!(
+            schedule.time_until_next_fetch(),
+            FETCH_INTERVAL - Duration::from_millis(30)
+        );
+
+        // Woke up exactly on time.
+        clock.advance(FETCH_INTERVAL - Duration::from_millis(30));
+        assert_eq!(schedule.time_until_next_fetch(), FETCH_INTERVAL);
+    }
+
+    #[test]
+    fn long_pause_restarts_cadence() {
+        let clock = ManualClock::new();
+        let schedule = Schedule::new(clock.clone());
+
+        schedule.time_until_next_fetch();
+        clock.advance(Duration::from_secs(60 * 60));
+        assert_eq!(schedule.time_until_next_fetch(), FETCH_INTERVAL);
+
+        clock.advance(FETCH_INTERVAL);
+        assert_eq!(schedule.time_until_next_fetch(), FETCH_INTERVAL);
+    }
+}
@@ -0,0 +1,362 @@
+use crate::clock::SystemClock;
+use crate::constants::{
+    TUI_EXTRA_VALUE_WIDTH, TUI_FREQUENCY_VALUE_WIDTH, TUI_LEFT_SPLIT_VALUE_WIDTH,
+    TUI_LEFT_VALUE_WIDTH, TUI_MICROPHONE_WARNING_WIDTH, TUI_RIGHT_VALUE_WIDTH,
+};
+use crate::layout::{
+    ExtraCell, Options, Values, extra_cells, format_cpu_frequency, format_disk, format_memory,
+    format_network, format_top_processes, is_cpu_frequency_low, memory_label,
+};
+use crate::metrics::Metrics;
+use crate::schedule::{Schedule, is_redraw_due};
+use std::cell::Cell;
+use std::io::{self, Write};
+#[cfg(target_os = "linux")]
+use std::process::Command;
+use std::thread;
+
+// https://en.wikipedia.org/wiki/ANSI_escape_code
+const CLEAR_LINE: &str = "\x1b[2K";
+const RED_BACKGROUND: &str = "\x1b[41m";
+const YELLOW_BACKGROUND: &str = "\x1b[43m";
+const RESET: &str = "\x1b[0m";
+
+/// Render metrics to the terminal until the process is killed.
+///
+/// Uses the same fetch and redraw intervals as the band, so the numbers match.
+///
+/// The per-core strip isn't shown, since it doesn't fit in a line of text.
+/// There's no tooltip, so the busiest processes are listed below the panel only while a threshold is crossed.
+pub fn run(metrics: &Metrics, options: Options) -> io::Result<()> {
+    let line_count = Cell::new(0);
+    let render = |redraw| {
+        let mut lines = format_lines(&Values::from_metrics(metrics), options, is_muted()).to_vec();
+        lines.extend(format_top_processes(&metrics.top_processes()));
+        let prev_line_count = if redraw { line_count.get() } else { 0 };
+        draw(&mut io::stdout().lock(), &lines, prev_line_count).map(|count| line_count.set(count))
+    };
+
+    let schedule = Schedule::new(SystemClock);
+
+    metrics.fetch();
+    render(false)?;
+
+    loop {
+        thread::sleep(schedule.time_until_next_fetch());
+
+        let fetch_count = metrics.fetch();
+        if is_redraw_due(fetch_count) {
+            render(true)?;
+        }
+    }
+}
+
+/// Write the panel, overwriting the previous one, which was `prev_line_count` lines long, and return how many lines were written.
+fn draw(out: &mut impl Write, lines: &[String], prev_line_count: usize) -> io::Result<usize> {
+    if prev_line_count > 0 {
+        // Move the cursor to the start of the line, N lines up
+        write!(out, "\x1b[{prev_line_count}F")?;
+    }
+    for line in lines {
+        writeln!(out, "{CLEAR_LINE}{line}")?;
+    }
+    // Clear lines left over from a longer panel, e.g. once processes are no longer listed.
+    let leftover_line_count = prev_line_count.saturating_sub(lines.len());
+    if leftover_line_count > 0 {
+        for _ in 0..leftover_line_count {
+            writeln!(out, "{CLEAR_LINE}")?;
+        }
+        write!(out, "\x1b[{leftover_line_count}F")?;
+    }
+    out.flush()?;
+    Ok(lines.len())
+}
+
+/// Lay out metrics the same way as the band: two lines, each with two columns of values and labels,
+/// with the microphone warning and any extra cells to the left.
+fn format_lines(values: &Values, options: Options, is_muted: bool) -> [String; 2] {
+    let Values { cpu, .. } = *values;
+    let extra_cells = extra_cells(values, options);
+
+    let blank = " ".repeat(TUI_MICROPHONE_WARNING_WIDTH);
+    let (warning_top, warning_bottom) = if is_muted {
+        (blank.clone(), blank)
+    } else {
+        // The emoji is two columns wide, but only counts as one char for padding.
+        let icon = format!("{:^1$}", "🎤", TUI_MICROPHONE_WARNING_WIDTH - 1);
+        (
+            format!("{RED_BACKGROUND}{icon}{RESET}"),
+            format!("{RED_BACKGROUND}{blank}{RESET}"),
+        )
+    };
+
+    let left_width = if options.is_left_column_split() {
+        TUI_LEFT_SPLIT_VALUE_WIDTH
+    } else {
+        TUI_LEFT_VALUE_WIDTH
+    };
+
+    // Extra cells fill columns leftwards, top line first, with labels padded to line up within each column.
+    let (mut extra_top, mut extra_bottom) = (String::new(), String::new());
+    for column in extra_cells.chunks(2).rev() {
+        let label_width = column
+            .iter()
+            .map(|cell| cell.label.chars().count())
+            .max()
+            .unwrap_or(0);
+        let cell = |cell: Option<&ExtraCell>| match cell {
+            Some(ExtraCell { value, label }) => {
+                format!("{value:>TUI_EXTRA_VALUE_WIDTH$} {label:label_width$} ")
+            }
+            None => " ".repeat(TUI_EXTRA_VALUE_WIDTH + label_width + 2),
+        };
+        extra_top += &cell(column.first());
+        extra_bottom += &cell(column.get(1));
+    }
+
+    // The CPU frequency goes between the columns, only on the top line, highlighted on both if it's low.
+    let (frequency_top, frequency_bottom) = match format_cpu_frequency(values, options) {
+        Some(frequency) => {
+            let top = format!(" {frequency:>TUI_FREQUENCY_VALUE_WIDTH$} GHz");
+            let bottom = " ".repeat(top.chars().count());
+            if is_cpu_frequency_low(values, options) {
+                (
+                    format!("{YELLOW_BACKGROUND}{top}{RESET}"),
+                    format!("{YELLOW_BACKGROUND}{bottom}{RESET}"),
+                )
+            } else {
+                (top, bottom)
+            }
+        }
+        None => (String::new(), String::new()),
+    };
+
+    let line = |warning: &str,
+                extra: &str,
+                left: (String, &str),
+                frequency: &str,
+                right: (String, &str)| {
+        let (left_value, left_label) = left;
+        let (right_value, right_label) = right;
+        let right_width = TUI_RIGHT_VALUE_WIDTH + 1;
+        format!(
+            "{warning}{extra}{left_value:>left_width$} {left_label}{frequency}{right_value:>right_width$} {right_label}"
+        )
+    };
+
+    [
+        line(
+            &warning_top,
+            &extra_top,
+            (format_network(values, options), "NET"),
+            &frequency_top,
+            (format!("{cpu:.0}%"), "CPU"),
+        ),
+        line(
+            &warning_bottom,
+            &extra_bottom,
+            (format_disk(values, options), "DSK"),
+            &frequency_bottom,
+            (format_memory(values, options), memory_label(options)),
+        ),
+    ]
+}
+
+/// Whether the default microphone is muted.
+///
+/// The band tracks this with listeners attached to its window, which we don't have, so poll instead.
+/// If the state can't be determined, assume muted, to avoid showing the microphone warning.
+#[cfg(target_os = "linux")]
+fn is_muted() -> bool {
+    // Works with both PulseAudio and PipeWire (via pipewire-pulse).
+    let output = match Command::new("pactl")
+        .args(["get-source-mute", "@DEFAULT_SOURCE@"])
+        .output()
+    {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            log::debug!("pactl failed with {}", output.status);
+            return true;
+        }
+        Err(e) => {
+            log::debug!("Failed to run pactl: {e}");
+            return true;
+        }
+    };
+
+    parse_pactl_mute(&String::from_utf8_lossy(&output.stdout)).unwrap_or_else(|| {
+        log::debug!("Unexpected pactl output");
+        true
+    })
+}
+
+/// Whether the default recording device is muted, polled like on Linux.
+#[cfg(windows)]
+fn is_muted() -> bool {
+    crate::window::is_default_microphone_muted().unwrap_or_else(|e| {
+        log::debug!("Failed to get microphone mute state: {e}");
+        true
+    })
+}
+
+/// Parse the output of `pactl get-source-mute`, e.g. `Mute: no`.
+#[cfg(target_os = "linux")]
+fn parse_pactl_mute(output: &str) -> Option<bool> {
+    match output.trim().strip_prefix("Mute: ")? {
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opt::{DiskDisplay, NetworkDisplay};
+
+    const VALUES: Values = Values {
+        cpu: 6.4,
+        cpu_frequency: Some(1234.0),
+        mem: 41.5,
+        swap: 12.6,
+        mem_pressure: Some(3.2),
+        net: 12.2,
+        net_received: 9.4,
+        net_sent: 2.8,
+        dsk: 1234.0,
+        dsk_read: 1000.0,
+        dsk_written: 234.0,
+        cpu_cores: Vec::new(),
+        extra_cells: Vec::new(),
+        battery: None,
+    };
+
+    #[test]
+    fn muted() {
+        assert_eq!(
+            format_lines(&VALUES, Options::default(), true),
+            ["      12 Mb/s NET   6% CPU", "    1234 MB/s DSK  42% RAM",]
+        );
+    }
+
+    #[test]
+    fn unmuted() {
+        assert_eq!(
+            format_lines(&VALUES, Options::default(), false),
+            [
+                "\x1b[41m 🎤 \x1b[0m  12 Mb/s NET   6% CPU",
+                "\x1b[41m    \x1b[0m1234 MB/s DSK  42% RAM",
+            ]
+        );
+    }
+
+    #[test]
+    fn network_directions() {
+        let options = Options {
+            network: NetworkDisplay::Both,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_lines(&VALUES, options, true),
+            [
+                "          ↓9 ↑3 Mb/s NET   6% CPU",
+                "           1234 MB/s DSK  42% RAM",
+            ]
+        );
+
+        let options = Options {
+            disk: DiskDisplay::Write,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_lines(&VALUES, options, true),
+            ["      12 Mb/s NET   6% CPU", "    W234 MB/s DSK  42% RAM",]
+        );
+    }
+
+    #[test]
+    fn cpu_frequency() {
+        let options = Options {
+            cpu_frequency: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_lines(&VALUES, options, true),
+            [
+                "      12 Mb/s NET  1.2 GHz   6% CPU",
+                "    1234 MB/s DSK           42% RAM",
+            ]
+        );
+
+        let options = Options {
+            cpu_frequency_warning_ghz: Some(2.0),
+            ..options
+        };
+        assert_eq!(
+            format_lines(&VALUES, options, true),
+            [
+                "      12 Mb/s NET\x1b[43m  1.2 GHz\x1b[0m   6% CPU",
+                "    1234 MB/s DSK\x1b[43m         \x1b[0m  42% RAM",
+            ]
+        );
+
+        // Not available, e.g. in a VM
+        let values = Values {
+            cpu_frequency: None,
+            ..VALUES
+        };
+        assert_eq!(
+            format_lines(&values, options, true),
+            ["      12 Mb/s NET   6% CPU", "    1234 MB/s DSK  42% RAM",]
+        );
+    }
+
+    #[test]
+    fn extra_cells() {
+        let cell = |value: &str, label: &str| ExtraCell {
+            value: value.to_owned(),
+            label: label.to_owned(),
+        };
+        let values = Values {
+            extra_cells: vec![cell("37%", "GPU"), cell("2048 MB", "VRAM"), cell("3", "Q")],
+            ..VALUES
+        };
+        assert_eq!(
+            format_lines(&values, Options::default(), true),
+            [
+                "            3 Q       37% GPU    12 Mb/s NET   6% CPU",
+                "                  2048 MB VRAM 1234 MB/s DSK  42% RAM",
+            ]
+        );
+    }
+
+    #[test]
+    fn redraw_overwrites_previous_lines() {
+        let lines = ["a".to_owned(), "b".to_owned()];
+
+        let mut out = Vec::new();
+        assert_eq!(draw(&mut out, &lines, 0).unwrap(), 2);
+        assert_eq!(out, b"\x1b[2Ka\n\x1b[2Kb\n");
+
+        let mut out = Vec::new();
+        assert_eq!(draw(&mut out, &lines, 2).unwrap(), 2);
+        assert_eq!(out, b"\x1b[2F\x1b[2Ka\n\x1b[2Kb\n");
+    }
+
+    #[test]
+    fn redraw_clears_leftover_lines() {
+        let lines = ["a".to_owned(), "b".to_owned()];
+
+        let mut out = Vec::new();
+        assert_eq!(draw(&mut out, &lines, 3).unwrap(), 2);
+        assert_eq!(out, b"\x1b[3F\x1b[2Ka\n\x1b[2Kb\n\x1b[2K\n\x1b[1F");
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn pactl_mute() {
+        assert_eq!(parse_pactl_mute("Mute: yes\n"), Some(true));
+        assert_eq!(parse_pactl_mute("Mute: no\n"), Some(false));
+        assert_eq!(parse_pactl_mute("Connection failure\n"), None);
+    }
+}
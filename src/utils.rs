@@ -1,8 +1,10 @@
 use std::fmt::{self, Display};
 
 use serde::{Deserialize, Serialize};
-use windows::Win32::Foundation::{POINT, RECT, SIZE};
+#[cfg(windows)]
+use windows::Win32::Foundation::{COLORREF, POINT, RECT, SIZE};
 
+#[cfg(windows)]
 pub trait RectExt {
     fn from_size(size: SIZE) -> Self;
 
@@ -18,6 +20,7 @@ pub trait RectExt {
     fn height(&self) -> i32;
 }
 
+#[cfg(windows)]
 impl RectExt for RECT {
     fn from_size(size: SIZE) -> Self {
         Self {
@@ -125,6 +128,7 @@ impl_scaleby!(i32, via: i64);
 impl_scaleby!(u16, via: u64);
 impl_scaleby!(u32, via: u64);
 
+#[cfg(windows)]
 impl ScaleBy for RECT {
     fn scale_by(self, by: ScalingFactor) -> Self {
         Self {
@@ -136,6 +140,7 @@ impl ScaleBy for RECT {
     }
 }
 
+#[cfg(windows)]
 impl ScaleBy for SIZE {
     fn scale_by(self, by: ScalingFactor) -> Self {
         Self {
@@ -145,6 +150,59 @@ impl ScaleBy for SIZE {
     }
 }
 
+/// 8-bit RGB color.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+#[cfg(windows)]
+impl From<Rgb> for COLORREF {
+    fn from(Rgb(r, g, b): Rgb) -> Self {
+        // COLORREF is 0x00BBGGRR
+        Self(u32::from(r) | (u32::from(g) << 8) | (u32::from(b) << 16))
+    }
+}
+
+/// Whether `text` matches `pattern`, ignoring case,
+/// where `*` matches any sequence of characters and `?` matches any single character.
+pub fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern
+        .chars()
+        .flat_map(char::to_lowercase)
+        .collect::<Vec<_>>();
+    let text = text
+        .chars()
+        .flat_map(char::to_lowercase)
+        .collect::<Vec<_>>();
+
+    let (mut p, mut t) = (0, 0);
+    // Position after the most recent `*`, and the text position it's currently matched up to.
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                backtrack = Some((p, t));
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                // Let the `*` match one more character, and try again.
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
 /// Represents an unscaled constant value.
 /// To prevent misuse, the inner value is not vailable unless you call `scale_by` or `into_inner`.
 #[derive(Copy, Clone, Serialize, Deserialize)]
@@ -181,6 +239,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[cfg(windows)]
     fn with_right_edge_at() {
         let before = RECT {
             left: 1,
@@ -195,6 +254,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(windows)]
     fn with_horizontal_midpoint_at() {
         let before = RECT {
             left: 1,
@@ -209,6 +269,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(windows)]
     fn with_horizontal_midpoint_at_odd_width() {
         let before = RECT {
             left: 1,
@@ -223,6 +284,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(windows)]
     fn with_vertical_midpoint_at() {
         let before = RECT {
             left: 1,
@@ -237,6 +299,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(windows)]
     fn with_vertical_midpoint_at_odd_height() {
         let before = RECT {
             left: 2,
@@ -250,6 +313,13 @@ mod tests {
         assert_eq!(after.size(), before.size());
     }
 
+    #[test]
+    #[cfg(windows)]
+    fn rgb_to_colorref() {
+        assert_eq!(COLORREF::from(Rgb(0x99, 0x00, 0x00)), COLORREF(0x00_00_99));
+        assert_eq!(COLORREF::from(Rgb(0x12, 0x34, 0x56)), COLORREF(0x56_34_12));
+    }
+
     #[test]
     fn scaling_by_zero() {
         assert_eq!(0.scale_by(ScalingFactor::from_ratio(0, 1)), 0);
@@ -288,4 +358,25 @@ mod tests {
         assert_eq!(2.scale_by(ScalingFactor::from_ratio(144, 96)), 3);
         assert_eq!(100.scale_by(ScalingFactor::from_ratio(144, 96)), 150);
     }
+
+    #[test]
+    fn glob() {
+        assert!(glob_matches("eth0", "eth0"));
+        assert!(glob_matches("eth*", "eth0"));
+        assert!(glob_matches("eth*", "eth"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("wi-fi*", "Wi-Fi 2"));
+        assert!(glob_matches(
+            "*VIRTUAL*",
+            "Hyper-V Virtual Ethernet Adapter"
+        ));
+        assert!(glob_matches("eth?", "eth1"));
+        assert!(glob_matches("*a*b", "aXbYab"));
+
+        assert!(!glob_matches("eth0", "eth1"));
+        assert!(!glob_matches("eth?", "eth"));
+        assert!(!glob_matches("eth*", "veth0"));
+        assert!(!glob_matches("*a*b", "aXbYa"));
+        assert!(!glob_matches("", "a"));
+    }
 }
@@ -1,8 +1,12 @@
 use crate::constants::{
     HOTKEY_MIC_MUTE, UM_ENABLE_DEBUG_PAINT, UM_ENABLE_KEEP_AWAKE, UM_INITIAL_METRICS,
-    UM_INITIAL_MIC_STATE, UM_INITIAL_RENDER,
+    UM_INITIAL_MIC_STATE, UM_INITIAL_RENDER, UM_SET_DISPLAY_OPTIONS, UM_SET_METRICS_OPTIONS,
+    UM_START_RECORDING,
 };
 use crate::defer;
+use crate::layout::Options;
+use crate::metrics;
+use crate::metrics::record::Recorder;
 use crate::opt::MicrophoneHotkey;
 use crate::window::proc::window_proc;
 use windows::Win32::Foundation::{HINSTANCE, LPARAM};
@@ -32,12 +36,27 @@ mod position;
 mod proc;
 mod state;
 mod timers;
+mod tooltip;
+
+/// Whether the default microphone is muted, for frontends without a window, i.e. the TUI.
+pub fn is_default_microphone_muted() -> Result<bool> {
+    // SAFETY: balanced by `CoUninitialize` below; fine to nest, e.g. if COM is already initialized on this thread
+    unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED | COINIT_DISABLE_OLE1DDE).ok()? };
+    defer! {
+        unsafe { CoUninitialize() };
+    };
+    microphone::is_default_muted()
+}
 
 /// Create the toplevel window, start timers for updating it, and pump the windows message loop.
 pub fn create_and_run_message_loop(
     mic_hotkey: Option<MicrophoneHotkey>,
     keep_awake_while_unlocked: bool,
+    keep_awake_min_battery_percent: Option<f64>,
+    display_options: Options,
+    metrics_options: metrics::Options,
     debug_paint: bool,
+    recorder: Option<Recorder>,
 ) -> Result<()> {
     // Initialize COM, to be used by the microphone management code.
     // Ideally, we would put this in the microphone state code, but the docs suggest that:
@@ -137,9 +156,51 @@ pub fn create_and_run_message_loop(
         };
     }
 
-    // Enqueue a message to tell the window to stay awake
+    // Enqueue a message to tell the window to stay awake, unless the battery is low
     if keep_awake_while_unlocked {
-        unsafe { PostMessageW(Some(window), WM_USER, UM_ENABLE_KEEP_AWAKE, LPARAM(0))? };
+        let min_battery_percent = Box::into_raw(Box::new(keep_awake_min_battery_percent));
+        if let Err(e) = unsafe {
+            PostMessageW(
+                Some(window),
+                WM_USER,
+                UM_ENABLE_KEEP_AWAKE,
+                LPARAM(min_battery_percent as isize),
+            )
+        } {
+            // SAFETY: the message wasn't posted, so we still own the threshold
+            drop(unsafe { Box::from_raw(min_battery_percent) });
+            return Err(e);
+        }
+    }
+
+    // Enqueue a message to tell the window how to display metrics
+    let display_options = Box::into_raw(Box::new(display_options));
+    if let Err(e) = unsafe {
+        PostMessageW(
+            Some(window),
+            WM_USER,
+            UM_SET_DISPLAY_OPTIONS,
+            LPARAM(display_options as isize),
+        )
+    } {
+        // SAFETY: the message wasn't posted, so we still own the options
+        drop(unsafe { Box::from_raw(display_options) });
+        return Err(e);
+    }
+
+    // Enqueue a message to tell the window how to collect metrics, before the initial metrics fetch
+    let metrics_options = Box::into_raw(Box::new(metrics_options));
+    if let Err(e) = unsafe {
+        PostMessageW(
+            Some(window),
+            WM_USER,
+            UM_SET_METRICS_OPTIONS,
+            LPARAM(metrics_options as isize),
+        )
+    } {
+        // SAFETY: the message wasn't posted, so we still own the options
+        drop(unsafe { Box::from_raw(metrics_options) });
+        return Err(e);
     }
 
     // Enqueue a message to tell the window about debug settings
@@ -147,6 +208,23 @@ pub fn create_and_run_message_loop(
         unsafe { PostMessageW(Some(window), WM_USER, UM_ENABLE_DEBUG_PAINT, LPARAM(0))? };
     }
 
+    // Enqueue a message to hand over the recorder, before the initial metrics fetch
+    if let Some(recorder) = recorder {
+        let recorder = Box::into_raw(Box::new(recorder));
+        if let Err(e) = unsafe {
+            PostMessageW(
+                Some(window),
+                WM_USER,
+                UM_START_RECORDING,
+                LPARAM(recorder as isize),
+            )
+        } {
+            // SAFETY: the message wasn't posted, so we still own the recorder
+            drop(unsafe { Box::from_raw(recorder) });
+            return Err(e);
+        }
+    }
+
     // Enqueue a message for initial metrics fetch
     unsafe { PostMessageW(Some(window), WM_USER, UM_INITIAL_METRICS, LPARAM(0))? };
 
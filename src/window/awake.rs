@@ -1,3 +1,4 @@
+use crate::metrics::Battery;
 use std::cell::Cell;
 use windows::Win32::System::Power::{
     ES_CONTINUOUS, ES_DISPLAY_REQUIRED, ES_SYSTEM_REQUIRED, EXECUTION_STATE,
@@ -7,6 +8,12 @@ use windows::core::{Error, Result};
 
 pub struct Awake {
     currently_kept_awake: Cell<Option<bool>>,
+    /// Whether the machine should be kept awake, e.g. because the session is unlocked, if the battery allows it.
+    wanted: Cell<bool>,
+    /// Don't keep the machine awake when running on battery below this charge.
+    min_battery_percent: Cell<Option<f64>>,
+    /// Whether the battery allowed keeping the machine awake, as of the latest fetch.
+    battery_allows: Cell<bool>,
 }
 
 impl Drop for Awake {
@@ -19,14 +26,40 @@ impl Awake {
     pub fn new() -> Self {
         Self {
             currently_kept_awake: Cell::new(None),
+            wanted: Cell::new(false),
+            min_battery_percent: Cell::new(None),
+            battery_allows: Cell::new(true),
         }
     }
 
-    pub fn enable(&self) {
+    pub fn enable(&self, min_battery_percent: Option<f64>) {
         self.currently_kept_awake.set(Some(false));
+        self.min_battery_percent.set(min_battery_percent);
     }
 
     pub fn keep_awake(&self, awake: bool) {
+        self.wanted.set(awake);
+        self.apply();
+    }
+
+    /// Stop keeping the machine awake while the battery is below the minimum, and resume once it's plugged in.
+    pub fn update_battery(&self, battery: Option<Battery>) {
+        let allows = match (battery, self.min_battery_percent.get()) {
+            (Some(battery), Some(min_percent)) => battery.allows_keep_awake(min_percent),
+            _ => true,
+        };
+        if self.battery_allows.replace(allows) != allows {
+            if allows {
+                log::info!("Battery is no longer low, allowing keep awake");
+            } else {
+                log::info!("Battery is low, no longer keeping awake");
+            }
+            self.apply();
+        }
+    }
+
+    fn apply(&self) {
+        let awake = self.wanted.get() && self.battery_allows.get();
         if let Err(e) = self.keep_awake_fallible(awake) {
             log::error!("Failed to set keep awake state to {awake}: {e}");
         }
@@ -2,6 +2,9 @@ use listener::ListenerManager;
 use std::cell::{Cell, RefCell};
 use std::ptr;
 use windows::Win32::Foundation::HWND;
+use windows::Win32::Media::Audio::Endpoints::IAudioEndpointVolume;
+use windows::Win32::Media::Audio::{IMMDeviceEnumerator, MMDeviceEnumerator, eCapture, eConsole};
+use windows::Win32::System::Com::{CLSCTX_INPROC_SERVER, CoCreateInstance};
 use windows::core::Result;
 
 mod listener;
@@ -72,3 +75,14 @@ impl Microphone {
         Ok(())
     }
 }
+
+/// Whether the default microphone is muted, checked once instead of listening for changes.
+///
+/// COM must be initialized on the calling thread.
+pub fn is_default_muted() -> Result<bool> {
+    let dev_enumerator: IMMDeviceEnumerator =
+        unsafe { CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_INPROC_SERVER)? };
+    let device = unsafe { dev_enumerator.GetDefaultAudioEndpoint(eCapture, eConsole)? };
+    let endpoint = unsafe { device.Activate::<IAudioEndpointVolume>(CLSCTX_INPROC_SERVER, None)? };
+    Ok(unsafe { endpoint.GetMute()? }.as_bool())
+}
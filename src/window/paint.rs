@@ -1,9 +1,9 @@
 use crate::constants::{
-    DEBUG_BACKGROUND_COLOR, FIRST_LINE_MIDPOINT_OFFSET_FROM_TOP, LABEL_WIDTH,
-    MICROPHONE_WARNING_COLOR, MICROPHONE_WARNING_WIDTH, RIGHT_COLUMN_WIDTH,
-    SECOND_LINE_MIDPOINT_OFFSET_FROM_TOP,
+    CPU_CORE_BAR_COLOR, CPU_CORE_TRACK_COLOR, DEBUG_BACKGROUND_COLOR, MICROPHONE_WARNING_COLOR,
+    TEXT_COLOR, THROTTLE_WARNING_COLOR,
 };
 use crate::defer;
+use crate::layout::{Align, Fill, Item, Options, Values, layout};
 use crate::metrics::Metrics;
 use crate::utils::{RectExt, ScaleBy, ScalingFactor};
 use std::cell::Cell;
@@ -36,6 +36,14 @@ pub struct Paint {
     debug_background_brush: HBRUSH,
     /// Brush for drawing the microphone warning.
     microphone_warning_brush: HBRUSH,
+    /// Brush for highlighting a low CPU frequency.
+    throttle_warning_brush: HBRUSH,
+    /// How to display metrics.
+    options: Cell<Options>,
+    /// Brush for drawing the unfilled part of per-core CPU bars.
+    cpu_core_track_brush: HBRUSH,
+    /// Brush for drawing the filled part of per-core CPU bars.
+    cpu_core_bar_brush: HBRUSH,
 }
 
 impl Drop for Paint {
@@ -53,25 +61,56 @@ impl Drop for Paint {
         if !unsafe { DeleteObject(self.microphone_warning_brush.into()) }.as_bool() {
             log::error!("DeleteObject failed: {}", Error::from_thread());
         }
+
+        if !unsafe { DeleteObject(self.throttle_warning_brush.into()) }.as_bool() {
+            log::error!("DeleteObject failed: {}", Error::from_thread());
+        }
+
+        if !unsafe { DeleteObject(self.cpu_core_track_brush.into()) }.as_bool() {
+            log::error!("DeleteObject failed: {}", Error::from_thread());
+        }
+
+        if !unsafe { DeleteObject(self.cpu_core_bar_brush.into()) }.as_bool() {
+            log::error!("DeleteObject failed: {}", Error::from_thread());
+        }
     }
 }
 
 impl Paint {
     pub fn new() -> Result<Self> {
-        let debug_background_brush = unsafe { CreateSolidBrush(DEBUG_BACKGROUND_COLOR) };
+        let debug_background_brush = unsafe { CreateSolidBrush(DEBUG_BACKGROUND_COLOR.into()) };
         if debug_background_brush.is_invalid() {
             return Err(Error::from_thread());
         }
 
-        let microphone_warning_brush = unsafe { CreateSolidBrush(MICROPHONE_WARNING_COLOR) };
+        let microphone_warning_brush = unsafe { CreateSolidBrush(MICROPHONE_WARNING_COLOR.into()) };
         if microphone_warning_brush.is_invalid() {
             return Err(Error::from_thread());
         }
 
+        let throttle_warning_brush = unsafe { CreateSolidBrush(THROTTLE_WARNING_COLOR.into()) };
+        if throttle_warning_brush.is_invalid() {
+            return Err(Error::from_thread());
+        }
+
+        let cpu_core_track_brush = unsafe { CreateSolidBrush(CPU_CORE_TRACK_COLOR.into()) };
+        if cpu_core_track_brush.is_invalid() {
+            return Err(Error::from_thread());
+        }
+
+        let cpu_core_bar_brush = unsafe { CreateSolidBrush(CPU_CORE_BAR_COLOR.into()) };
+        if cpu_core_bar_brush.is_invalid() {
+            return Err(Error::from_thread());
+        }
+
         Ok(Self {
             debug: Cell::new(false),
             debug_background_brush,
             microphone_warning_brush,
+            throttle_warning_brush,
+            options: Cell::new(Options::default()),
+            cpu_core_track_brush,
+            cpu_core_bar_brush,
             called_buffered_paint_init: {
                 // SAFETY: init and uninit must be called in pairs; after this point, we construct self, so drop will call uninit
                 unsafe { BufferedPaintInit()? }
@@ -84,6 +123,10 @@ impl Paint {
         self.debug.set(debug);
     }
 
+    pub fn set_options(&self, options: Options) {
+        self.options.set(options);
+    }
+
     /// Paint the window using the window's device context.
     pub fn render(
         &self,
@@ -211,89 +254,51 @@ impl Paint {
             }
         }
 
-        let middle_at = |x, y| {
-            move |r: RECT| {
-                r.with_horizontal_midpoint_at(x)
-                    .with_vertical_midpoint_at(y)
-            }
-        };
-        let right_mid_at =
-            |x, y| move |r: RECT| r.with_right_edge_at(x).with_vertical_midpoint_at(y);
-        let left_mid_at = |x, y| move |r: RECT| r.with_left_edge_at(x).with_vertical_midpoint_at(y);
-
-        let rect = |r: RECT, color: HBRUSH| {
-            if unsafe { FillRect(hdc, &r, color) } == 0 {
-                return Err(Error::from_thread());
+        let items = layout(
+            size.cx,
+            size.cy,
+            dpi,
+            &Values::from_metrics(metrics),
+            self.options.get(),
+            is_muted,
+            self.debug.get(),
+        );
+
+        for item in items {
+            match item {
+                Item::Rect { rect, fill } => {
+                    let rect = RECT {
+                        left: rect.left,
+                        top: rect.top,
+                        right: rect.right,
+                        bottom: rect.bottom,
+                    };
+                    let brush = match fill {
+                        Fill::DebugBackground => self.debug_background_brush,
+                        Fill::MicrophoneWarning => self.microphone_warning_brush,
+                        Fill::ThrottleWarning => self.throttle_warning_brush,
+                        Fill::CpuCoreTrack => self.cpu_core_track_brush,
+                        Fill::CpuCoreBar => self.cpu_core_bar_brush,
+                    };
+                    if unsafe { FillRect(hdc, &rect, brush) } == 0 {
+                        return Err(Error::from_thread());
+                    }
+                    // GDI does not properly support alpha, so we need to set the alpha channel manually afterwards.
+                    unsafe { BufferedPaintSetAlpha(buffered_paint, Some(&rect), 255)? };
+                }
+                Item::Text { text, x, y, align } => {
+                    draw_text(hdc, text_style, &text, |r: RECT| {
+                        let r = match align {
+                            Align::Left => r.with_left_edge_at(x),
+                            Align::Center => r.with_horizontal_midpoint_at(x),
+                            Align::Right => r.with_right_edge_at(x),
+                        };
+                        r.with_vertical_midpoint_at(y)
+                    })?;
+                }
             }
-            // GDI does not properly support alpha, so we need to set the alpha channel manually afterwards.
-            unsafe { BufferedPaintSetAlpha(buffered_paint, Some(&r), 255)? };
-            Ok(())
-        };
-
-        let text = |text: &str, position: &dyn Fn(RECT) -> RECT| {
-            draw_text(hdc, text_style, text, position)
-        };
-
-        // When debugging is enabled, fill in window background.
-
-        if self.debug.get() {
-            rect(RECT::from_size(size), self.debug_background_brush)?;
-        }
-
-        // Draw microphone warning if unmuted
-
-        if !is_muted {
-            rect(
-                RECT {
-                    top: 0,
-                    left: 0,
-                    bottom: size.cy,
-                    right: MICROPHONE_WARNING_WIDTH.scale_by(dpi),
-                },
-                self.microphone_warning_brush,
-            )?;
-
-            text(
-                "🎤",
-                &middle_at(MICROPHONE_WARNING_WIDTH.scale_by(dpi) / 2, size.cy / 2),
-            )?;
         }
 
-        // Draw metrics
-
-        let cpu = metrics.avg_cpu_percent();
-        let mem = metrics.avg_memory_percent();
-        let net = metrics.avg_network_mbit();
-        let dsk = metrics.avg_disk_mbyte();
-
-        let right_column = size.cx - LABEL_WIDTH.scale_by(dpi);
-        let left_column = size.cx - RIGHT_COLUMN_WIDTH.scale_by(dpi) - LABEL_WIDTH.scale_by(dpi);
-
-        let first_line_midpoint = FIRST_LINE_MIDPOINT_OFFSET_FROM_TOP.scale_by(dpi);
-        let second_line_midpoint = SECOND_LINE_MIDPOINT_OFFSET_FROM_TOP.scale_by(dpi);
-
-        text(" CPU", &left_mid_at(right_column, first_line_midpoint))?;
-        text(" RAM", &left_mid_at(right_column, second_line_midpoint))?;
-        text(" NET", &left_mid_at(left_column, first_line_midpoint))?;
-        text(" DSK", &left_mid_at(left_column, second_line_midpoint))?;
-
-        text(
-            &format!("{cpu:.0}%"),
-            &right_mid_at(right_column, first_line_midpoint),
-        )?;
-        text(
-            &format!("{mem:.0}%"),
-            &right_mid_at(right_column, second_line_midpoint),
-        )?;
-        text(
-            &format!("{net:.0} Mb/s"),
-            &right_mid_at(left_column, first_line_midpoint),
-        )?;
-        text(
-            &format!("{dsk:.0} MB/s"),
-            &right_mid_at(left_column, second_line_midpoint),
-        )?;
-
         Ok(())
     }
 }
@@ -332,7 +337,7 @@ fn draw_text(
             Some(&DTTOPTS {
                 dwSize: mem::size_of::<DTTOPTS>() as u32,
                 dwFlags: DTT_COMPOSITED | DTT_TEXTCOLOR,
-                crText: COLORREF(0xffffff),
+                crText: TEXT_COLOR.into(),
                 ..Default::default()
             }),
         )?
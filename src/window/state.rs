@@ -1,11 +1,16 @@
 use crate::constants::{
     HOTKEY_MIC_MUTE, HSHELL_RUDEAPPACTIVATED, HSHELL_WINDOWACTIVATED, IDT_FETCH_AND_REDRAW_TIMER,
-    IDT_MIC_STATE_TIMER, IDT_TRAY_POSITION_TIMER, IDT_Z_ORDER_TIMER, REDRAW_EVERY_N_FETCHES,
-    UM_ENABLE_DEBUG_PAINT, UM_ENABLE_KEEP_AWAKE, UM_INITIAL_METRICS, UM_INITIAL_MIC_STATE,
-    UM_INITIAL_RENDER, UM_QUEUE_MIC_STATE_CHECK, UM_QUEUE_TRAY_POSITION_CHECK, WTS_SESSION_LOCK,
-    WTS_SESSION_LOGOFF, WTS_SESSION_LOGON, WTS_SESSION_UNLOCK,
+    IDT_MIC_STATE_TIMER, IDT_TRAY_POSITION_TIMER, IDT_Z_ORDER_TIMER, UM_ENABLE_DEBUG_PAINT,
+    UM_ENABLE_KEEP_AWAKE, UM_INITIAL_METRICS, UM_INITIAL_MIC_STATE, UM_INITIAL_RENDER,
+    UM_QUEUE_MIC_STATE_CHECK, UM_QUEUE_TRAY_POSITION_CHECK, UM_SET_DISPLAY_OPTIONS,
+    UM_SET_METRICS_OPTIONS, UM_START_RECORDING, WTS_SESSION_LOCK, WTS_SESSION_LOGOFF,
+    WTS_SESSION_LOGON, WTS_SESSION_UNLOCK,
 };
-use crate::metrics::Metrics;
+use crate::layout::{Options, format_top_processes};
+use crate::metrics::record::Recorder;
+use crate::metrics::{self, Metrics};
+use crate::opt::MemoryUsage;
+use crate::schedule::is_redraw_due;
 use crate::utils::ScaleBy;
 use crate::window::awake::Awake;
 use crate::window::messages;
@@ -14,6 +19,7 @@ use crate::window::paint::Paint;
 use crate::window::position::Position;
 use crate::window::proc::ProcHandler;
 use crate::window::timers::Timers;
+use crate::window::tooltip::Tooltip;
 use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
 use windows::Win32::UI::WindowsAndMessaging::{
     PostQuitMessage, RegisterWindowMessageW, WM_DESTROY, WM_DISPLAYCHANGE, WM_DPICHANGED,
@@ -35,6 +41,8 @@ pub struct InfoBand {
     position: Position,
     /// Microphone state.
     mic: Microphone,
+    /// Tooltip listing the busiest processes.
+    tooltip: Tooltip,
     /// Performance metrics.
     metrics: Metrics,
 }
@@ -56,7 +64,8 @@ impl ProcHandler for InfoBand {
             paint: Paint::new()?,
             position: Position::new(window)?,
             mic: Microphone::new(window)?,
-            metrics: Metrics::new()?,
+            tooltip: Tooltip::new(window),
+            metrics: Metrics::new(MemoryUsage::Available)?,
         })
     }
 
@@ -137,19 +146,46 @@ impl ProcHandler for InfoBand {
             }
             WM_USER => match wparam {
                 UM_ENABLE_KEEP_AWAKE => {
-                    log::info!("Enabling keep awake (UM_ENABLE_KEEP_AWAKE)");
-                    self.awake.enable();
+                    // SAFETY: lparam was created by `Box::into_raw` when posting this message, and each message is only handled once
+                    let min_battery_percent =
+                        unsafe { Box::from_raw(lparam.0 as *mut Option<f64>) };
+                    log::info!(
+                        "Enabling keep awake with minimum battery {min_battery_percent:?} (UM_ENABLE_KEEP_AWAKE)"
+                    );
+                    self.awake.enable(*min_battery_percent);
                     self.awake.keep_awake(true);
                     LRESULT(0)
                 }
+                UM_SET_DISPLAY_OPTIONS => {
+                    // SAFETY: lparam was created by `Box::into_raw` when posting this message, and each message is only handled once
+                    let options = unsafe { Box::from_raw(lparam.0 as *mut Options) };
+                    log::info!("Setting display options to {options:?} (UM_SET_DISPLAY_OPTIONS)");
+                    self.paint.set_options(*options);
+                    LRESULT(0)
+                }
+                UM_SET_METRICS_OPTIONS => {
+                    // SAFETY: lparam was created by `Box::into_raw` when posting this message, and each message is only handled once
+                    let options = unsafe { Box::from_raw(lparam.0 as *mut metrics::Options) };
+                    log::info!("Setting metrics options to {options:?} (UM_SET_METRICS_OPTIONS)");
+                    self.metrics.set_options(*options);
+                    LRESULT(0)
+                }
                 UM_ENABLE_DEBUG_PAINT => {
                     log::info!("Enabling debug paint (UM_ENABLE_DEBUG_PAINT)");
                     self.paint.set_debug(true);
                     LRESULT(0)
                 }
+                UM_START_RECORDING => {
+                    log::info!("Starting recording (UM_START_RECORDING)");
+                    // SAFETY: lparam was created by `Box::into_raw` when posting this message, and each message is only handled once
+                    let recorder = unsafe { Box::from_raw(lparam.0 as *mut Recorder) };
+                    self.metrics.start_recording(*recorder);
+                    LRESULT(0)
+                }
                 UM_INITIAL_METRICS => {
                     log::info!("Initial metrics fetch (UM_INITIAL_METRICS)");
                     self.metrics.fetch();
+                    self.awake.update_battery(self.metrics.battery());
                     // Start timer for fetching metrics and redrawing.
                     self.timers.fetch_and_redraw.reschedule(window);
                     LRESULT(0)
@@ -238,12 +274,14 @@ impl ProcHandler for InfoBand {
                     log::info!("Pausing updates & keep-awake due to logoff (WTS_SESSION_LOGOFF)");
                     self.timers.fetch_and_redraw.kill(window);
                     self.awake.keep_awake(false);
+                    self.tooltip.hide();
                     LRESULT(0)
                 }
                 WTS_SESSION_LOCK => {
                     log::info!("Pausing updates & keep-awake due to lock (WTS_SESSION_LOCK)");
                     self.timers.fetch_and_redraw.kill(window);
                     self.awake.keep_awake(false);
+                    self.tooltip.hide();
                     LRESULT(0)
                 }
                 WTS_SESSION_UNLOCK => {
@@ -293,9 +331,20 @@ impl ProcHandler for InfoBand {
             WM_TIMER => match wparam {
                 IDT_FETCH_AND_REDRAW_TIMER => {
                     log::trace!("Fetching metrics (IDT_FETCH_AND_REDRAW_TIMER)");
+                    // Processes are only read while they're shown, so check for hovering before fetching.
+                    let (_, rect) = self.position.get();
+                    let hovered = self.tooltip.is_hovered(rect);
+                    self.metrics.set_processes_wanted(hovered);
                     let fetch_count = self.metrics.fetch();
+                    self.awake.update_battery(self.metrics.battery());
+                    if hovered {
+                        self.tooltip
+                            .show(&format_top_processes(&self.metrics.top_processes()), rect);
+                    } else {
+                        self.tooltip.hide();
+                    }
 
-                    if fetch_count.is_multiple_of(REDRAW_EVERY_N_FETCHES) {
+                    if is_redraw_due(fetch_count) {
                         log::trace!("Starting repaint (IDT_FETCH_AND_REDRAW_TIMER)");
                         let (dpi, rect) = self.position.get();
                         self.paint
@@ -0,0 +1,225 @@
+use std::cell::{Cell, OnceCell};
+use std::mem;
+use windows::Win32::Foundation::{HWND, LPARAM, POINT, RECT, WPARAM};
+use windows::Win32::UI::Controls::{
+    ICC_TAB_CLASSES, INITCOMMONCONTROLSEX, InitCommonControlsEx, TOOLTIPS_CLASSW, TTF_ABSOLUTE,
+    TTF_TRACK, TTM_ADDTOOLW, TTM_GETBUBBLESIZE, TTM_SETMAXTIPWIDTH, TTM_TRACKACTIVATE,
+    TTM_TRACKPOSITION, TTM_UPDATETIPTEXTW, TTS_ALWAYSTIP, TTS_NOPREFIX, TTTOOLINFOW,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    CW_USEDEFAULT, CreateWindowExW, GetCursorPos, SendMessageW, WINDOW_STYLE, WS_EX_TOPMOST,
+    WS_POPUP,
+};
+use windows::core::{Error, PWSTR, Result};
+
+/// Tooltip listing the busiest processes while the cursor is over the window.
+///
+/// Our window is transparent to the mouse, so it never gets hover messages, and instead we check the cursor position
+/// whenever metrics are fetched, and show a tracking tooltip, which is positioned manually.
+pub struct Tooltip {
+    /// The window which owns the tooltip.
+    owner: HWND,
+    /// Created the first time it's shown.
+    ///
+    /// Note: this window will be destroyed when the owner is destroyed.
+    window: OnceCell<HWND>,
+    /// Whether the tooltip is currently shown.
+    shown: Cell<bool>,
+}
+
+impl Tooltip {
+    pub fn new(owner: HWND) -> Self {
+        Self {
+            owner,
+            window: OnceCell::new(),
+            shown: Cell::new(false),
+        }
+    }
+
+    /// Whether the cursor is over `rect`, in screen coordinates.
+    pub fn is_hovered(&self, rect: RECT) -> bool {
+        let mut cursor = POINT::default();
+        // SAFETY: `cursor` is valid for writes
+        if let Err(e) = unsafe { GetCursorPos(&mut cursor) } {
+            // e.g. while the secure desktop is shown
+            log::debug!("GetCursorPos failed: {e}");
+            return false;
+        }
+        (rect.left..rect.right).contains(&cursor.x) && (rect.top..rect.bottom).contains(&cursor.y)
+    }
+
+    /// Show `lines` just outside of `rect` (i.e. above the taskbar), or hide the tooltip if there aren't any.
+    pub fn show(&self, lines: &[String], rect: RECT) {
+        if lines.is_empty() {
+            self.hide();
+            return;
+        }
+        if let Err(e) = self.show_fallible(lines, rect) {
+            log::error!("Showing tooltip failed: {e}");
+        }
+    }
+
+    fn show_fallible(&self, lines: &[String], rect: RECT) -> Result<()> {
+        let window = self.window()?;
+
+        let mut text = lines
+            .join("\r\n")
+            .encode_utf16()
+            .chain([0])
+            .collect::<Vec<_>>();
+        let info = TTTOOLINFOW {
+            lpszText: PWSTR(text.as_mut_ptr()),
+            ..self.tool_info()
+        };
+        // SAFETY: `info` and the text it points to are valid for the duration of the call, and the tooltip copies the text
+        unsafe {
+            SendMessageW(
+                window,
+                TTM_UPDATETIPTEXTW,
+                None,
+                Some(LPARAM(&info as *const _ as isize)),
+            )
+        };
+
+        // Low 16 bits contains width, high 16 bits contains height
+        // SAFETY: `info` is valid for the duration of the call
+        let size = unsafe {
+            SendMessageW(
+                window,
+                TTM_GETBUBBLESIZE,
+                None,
+                Some(LPARAM(&info as *const _ as isize)),
+            )
+        };
+        let width = i32::from(size.0 as u16);
+        let height = i32::from((size.0 >> 16) as u16);
+
+        // Right-aligned with the window, above it if the taskbar is at the bottom, otherwise below it.
+        let x = rect.right - width;
+        let y = if rect.top >= height {
+            rect.top - height
+        } else {
+            rect.bottom
+        };
+        // Coordinates are packed as 16-bit values, and can be negative on secondary monitors.
+        let position = (x as u16 as u32) | ((y as u16 as u32) << 16);
+        // SAFETY: no pointers are passed
+        unsafe {
+            SendMessageW(
+                window,
+                TTM_TRACKPOSITION,
+                None,
+                Some(LPARAM(position as isize)),
+            )
+        };
+
+        if !self.shown.replace(true) {
+            log::debug!("Showing tooltip");
+            // SAFETY: `info` is valid for the duration of the call
+            unsafe {
+                SendMessageW(
+                    window,
+                    TTM_TRACKACTIVATE,
+                    Some(WPARAM(1)),
+                    Some(LPARAM(&info as *const _ as isize)),
+                )
+            };
+        }
+        Ok(())
+    }
+
+    pub fn hide(&self) {
+        let Some(&window) = self.window.get() else {
+            return;
+        };
+        if self.shown.replace(false) {
+            log::debug!("Hiding tooltip");
+            let info = self.tool_info();
+            // SAFETY: `info` is valid for the duration of the call
+            unsafe {
+                SendMessageW(
+                    window,
+                    TTM_TRACKACTIVATE,
+                    Some(WPARAM(0)),
+                    Some(LPARAM(&info as *const _ as isize)),
+                )
+            };
+        }
+    }
+
+    /// Identifies the single tool in the tooltip.
+    fn tool_info(&self) -> TTTOOLINFOW {
+        TTTOOLINFOW {
+            // Without a manifest selecting version 6 of the common controls, the tooltip rejects the full size,
+            // which includes `lpReserved`.
+            cbSize: mem::offset_of!(TTTOOLINFOW, lpReserved) as u32,
+            uFlags: TTF_TRACK | TTF_ABSOLUTE,
+            hwnd: self.owner,
+            uId: 0,
+            ..Default::default()
+        }
+    }
+
+    fn window(&self) -> Result<HWND> {
+        if let Some(&window) = self.window.get() {
+            return Ok(window);
+        }
+
+        let controls = INITCOMMONCONTROLSEX {
+            dwSize: mem::size_of::<INITCOMMONCONTROLSEX>() as u32,
+            dwICC: ICC_TAB_CLASSES,
+        };
+        // SAFETY: `controls` is valid for the duration of the call
+        if !unsafe { InitCommonControlsEx(&controls) }.as_bool() {
+            return Err(Error::from_thread());
+        }
+
+        let window = unsafe {
+            CreateWindowExW(
+                WS_EX_TOPMOST,
+                TOOLTIPS_CLASSW,
+                None,
+                WS_POPUP | WINDOW_STYLE(TTS_ALWAYSTIP | TTS_NOPREFIX),
+                CW_USEDEFAULT,
+                CW_USEDEFAULT,
+                CW_USEDEFAULT,
+                CW_USEDEFAULT,
+                Some(self.owner),
+                None,
+                None,
+                None,
+            )?
+        };
+
+        let mut empty = [0u16];
+        let info = TTTOOLINFOW {
+            lpszText: PWSTR(empty.as_mut_ptr()),
+            ..self.tool_info()
+        };
+        // SAFETY: `info` and the text it points to are valid for the duration of the call, and the tooltip copies the text
+        let added = unsafe {
+            SendMessageW(
+                window,
+                TTM_ADDTOOLW,
+                None,
+                Some(LPARAM(&info as *const _ as isize)),
+            )
+        };
+        if added.0 == 0 {
+            return Err(Error::from_thread());
+        }
+
+        // Setting a maximum width makes the tooltip break lines at `\r\n`; this one is wide enough to never wrap.
+        // SAFETY: no pointers are passed
+        unsafe {
+            SendMessageW(
+                window,
+                TTM_SETMAXTIPWIDTH,
+                None,
+                Some(LPARAM(i16::MAX as isize)),
+            )
+        };
+
+        Ok(*self.window.get_or_init(|| window))
+    }
+}